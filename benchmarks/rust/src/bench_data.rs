@@ -16,7 +16,7 @@ fn main() {
     unsafe {
         libc::sleep(4);
     }
-    let rank = comm_rank::mpi_comm_rank();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
 
     let mut data = Vec::new();
     if rank == 0 {
@@ -25,7 +25,7 @@ fn main() {
         }
     }
 
-    barrier::mpi_barrier();
+    barrier::mpi_barrier(MPI_COMM_WORLD);
     let sw = Stopwatch::start_new();
 
     if rank == 0 {
@@ -40,7 +40,7 @@ fn main() {
                           MPI_COMM_WORLD);
     }
 
-    barrier::mpi_barrier();
+    barrier::mpi_barrier(MPI_COMM_WORLD);
     if rank == 0 {
         print!("{}", (sw.elapsed_ms() as f64) / (1000 as f64));
     }
@@ -6,14 +6,14 @@ extern crate rand;
 
 use stopwatch::Stopwatch;
 
-use mpirs::{comm_rank, num_procs, send, receive, init, finalize};
-use mpirs::comm_request::RequestProc;
+use mpirs::{comm_rank, init, finalize};
 use mpirs::mpi_comm::MPI_COMM_WORLD;
 use mpirs::barrier;
+use mpirs::reduce;
+use mpirs::op::Op;
 
 use rand::distributions::{IndependentSample, Range};
 
-const TAG: u64 = 42;
 const SIZE: u64 = 1024;
 const A: f32 = 313.37;
 
@@ -22,8 +22,7 @@ fn main() {
         libc::sleep(4);
     }
 
-    let rank = comm_rank::mpi_comm_rank();
-    let size = num_procs::mpi_get_num_procs();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
 
     let between = Range::new(0f32, 8192f32);
     let mut rng = rand::thread_rng();
@@ -37,26 +36,17 @@ fn main() {
         vec_b.push(between.ind_sample(&mut rng));
     }
 
-    barrier::mpi_barrier();
+    barrier::mpi_barrier(MPI_COMM_WORLD);
     let sw = Stopwatch::start_new();
 
     for (i, j) in vec_a.iter().zip(vec_b) {
         result += A * i + j;
     }
 
-    if rank == 0 {
-        let mut value: f32 = 0.0;
-        for _ in 0..size - 1 {
-            receive::mpi_recv(&mut value,
-                              RequestProc::Any,
-                              TAG,
-                              MPI_COMM_WORLD);
-        }
-    } else {
-        send::mpi_send(&result, RequestProc::Process(0), TAG, MPI_COMM_WORLD);
-    }
+    let mut total = Vec::new();
+    reduce::mpi_reduce(&[result], &mut total, &Op::sum(), 0, MPI_COMM_WORLD);
 
-    barrier::mpi_barrier();
+    barrier::mpi_barrier(MPI_COMM_WORLD);
 
     if rank == 0 {
         print!("{}", (sw.elapsed_ms() as f64) / (1000 as f64));
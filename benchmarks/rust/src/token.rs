@@ -16,9 +16,9 @@ fn main() {
         libc::sleep(4);
     }
 
-    let rank = comm_rank::mpi_comm_rank();
-    let size = num_procs::mpi_get_num_procs();
-    barrier::mpi_barrier();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+    barrier::mpi_barrier(MPI_COMM_WORLD);
     let sw = Stopwatch::start_new();
 
     if rank == 0 {
@@ -46,7 +46,7 @@ fn main() {
     }
 
 
-    barrier::mpi_barrier();
+    barrier::mpi_barrier(MPI_COMM_WORLD);
 
     if rank == 0 {
         print!("{}", (sw.elapsed_ms() as f64) / (1000 as f64));
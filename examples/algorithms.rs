@@ -0,0 +1,53 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, bcast, reduce, alltoall};
+use mpirs::algorithm::{self, BcastAlgorithm, AllreduceAlgorithm, AlltoallAlgorithm};
+use mpirs::op::Op;
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    // Every algorithm must give the same result. Small sizes keep the run short, so force each
+    // algorithm instead of relying on the decision tables.
+    algorithm::set_bcast_segment(3);
+    for alg in &[BcastAlgorithm::Binomial,
+                 BcastAlgorithm::Pipelined,
+                 BcastAlgorithm::ScatterAllgather] {
+        algorithm::set_bcast_algorithm(Some(*alg));
+        let root = size / 2;
+        let mut buf = if rank == root { (0..10).collect() } else { Vec::new() };
+        bcast::mpi_bcast_vec(&mut buf, 10, root, MPI_COMM_WORLD);
+        assert_eq!(buf, (0..10).collect::<Vec<u64>>());
+        println!("Process {} broadcast with {:?}", rank, alg);
+    }
+
+    let concat = Op::create(|a: &String, b: &String| format!("{}{}", a, b), false);
+    let order: String = (0..size).map(|r| format!("{}", r)).collect();
+    for alg in &[AllreduceAlgorithm::ReduceBcast,
+                 AllreduceAlgorithm::RecursiveDoubling,
+                 AllreduceAlgorithm::Ring] {
+        algorithm::set_allreduce_algorithm(Some(*alg));
+        let mut sum = Vec::new();
+        reduce::mpi_allreduce(&[rank, 1, 2, 3, 4], &mut sum, &Op::sum(), MPI_COMM_WORLD);
+        assert_eq!(sum, vec![size * (size - 1) / 2, size, 2 * size, 3 * size, 4 * size]);
+
+        let mut joined = Vec::new();
+        reduce::mpi_allreduce(&[format!("{}", rank)], &mut joined, &concat, MPI_COMM_WORLD);
+        assert_eq!(joined[0], order);
+        println!("Process {} reduced with {:?}", rank, alg);
+    }
+
+    for alg in &[AlltoallAlgorithm::Pairwise, AlltoallAlgorithm::Bruck] {
+        algorithm::set_alltoall_algorithm(Some(*alg));
+        let sendbuf: Vec<usize> = (0..size).map(|dest| rank * 100 + dest).collect();
+        let mut recvbuf = Vec::new();
+        alltoall::mpi_alltoall(&sendbuf, &mut recvbuf, MPI_COMM_WORLD);
+        assert_eq!(recvbuf, (0..size).map(|src| src * 100 + rank).collect::<Vec<usize>>());
+        println!("Process {} exchanged with {:?}", rank, alg);
+    }
+
+    finalize::mpi_finalize();
+}
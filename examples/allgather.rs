@@ -0,0 +1,28 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, allgather};
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    let mut ranks = Vec::new();
+    allgather::mpi_allgather(&[rank], &mut ranks, MPI_COMM_WORLD);
+    println!("Process {} has ranks {:?}", rank, ranks);
+
+    // Rank i contributes i elements, placed in reverse rank order
+    let counts: Vec<usize> = (0..size).collect();
+    let total: usize = counts.iter().sum();
+    let mut displs = vec![0; size];
+    for i in (0..size - 1).rev() {
+        displs[i] = displs[i + 1] + counts[i + 1];
+    }
+
+    let mut offsets = vec![0; total];
+    allgather::mpi_allgatherv(&vec![rank; rank], &mut offsets, &counts, &displs, MPI_COMM_WORLD);
+    println!("Process {} has blocks {:?}", rank, offsets);
+
+    finalize::mpi_finalize();
+}
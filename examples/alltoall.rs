@@ -0,0 +1,38 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, alltoall};
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    // Transpose: element (rank, i) ends up at (i, rank)
+    let row: Vec<usize> = (0..size).map(|i| rank * size + i).collect();
+    let mut column = Vec::new();
+    alltoall::mpi_alltoall(&row, &mut column, MPI_COMM_WORLD);
+    println!("Process {} has column {:?}", rank, column);
+
+    // Every process sends a string to even ranks and a number to odd ones
+    let blocks = (0..size)
+                     .map(|i| {
+                         if i % 2 == 0 {
+                             alltoall::to_block(&format!("from {}", rank))
+                         } else {
+                             alltoall::to_block(&(rank as u64))
+                         }
+                     })
+                     .collect::<Vec<_>>();
+    let mut received = Vec::new();
+    alltoall::mpi_alltoallw(&blocks, &mut received, MPI_COMM_WORLD);
+    if rank % 2 == 0 {
+        let words: Vec<String> = received.into_iter().map(alltoall::from_block).collect();
+        println!("Process {} got {:?}", rank, words);
+    } else {
+        let numbers: Vec<u64> = received.into_iter().map(alltoall::from_block).collect();
+        println!("Process {} got {:?}", rank, numbers);
+    }
+
+    finalize::mpi_finalize();
+}
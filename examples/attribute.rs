@@ -0,0 +1,52 @@
+extern crate mpirs;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use mpirs::{comm_rank, num_procs, init, finalize, barrier};
+use mpirs::attribute::{self, MPI_TAG_UB, MPI_UNIVERSE_SIZE, MPI_WTIME_IS_GLOBAL};
+use mpirs::mpi_comm::{self, MPIComm, MPI_COMM_WORLD};
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    assert_eq!(*attribute::mpi_comm_get_attr(MPI_COMM_WORLD, MPI_TAG_UB).unwrap(),
+               u64::max_value());
+    assert!(*attribute::mpi_comm_get_attr(MPI_COMM_WORLD, MPI_UNIVERSE_SIZE).unwrap() >= size);
+    assert!(*attribute::mpi_comm_get_attr(MPI_COMM_WORLD, MPI_WTIME_IS_GLOBAL).unwrap());
+
+    // A library keeps a private duplicate of every communicator it is used on. Duplicates of the
+    // user's communicator get their own, and the private one goes away with the communicator.
+    let freed = Arc::new(AtomicUsize::new(0));
+    let counter = freed.clone();
+    let private = attribute::mpi_comm_create_keyval(attribute::mpi_comm_null_copy_fn,
+                                                    move |_, inner: &MPIComm| {
+                                                        mpi_comm::mpi_comm_free(*inner);
+                                                        counter.fetch_add(1, Ordering::SeqCst);
+                                                    });
+    // Tuning data is inherited by duplicates
+    let tuning = attribute::mpi_comm_create_keyval(attribute::mpi_comm_dup_fn,
+                                                   attribute::mpi_comm_null_delete_fn);
+
+    let comm = mpi_comm::mpi_comm_dup(MPI_COMM_WORLD);
+    let inner = mpi_comm::mpi_comm_dup(comm);
+    attribute::mpi_comm_set_attr(comm, private, inner);
+    attribute::mpi_comm_set_attr(comm, tuning, vec![rank, 64]);
+    barrier::mpi_barrier(*attribute::mpi_comm_get_attr(comm, private).unwrap());
+
+    let dup = mpi_comm::mpi_comm_dup(comm);
+    assert!(attribute::mpi_comm_get_attr(dup, private).is_none());
+    assert_eq!(*attribute::mpi_comm_get_attr(dup, tuning).unwrap(), vec![rank, 64]);
+
+    mpi_comm::mpi_comm_free(dup);
+    assert_eq!(freed.load(Ordering::SeqCst), 0);
+    mpi_comm::mpi_comm_free(comm);
+    assert_eq!(freed.load(Ordering::SeqCst), 1);
+    println!("Process {} released its private communicator", rank);
+
+    attribute::mpi_comm_free_keyval(private);
+    attribute::mpi_comm_free_keyval(tuning);
+    finalize::mpi_finalize();
+}
@@ -0,0 +1,35 @@
+extern crate mpirs;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mpirs::{comm_rank, num_procs, init, finalize, barrier};
+use mpirs::algorithm::{self, BarrierAlgorithm};
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    for alg in &[BarrierAlgorithm::Central, BarrierAlgorithm::Dissemination] {
+        algorithm::set_barrier_algorithm(Some(*alg));
+
+        // The last process arrives late, nobody may leave before it
+        let start = Instant::now();
+        if rank == size - 1 {
+            thread::sleep(Duration::from_millis(200));
+        }
+        barrier::mpi_barrier(MPI_COMM_WORLD);
+        assert!(start.elapsed() >= Duration::from_millis(200) || size == 1);
+
+        // A second barrier entered while the first is still pending stays separate
+        let first = barrier::mpi_ibarrier(MPI_COMM_WORLD);
+        let second = barrier::mpi_ibarrier(MPI_COMM_WORLD);
+        second.wait();
+        first.wait();
+        println!("Process {} passed the {:?} barriers", rank, alg);
+    }
+
+    finalize::mpi_finalize();
+}
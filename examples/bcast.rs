@@ -26,7 +26,7 @@ fn main() {
         libc::usleep(32000);
     }
 
-    let rank = comm_rank::mpi_comm_rank();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
     let mut token = Token { val: 65 };
 
     bcast::mpi_bcast(&mut token, 1, MPI_COMM_WORLD);
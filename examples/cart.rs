@@ -0,0 +1,52 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, reduce, sendrecv};
+use mpirs::topology;
+use mpirs::mpi_comm::{self, MPI_COMM_WORLD};
+use mpirs::comm_request::RequestProc;
+use mpirs::op::Op;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    // Rows wrap around, columns do not
+    let mut dims = vec![0, 0];
+    topology::mpi_dims_create(size, &mut dims);
+    let grid = topology::mpi_cart_create(MPI_COMM_WORLD, &dims, &[false, true]).unwrap();
+    let (_, _, coords) = topology::mpi_cart_get(grid);
+    let me = comm_rank::mpi_comm_rank(grid);
+    assert_eq!(topology::mpi_cartdim_get(grid), 2);
+    assert_eq!(topology::mpi_cart_coords(grid, me), coords);
+    assert_eq!(topology::mpi_cart_rank(grid, &[coords[0] as isize, coords[1] as isize]), me);
+
+    // Pass the rank one step along each dimension. Beyond the edges of the grid the neighbours
+    // are ProcNull, so the first and last rows need no special case.
+    for direction in 0..2 {
+        let (source, dest) = topology::mpi_cart_shift(grid, direction, 1);
+        let mut from = usize::max_value();
+        sendrecv::mpi_sendrecv(&me, dest, 0, &mut from, source, 0, grid);
+        match source {
+            RequestProc::Process(source) => assert_eq!(from, source),
+            _ => assert_eq!((direction, coords[0], from), (0, 0, usize::max_value())),
+        }
+    }
+
+    // Sum over each row of the grid
+    let row = topology::mpi_cart_sub(grid, &[false, true]);
+    assert_eq!(topology::mpi_cart_get(row), (vec![dims[1]], vec![true], vec![coords[1]]));
+    let mut sum = Vec::new();
+    reduce::mpi_allreduce(&[me], &mut sum, &Op::sum(), row);
+    let first = coords[0] * dims[1];
+    assert_eq!(sum[0], (first..first + dims[1]).sum());
+    println!("Process {} sits at {:?} of a {:?} grid, row sum {}",
+             rank,
+             coords,
+             dims,
+             sum[0]);
+
+    mpi_comm::mpi_comm_free(row);
+    mpi_comm::mpi_comm_free(grid);
+    finalize::mpi_finalize();
+}
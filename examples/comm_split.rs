@@ -0,0 +1,79 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, reduce, barrier, send, receive};
+use mpirs::mpi_comm::{self, CommCompare, MPI_COMM_WORLD};
+use mpirs::comm_request::{Extract, RequestProc};
+use mpirs::group;
+use mpirs::op::Op;
+use mpirs::receiver_traits::Message;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    // Lay the processes out on a grid with two columns
+    let cols = 2;
+    let row = mpi_comm::mpi_comm_split(MPI_COMM_WORLD, Some(rank / cols), rank % cols).unwrap();
+    let col = mpi_comm::mpi_comm_split(MPI_COMM_WORLD, Some(rank % cols), rank / cols).unwrap();
+    assert_eq!(comm_rank::mpi_comm_rank(row), rank % cols);
+    assert_eq!(comm_rank::mpi_comm_rank(col), rank / cols);
+
+    let mut row_sum = Vec::new();
+    reduce::mpi_allreduce(&[rank], &mut row_sum, &Op::sum(), row);
+    let mut col_sum = Vec::new();
+    reduce::mpi_allreduce(&[rank], &mut col_sum, &Op::sum(), col);
+    println!("Process {} has row sum {} and column sum {}", rank, row_sum[0], col_sum[0]);
+
+    // Messages on a duplicate never match those on the original, even with the same tag
+    let dup = mpi_comm::mpi_comm_dup(MPI_COMM_WORLD);
+    assert_eq!(mpi_comm::mpi_comm_compare(dup, MPI_COMM_WORLD), CommCompare::Congruent);
+    if size > 1 {
+        if rank == 0 {
+            let on_world = send::mpi_isend(&1u32, RequestProc::Process(1), 7, MPI_COMM_WORLD);
+            let on_dup = send::mpi_isend(&2u32, RequestProc::Process(1), 7, dup);
+            on_world.wait();
+            on_dup.wait();
+        } else if rank == 1 {
+            let mut on_dup = 0u32;
+            let mut on_world = 0u32;
+            receive::mpi_recv(&mut on_dup, RequestProc::Process(0), 7, dup);
+            receive::mpi_recv(&mut on_world, RequestProc::Process(0), 7, MPI_COMM_WORLD);
+            assert_eq!((on_world, on_dup), (1, 2));
+        }
+    }
+
+    // Reverse the order of the processes, then keep only the even ones
+    let reversed = mpi_comm::mpi_comm_split(MPI_COMM_WORLD, Some(0), size - rank).unwrap();
+    assert_eq!(comm_rank::mpi_comm_rank(reversed), size - 1 - rank);
+    assert!(size == 1 || mpi_comm::mpi_comm_compare(reversed, dup) == CommCompare::Similar);
+
+    // Received messages name their source by its rank in the communicator
+    let me = comm_rank::mpi_comm_rank(reversed);
+    if me == 0 {
+        for _ in 1..size {
+            let status = receive::mpi_irecv(&mut 0, RequestProc::Any, 8, reversed).recv().unwrap();
+            let from: usize = Extract::data(&status).unwrap();
+            assert_eq!(status.src(), Some(RequestProc::Process(from)));
+        }
+    } else {
+        send::mpi_send(&me, RequestProc::Process(0), 8, reversed);
+    }
+
+    let world = group::mpi_comm_group(MPI_COMM_WORLD);
+    let evens: Vec<usize> = (0..size).filter(|r| r % 2 == 0).collect();
+    let even = mpi_comm::mpi_comm_create(MPI_COMM_WORLD, &group::mpi_group_incl(&world, &evens));
+    assert_eq!(even.is_some(), rank % 2 == 0);
+    if let Some(even) = even {
+        barrier::mpi_barrier(even);
+        println!("Process {} is rank {} of the even processes",
+                 rank,
+                 comm_rank::mpi_comm_rank(even));
+        mpi_comm::mpi_comm_free(even);
+    }
+
+    for comm in vec![row, col, dup, reversed] {
+        mpi_comm::mpi_comm_free(comm);
+    }
+    finalize::mpi_finalize();
+}
@@ -0,0 +1,112 @@
+//! Run as two jobs that find each other through a name server:
+//!
+//! ```text
+//! mpirun --name-server -p 31400 &
+//! MPIRS_NAME_SERVER=31400 mpirun -n 2 -p 31401 connect simulation &
+//! MPIRS_NAME_SERVER=31400 mpirun -n 3 -p 31402 connect visualization
+//! ```
+extern crate mpirs;
+
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::Duration;
+
+use mpirs::{comm_rank, num_procs, init, finalize, reduce, barrier, sendrecv};
+use mpirs::{info, port};
+use mpirs::mpi_comm::{self, MPIComm, MPI_COMM_WORLD};
+use mpirs::comm_request::RequestProc;
+use mpirs::op::Op;
+
+const SERVICE: &'static str = "simulation";
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+    let hints = info::mpi_info_create();
+    let simulation = env::args().nth(1).expect("Pass simulation or visualization") == "simulation";
+
+    // Connections through ports that lead nowhere fail on every process
+    let failed = panic::catch_unwind(AssertUnwindSafe(|| if simulation {
+        let port_name = port::mpi_open_port(&hints);
+        port::mpi_close_port(&port_name);
+        port::mpi_comm_accept(&port_name, &hints, 0, MPI_COMM_WORLD)
+    } else {
+        port::mpi_comm_connect("127.0.0.1:1/0", &hints, 0, MPI_COMM_WORLD)
+    }));
+    assert!(failed.is_err());
+
+    let other = if simulation {
+        let port_name = port::mpi_open_port(&hints);
+        if rank == 0 {
+            port::mpi_publish_name(SERVICE, &hints, &port_name);
+        }
+        let other = port::mpi_comm_accept(&port_name, &hints, 0, MPI_COMM_WORLD);
+        if rank == 0 {
+            port::mpi_unpublish_name(SERVICE, &hints, &port_name);
+        }
+        port::mpi_close_port(&port_name);
+        other
+    } else {
+        // The simulation may not have published its port yet
+        let mut port_name = None;
+        if rank == 0 {
+            while port_name.is_none() {
+                port_name = port::mpi_lookup_name(SERVICE, &hints);
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+        let port_name = port_name.unwrap_or_default();
+        port::mpi_comm_connect(&port_name, &hints, 0, MPI_COMM_WORLD)
+    };
+    exchange(other, rank, simulation);
+
+    // A duplicate reaches the other job just the same
+    let dup = mpi_comm::mpi_comm_dup(other);
+    exchange(dup, rank, simulation);
+    mpi_comm::mpi_comm_free(dup);
+
+    // Both jobs together in one communicator, the simulation first
+    let all = mpi_comm::mpi_intercomm_merge(other, !simulation);
+    let remote_size = num_procs::mpi_comm_remote_size(other);
+    let mut total = Vec::new();
+    reduce::mpi_allreduce(&[1usize], &mut total, &Op::sum(), all);
+    assert_eq!(total[0], size + remote_size);
+    let me = comm_rank::mpi_comm_rank(all);
+    assert_eq!(me, if simulation { rank } else { remote_size + rank });
+    barrier::mpi_barrier(all);
+    println!("Process {} of the {} is rank {} of {}",
+             rank,
+             if simulation { "simulation" } else { "visualization" },
+             me,
+             total[0]);
+
+    mpi_comm::mpi_comm_free(all);
+    mpi_comm::mpi_comm_free(other);
+    finalize::mpi_finalize();
+}
+
+/// Pair up the processes of both jobs by rank and let them swap a value. Then sum over the
+/// other job.
+fn exchange(other: MPIComm, rank: usize, simulation: bool) {
+    let remote_size = num_procs::mpi_comm_remote_size(other);
+    let value = if simulation { 100 + rank } else { 200 + rank };
+    if rank < remote_size && rank < num_procs::mpi_get_num_procs(other) {
+        let mut reply = 0usize;
+        sendrecv::mpi_sendrecv(&value,
+                               RequestProc::Process(rank),
+                               0,
+                               &mut reply,
+                               RequestProc::Process(rank),
+                               0,
+                               other);
+        assert_eq!(reply, if simulation { 200 + rank } else { 100 + rank });
+    }
+
+    let mut sum = Vec::new();
+    reduce::mpi_allreduce(&[value], &mut sum, &Op::sum(), other);
+    let base = if simulation { 200 } else { 100 };
+    assert_eq!(sum[0], (0..remote_size).map(|r| base + r).sum());
+    barrier::mpi_barrier(other);
+}
@@ -0,0 +1,112 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, allgather, barrier};
+use mpirs::{info, window};
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+use mpirs::op::Op;
+use mpirs::window::LockType;
+
+const ROUNDS: usize = 10;
+/// Every process exposes a counter, a value guarded by locks, a spin lock with the value it
+/// guards and a hash table
+const COUNTER: usize = 0;
+const GUARDED: usize = 1;
+const SPIN: usize = 2;
+const SPUN: usize = 3;
+const TABLE: usize = 4;
+const SLOTS: usize = 2 * ROUNDS + 2;
+
+/// Insert `key`, not 0, into the table of process `key % size`. Returns whether this call
+/// inserted it, rather than finding it there.
+fn insert(key: usize, size: usize, win: window::Window<usize>) -> bool {
+    let target = key % size;
+    for probe in 0..SLOTS {
+        let slot = TABLE + (key / size + probe) % SLOTS;
+        match window::mpi_compare_and_swap(&key, &0, target, slot, win) {
+            0 => return true,
+            old if old == key => return false,
+            _ => {}
+        }
+    }
+    panic!("Table of process {} is full", target);
+}
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+    let hints = info::mpi_info_create();
+    let win = window::mpi_win_allocate::<usize>(TABLE + SLOTS, &hints, MPI_COMM_WORLD);
+
+    // Everyone draws tickets from the counter of process 0 at once, no two the same
+    window::mpi_win_lock_all(win);
+    let mut tickets = Vec::new();
+    for _ in 0..ROUNDS {
+        tickets.push(window::mpi_fetch_and_op(&1, 0, COUNTER, &Op::sum(), win));
+    }
+    window::mpi_win_flush(0, win);
+    window::mpi_win_unlock_all(win);
+    let mut drawn = Vec::new();
+    allgather::mpi_allgather(&tickets, &mut drawn, MPI_COMM_WORLD);
+    drawn.sort();
+    assert_eq!(drawn, (0..size * ROUNDS).collect::<Vec<_>>());
+
+    // Plain reads and writes are safe under an exclusive lock
+    for _ in 0..ROUNDS {
+        window::mpi_win_lock(LockType::Exclusive, 0, win);
+        let mut value = [0];
+        window::mpi_get(&mut value, 0, GUARDED, win);
+        window::mpi_put(&[value[0] + 1], 0, GUARDED, win);
+        window::mpi_win_unlock(0, win);
+    }
+    barrier::mpi_barrier(MPI_COMM_WORLD);
+    window::mpi_win_lock(LockType::Shared, 0, win);
+    let mut guarded = [0];
+    window::mpi_get(&mut guarded, 0, GUARDED, win);
+    window::mpi_win_unlock(0, win);
+    assert_eq!(guarded[0], size * ROUNDS);
+
+    // The same with a spin lock built from compare and swap, holding the number of its owner
+    window::mpi_win_lock_all(win);
+    for _ in 0..ROUNDS {
+        while window::mpi_compare_and_swap(&(rank + 1), &0, 0, SPIN, win) != 0 {}
+        let mut value = [0];
+        window::mpi_get(&mut value, 0, SPUN, win);
+        window::mpi_put(&[value[0] + 1], 0, SPUN, win);
+        let mut owner = [0];
+        window::mpi_get(&mut owner, 0, SPIN, win);
+        assert_eq!(owner[0], rank + 1);
+        window::mpi_put(&[0], 0, SPIN, win);
+    }
+    window::mpi_win_unlock_all(win);
+    barrier::mpi_barrier(MPI_COMM_WORLD);
+    window::mpi_win_lock(LockType::Shared, 0, win);
+    let mut spun = [0];
+    window::mpi_get(&mut spun, 0, SPUN, win);
+    window::mpi_win_unlock(0, win);
+    assert_eq!(spun[0], size * ROUNDS);
+
+    // A hash table spread over all processes. Every process inserts keys of its own and one key
+    // all of them share, which only one of them gets to insert.
+    let keys: Vec<usize> = (0..ROUNDS).map(|i| rank * ROUNDS + i + 1).collect();
+    let shared = size * ROUNDS + 1;
+    window::mpi_win_lock_all(win);
+    for &key in &keys {
+        assert!(insert(key, size, win));
+    }
+    let won = insert(shared, size, win);
+    window::mpi_win_unlock_all(win);
+
+    let mut winners = Vec::new();
+    allgather::mpi_allgather(&[won], &mut winners, MPI_COMM_WORLD);
+    assert_eq!(winners.iter().filter(|&&w| w).count(), 1);
+
+    let exposed = window::mpi_win_free(win);
+    let mut stored: Vec<usize> = exposed[TABLE..].iter().cloned().filter(|&k| k != 0).collect();
+    stored.sort();
+    let expected: Vec<usize> = (1..shared + 1).filter(|k| k % size == rank).collect();
+    assert_eq!(stored, expected);
+
+    println!("Process {} drew tickets {:?}", rank, tickets);
+    finalize::mpi_finalize();
+}
@@ -0,0 +1,56 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, send, receive};
+use mpirs::topology::{self, TopoType};
+use mpirs::mpi_comm::{self, MPI_COMM_WORLD};
+use mpirs::comm_request::RequestProc;
+use mpirs::receiver_traits::Message;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    // A star: process 0 is connected to all others
+    let mut index = vec![size - 1];
+    let mut edges: Vec<usize> = (1..size).collect();
+    for r in 1..size {
+        index.push(size - 1 + r);
+        edges.push(0);
+    }
+    let star = topology::mpi_graph_create(MPI_COMM_WORLD, &index, &edges).unwrap();
+    assert_eq!(topology::mpi_topo_test(star), Some(TopoType::Graph));
+    assert_eq!(topology::mpi_graphdims_get(star), (size, 2 * (size - 1)));
+    let neighbors = topology::mpi_graph_neighbors(star, rank);
+    assert_eq!(topology::mpi_graph_neighbors_count(star, rank), neighbors.len());
+    println!("Process {} has star neighbours {:?}", rank, neighbors);
+
+    // A directed ring where every process sends to the next two
+    let hops: Vec<usize> = (1..3).filter(|&d| d < size).collect();
+    let sources: Vec<usize> = hops.iter().map(|d| (rank + size - d) % size).collect();
+    let destinations: Vec<usize> = hops.iter().map(|d| (rank + d) % size).collect();
+    let ring = topology::mpi_dist_graph_create_adjacent(MPI_COMM_WORLD, &sources, &destinations);
+    assert_eq!(topology::mpi_topo_test(ring), Some(TopoType::DistGraph));
+    assert_eq!(topology::mpi_dist_graph_neighbors_count(ring),
+               (sources.len(), destinations.len()));
+
+    let (sources, destinations) = topology::mpi_dist_graph_neighbors(ring);
+    let sent: Vec<_> = destinations.iter()
+                                   .map(|&d| {
+                                       send::mpi_isend(&rank, RequestProc::Process(d), 0, ring)
+                                   })
+                                   .collect();
+    for &s in &sources {
+        let mut from = 0usize;
+        receive::mpi_recv(&mut from, RequestProc::Process(s), 0, ring);
+        assert_eq!(from, s);
+    }
+    for s in sent {
+        s.wait();
+    }
+    println!("Process {} received from {:?}", rank, sources);
+
+    mpi_comm::mpi_comm_free(ring);
+    mpi_comm::mpi_comm_free(star);
+    finalize::mpi_finalize();
+}
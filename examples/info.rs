@@ -0,0 +1,70 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, reduce};
+use mpirs::{group, info, topology};
+use mpirs::mpi_comm::{self, MPI_COMM_WORLD};
+use mpirs::op::Op;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    assert_eq!(mpi_comm::mpi_comm_get_name(MPI_COMM_WORLD), "MPI_COMM_WORLD");
+
+    // Split into even and odd ranks and name the halves, so mpirun --trace tells them apart
+    let half = mpi_comm::mpi_comm_split(MPI_COMM_WORLD, Some(rank % 2), rank).unwrap();
+    assert_eq!(mpi_comm::mpi_comm_get_name(half), "");
+    mpi_comm::mpi_comm_set_name(half, if rank % 2 == 0 { "even" } else { "odd" });
+    assert_eq!(half.to_string(), mpi_comm::mpi_comm_get_name(half));
+
+    let mut hints = info::mpi_info_create();
+    info::mpi_info_set(&mut hints, "mpi_assert_no_any_tag", "true");
+    info::mpi_info_set(&mut hints, "mpirs_purpose", "sums");
+    mpi_comm::mpi_comm_set_info(half, &hints);
+
+    // Duplicates inherit the hints, unless they are given their own
+    let dup = mpi_comm::mpi_comm_dup(half);
+    assert_eq!(mpi_comm::mpi_comm_get_info(dup), hints);
+    let plain = mpi_comm::mpi_comm_dup_with_info(half, &info::mpi_info_create());
+    assert_eq!(info::mpi_info_get_nkeys(&mpi_comm::mpi_comm_get_info(plain)), 0);
+
+    // New communicators can be given hints right away
+    let whole = mpi_comm::mpi_comm_split_with_info(half, Some(0), rank, &hints).unwrap();
+    assert_eq!(mpi_comm::mpi_comm_get_info(whole), hints);
+    let all = group::mpi_comm_group(half);
+    let created = mpi_comm::mpi_comm_create_with_info(half, &all, &hints).unwrap();
+    assert_eq!(mpi_comm::mpi_comm_get_info(created), hints);
+    mpi_comm::mpi_comm_free(created);
+    mpi_comm::mpi_comm_free(whole);
+
+    // So can intercommunicators and those with a topology. The leaders of the halves are world
+    // ranks 0 and 1.
+    let inter =
+        mpi_comm::mpi_intercomm_create_with_info(half, 0, MPI_COMM_WORLD, 1 - rank % 2, 5, &hints);
+    assert_eq!(mpi_comm::mpi_comm_get_info(inter), hints);
+    let merged = mpi_comm::mpi_intercomm_merge_with_info(inter, rank % 2 == 1, &hints);
+    assert_eq!(mpi_comm::mpi_comm_get_info(merged), hints);
+    let n = num_procs::mpi_get_num_procs(merged);
+    let ring = topology::mpi_cart_create_with_info(merged, &[n], &[true], &hints).unwrap();
+    assert_eq!(mpi_comm::mpi_comm_get_info(ring), hints);
+    let graph = topology::mpi_graph_create_with_info(merged, &vec![0; n], &[], &hints).unwrap();
+    assert_eq!(mpi_comm::mpi_comm_get_info(graph), hints);
+    let dist = topology::mpi_dist_graph_create_adjacent_with_info(merged, &[], &[], &hints);
+    assert_eq!(mpi_comm::mpi_comm_get_info(dist), hints);
+    for comm in vec![dist, graph, ring, merged, inter] {
+        mpi_comm::mpi_comm_free(comm);
+    }
+
+    let mut sum = Vec::new();
+    reduce::mpi_allreduce(&[rank], &mut sum, &Op::sum(), half);
+
+    let hints = mpi_comm::mpi_comm_get_info(dup);
+    let keys: Vec<String> = (0..info::mpi_info_get_nkeys(&hints))
+                                .map(|n| info::mpi_info_get_nthkey(&hints, n))
+                                .collect();
+    println!("Process {} summed {} on {} with hints {:?}", rank, sum[0], half, keys);
+
+    mpi_comm::mpi_comm_free(plain);
+    mpi_comm::mpi_comm_free(dup);
+    mpi_comm::mpi_comm_free(half);
+    finalize::mpi_finalize();
+}
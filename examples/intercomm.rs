@@ -0,0 +1,158 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, reduce, allgather, barrier, sendrecv};
+use mpirs::{bcast, gather, scatter, alltoall, reduce_scatter};
+use mpirs::mpi_comm::{self, MPI_COMM_WORLD, MPI_ROOT, MPI_PROC_NULL};
+use mpirs::comm_request::RequestProc;
+use mpirs::group;
+use mpirs::op::Op;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+    assert!(size >= 2, "Needs at least two processes");
+
+    // Even processes solve the fluid, odd ones the structure. World ranks 0 and 1 lead them.
+    let fluid = rank % 2 == 0;
+    let local = mpi_comm::mpi_comm_split(MPI_COMM_WORLD, Some(rank % 2), rank).unwrap();
+    let remote_leader = if fluid { 1 } else { 0 };
+    let inter = mpi_comm::mpi_intercomm_create(local, 0, MPI_COMM_WORLD, remote_leader, 99);
+    assert!(mpi_comm::mpi_comm_test_inter(inter));
+
+    let me = comm_rank::mpi_comm_rank(inter);
+    let remote_size = num_procs::mpi_comm_remote_size(inter);
+    let remote_world: Vec<usize> = (0..size).filter(|r| (r % 2 == 0) != fluid).collect();
+    assert_eq!(group::mpi_group_size(&group::mpi_comm_remote_group(inter)), remote_size);
+    assert_eq!(remote_size, remote_world.len());
+
+    // Ranks address processes of the other group
+    if me < remote_size && me < num_procs::mpi_get_num_procs(inter) {
+        let mut reply = 0usize;
+        sendrecv::mpi_sendrecv(&rank,
+                               RequestProc::Process(me),
+                               0,
+                               &mut reply,
+                               RequestProc::Process(me),
+                               0,
+                               inter);
+        assert_eq!(reply, remote_world[me]);
+    }
+
+    // Collectives deliver the contributions of the other group
+    let mut sum = Vec::new();
+    reduce::mpi_allreduce(&[rank], &mut sum, &Op::sum(), inter);
+    assert_eq!(sum[0], remote_world.iter().sum());
+
+    let mut ranks = Vec::new();
+    allgather::mpi_allgather(&[rank], &mut ranks, inter);
+    assert_eq!(ranks, remote_world);
+
+    // Rank i of a group contributes i + 1 copies of its world rank
+    let counts: Vec<usize> = (1..remote_size + 1).collect();
+    let displs: Vec<usize> = (0..remote_size).map(|i| i * (i + 1) / 2).collect();
+    let mut blocks = vec![0; counts.iter().sum()];
+    allgather::mpi_allgatherv(&vec![rank; me + 1], &mut blocks, &counts, &displs, inter);
+    let expected: Vec<usize> = remote_world.iter()
+                                           .enumerate()
+                                           .flat_map(|(i, &r)| vec![r; i + 1])
+                                           .collect();
+    assert_eq!(blocks, expected);
+
+    // The fluid leader is the root, its data flows to or from the structure
+    let root = match (fluid, me) {
+        (true, 0) => MPI_ROOT,
+        (true, _) => MPI_PROC_NULL,
+        (false, _) => 0,
+    };
+    let mut value = if root == MPI_ROOT { 42 } else { 0 };
+    bcast::mpi_bcast(&mut value, root, inter);
+    assert_eq!(value, if root == MPI_PROC_NULL { 0 } else { 42 });
+    assert_eq!(bcast::mpi_ibcast(value, root, inter).wait(), value);
+
+    let remote_sum: usize = remote_world.iter().sum();
+    let mut total = Vec::new();
+    reduce::mpi_reduce(&[rank], &mut total, &Op::sum(), root, inter);
+    assert_eq!(total, if root == MPI_ROOT { vec![remote_sum] } else { Vec::new() });
+
+    let mut gathered = Vec::new();
+    gather::mpi_gather(&[rank, rank], &mut gathered, root, inter);
+    let mut placed = vec![0; expected.len()];
+    gather::mpi_gatherv(&vec![rank; me + 1], &mut placed, &counts, &displs, root, inter);
+    if root == MPI_ROOT {
+        let pairs: Vec<usize> = remote_world.iter().flat_map(|&r| vec![r, r]).collect();
+        assert_eq!(gathered, pairs);
+        assert_eq!(placed, expected);
+    }
+
+    let tens: Vec<usize> = remote_world.iter().map(|r| 10 * r).collect();
+    let mut ten = Vec::new();
+    scatter::mpi_scatter(&tens, &mut ten, root, inter);
+    let mut part = Vec::new();
+    scatter::mpi_scatterv(&expected, &counts, &displs, &mut part, root, inter);
+    if !fluid {
+        assert_eq!(ten, vec![10 * rank]);
+        assert_eq!(part, vec![rank; me + 1]);
+    }
+
+    // Every process exchanges a block with every process of the other group
+    let outgoing: Vec<usize> = (0..remote_size).map(|j| 100 * rank + j).collect();
+    let mut incoming = Vec::new();
+    alltoall::mpi_alltoall(&outgoing, &mut incoming, inter);
+    assert_eq!(incoming, remote_world.iter().map(|r| 100 * r + me).collect::<Vec<_>>());
+
+    let recvcounts = vec![me + 1; remote_size];
+    let rdispls: Vec<usize> = (0..remote_size).map(|i| i * (me + 1)).collect();
+    let mut copies = vec![0; remote_size * (me + 1)];
+    alltoall::mpi_alltoallv(&vec![rank; expected.len()],
+                            &counts,
+                            &displs,
+                            &mut copies,
+                            &recvcounts,
+                            &rdispls,
+                            inter);
+    assert_eq!(copies, remote_world.iter().flat_map(|&r| vec![r; me + 1]).collect::<Vec<_>>());
+
+    let notes: Vec<_> = (0..remote_size).map(|j| alltoall::to_block(&(rank, j))).collect();
+    let mut received = Vec::new();
+    alltoall::mpi_alltoallw(&notes, &mut received, inter);
+    let received: Vec<(usize, usize)> = received.into_iter().map(alltoall::from_block).collect();
+    assert_eq!(received, remote_world.iter().map(|&r| (r, me)).collect::<Vec<_>>());
+
+    // Element j of the reduction over the other group goes to rank j
+    let contribution: Vec<usize> = (0..remote_size).map(|j| rank + j).collect();
+    let mut element = Vec::new();
+    reduce_scatter::mpi_reduce_scatter_block(&contribution, &mut element, 1, &Op::sum(), inter);
+    assert_eq!(element, vec![remote_sum + remote_size * me]);
+    barrier::mpi_barrier(inter);
+    println!("Process {} ({}) sees {} remote processes",
+             rank,
+             if fluid { "fluid" } else { "structure" },
+             remote_size);
+
+    // A duplicate connects the same groups in a context of its own
+    let dup = mpi_comm::mpi_comm_dup(inter);
+    assert!(mpi_comm::mpi_comm_test_inter(dup));
+    assert_eq!(mpi_comm::mpi_comm_compare(dup, inter), mpi_comm::CommCompare::Congruent);
+    let mut sum = Vec::new();
+    reduce::mpi_allreduce(&[rank], &mut sum, &Op::sum(), dup);
+    assert_eq!(sum[0], remote_sum);
+    mpi_comm::mpi_comm_free(dup);
+
+    // Merge with the fluid first
+    let merged = mpi_comm::mpi_intercomm_merge(inter, !fluid);
+    assert!(!mpi_comm::mpi_comm_test_inter(merged));
+    let fluids = (size + 1) / 2;
+    let expected = if fluid { rank / 2 } else { fluids + rank / 2 };
+    assert_eq!(comm_rank::mpi_comm_rank(merged), expected);
+    assert_eq!(num_procs::mpi_get_num_procs(merged), size);
+
+    let mut total = Vec::new();
+    reduce::mpi_allreduce(&[rank], &mut total, &Op::sum(), merged);
+    assert_eq!(total[0], size * (size - 1) / 2);
+
+    for comm in vec![merged, inter, local] {
+        mpi_comm::mpi_comm_free(comm);
+    }
+    finalize::mpi_finalize();
+}
@@ -0,0 +1,86 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, neighbor};
+use mpirs::topology;
+use mpirs::mpi_comm::{self, MPI_COMM_WORLD};
+use mpirs::comm_request::RequestProc;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    // Halo exchange on a grid that wraps around in its first dimension only
+    let mut dims = vec![0, 0];
+    topology::mpi_dims_create(size, &mut dims);
+    let grid = topology::mpi_cart_create(MPI_COMM_WORLD, &dims, &[true, false]).unwrap();
+    let me = comm_rank::mpi_comm_rank(grid);
+    let mut shifts = Vec::new();
+    for direction in 0..2 {
+        let (below, above) = topology::mpi_cart_shift(grid, direction, 1);
+        for neighbor in vec![below, above] {
+            shifts.push(match neighbor {
+                RequestProc::Process(r) => Some(r),
+                _ => None,
+            });
+        }
+    }
+
+    // Block `i` goes to neighbour `i`. The neighbour below sends the block it sends up.
+    let missing = (usize::max_value(), 0);
+    let sendbuf: Vec<(usize, usize)> = (0..4).map(|i| (me, i)).collect();
+    let mut recvbuf = vec![missing; 4];
+    neighbor::mpi_neighbor_alltoall(&sendbuf, &mut recvbuf, grid);
+    for i in 0..4 {
+        let expected = shifts[i].map(|n| (n, i ^ 1)).unwrap_or(missing);
+        assert_eq!(recvbuf[i], expected);
+    }
+
+    let mut ranks = vec![usize::max_value(); 4];
+    neighbor::mpi_neighbor_allgather(&[me], &mut ranks, grid);
+    for i in 0..4 {
+        assert_eq!(ranks[i], shifts[i].unwrap_or(usize::max_value()));
+    }
+    println!("Process {} has grid neighbours {:?}", rank, shifts);
+
+    // Every process sends to the next two on a ring, `r + 1` elements from process `r`
+    let hops: Vec<usize> = (1..3).filter(|&d| d < size).collect();
+    let sources: Vec<usize> = hops.iter().map(|d| (rank + size - d) % size).collect();
+    let destinations: Vec<usize> = hops.iter().map(|d| (rank + d) % size).collect();
+    let ring = topology::mpi_dist_graph_create_adjacent(MPI_COMM_WORLD, &sources, &destinations);
+
+    let recvcounts: Vec<usize> = sources.iter().map(|s| s + 1).collect();
+    let displs: Vec<usize> = (0..sources.len()).map(|i| recvcounts[..i].iter().sum()).collect();
+    let total = recvcounts.iter().sum();
+    let gathered = neighbor::mpi_ineighbor_allgatherv(vec![rank; rank + 1],
+                                                     vec![0; total],
+                                                     recvcounts.clone(),
+                                                     displs.clone(),
+                                                     ring);
+
+    // Send `rank + i` to the `i`th destination alongside
+    let sendbuf: Vec<usize> = (0..destinations.len()).map(|i| rank + i).collect();
+    let ones = vec![1; destinations.len()];
+    let offsets: Vec<usize> = (0..destinations.len()).collect();
+    let exchanged = neighbor::mpi_ineighbor_alltoallv(sendbuf,
+                                                     ones.clone(),
+                                                     offsets.clone(),
+                                                     vec![0; sources.len()],
+                                                     ones,
+                                                     offsets,
+                                                     ring);
+
+    let gathered = gathered.wait();
+    for (i, &s) in sources.iter().enumerate() {
+        assert_eq!(&gathered[displs[i]..displs[i] + recvcounts[i]], &vec![s; s + 1][..]);
+    }
+    let exchanged = exchanged.wait();
+    for (i, &s) in sources.iter().enumerate() {
+        assert_eq!(exchanged[i], s + i);
+    }
+    println!("Process {} gathered {:?}", rank, gathered);
+
+    mpi_comm::mpi_comm_free(ring);
+    mpi_comm::mpi_comm_free(grid);
+    finalize::mpi_finalize();
+}
@@ -0,0 +1,34 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, bcast, reduce, barrier};
+use mpirs::op::Op;
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    // Two collectives in flight at once, overlapped with local work
+    let mut sum = reduce::mpi_iallreduce(vec![rank], &Op::sum(), MPI_COMM_WORLD);
+    let token = bcast::mpi_ibcast(rank * 100, size - 1, MPI_COMM_WORLD);
+
+    let mut local = 0u64;
+    while !sum.test() {
+        local += 1;
+    }
+
+    // A blocking collective started after the non-blocking ones completes independently
+    let mut max = Vec::new();
+    reduce::mpi_allreduce(&[rank], &mut max, &Op::max(), MPI_COMM_WORLD);
+
+    println!("Process {} got sum {}, token {}, max {} after {} local steps",
+             rank,
+             sum.wait()[0],
+             token.wait(),
+             max[0],
+             local);
+
+    barrier::mpi_ibarrier(MPI_COMM_WORLD).wait();
+    finalize::mpi_finalize();
+}
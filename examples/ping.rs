@@ -7,7 +7,7 @@ use mpirs::mpi_comm::MPI_COMM_WORLD;
 const TAG: u64 = 42;
 
 fn main() {
-    let rank = comm_rank::mpi_comm_rank();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
     if rank == 0 {
         let mut message = format!("ping!");
         send::mpi_send(&message,
@@ -1,9 +1,10 @@
 extern crate mpirs;
 
 use mpirs::{comm_rank, finalize};
+use mpirs::mpi_comm::MPI_COMM_WORLD;
 
 fn main() {
-    let rank = comm_rank::mpi_comm_rank();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
     println!("rank: {}", rank);
     finalize::mpi_finalize();
 }
@@ -0,0 +1,33 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, reduce};
+use mpirs::op::Op;
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    let mut sum = Vec::new();
+    reduce::mpi_reduce(&[rank as u64, 1], &mut sum, &Op::sum(), 0, MPI_COMM_WORLD);
+    if rank == 0 {
+        println!("Sum of ranks: {}, number of processes: {}", sum[0], sum[1]);
+    }
+
+    // Non-commutative: concatenation must come out in rank order on every root
+    let root = size - 1;
+    let concat = Op::create(|a: &String, b: &String| format!("{}{}", a, b), false);
+    let mut order = Vec::new();
+    reduce::mpi_reduce(&[format!("{}", rank)], &mut order, &concat, root, MPI_COMM_WORLD);
+    if rank == root {
+        println!("Process {} has rank order {}", rank, order[0]);
+    }
+
+    let mut loc = Vec::new();
+    let value = ((rank * 7) % size) as i64;
+    reduce::mpi_allreduce(&[(value, rank)], &mut loc, &Op::maxloc(), MPI_COMM_WORLD);
+    println!("Process {} sees max {} at rank {}", rank, loc[0].0, loc[0].1);
+
+    finalize::mpi_finalize();
+}
@@ -0,0 +1,57 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize};
+use mpirs::{group, info, window};
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+use mpirs::op::Op;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+    let hints = info::mpi_info_create();
+
+    // A distributed array with one slot per process on every process
+    let win = window::mpi_win_allocate::<usize>(size, &hints, MPI_COMM_WORLD);
+    window::mpi_win_fence(win);
+    for target in 0..size {
+        window::mpi_put(&[rank * 10 + target], target, rank, win);
+    }
+    window::mpi_win_fence(win);
+
+    let mut mine = vec![0; size];
+    window::mpi_get(&mut mine, rank, 0, win);
+    let expected: Vec<usize> = (0..size).map(|r| r * 10 + rank).collect();
+    assert_eq!(mine, expected);
+
+    // Everyone adds to the first element of process 0, concurrently
+    window::mpi_win_fence(win);
+    window::mpi_accumulate(&[rank + 1], 0, 0, &Op::sum(), win);
+    window::mpi_win_fence(win);
+    let mut total = [0];
+    window::mpi_get_accumulate(&[0], &mut total, 0, 0, &Op::no_op(), win);
+    assert_eq!(total[0], (1..size + 1).sum());
+
+    // Odd processes write to the even process below them
+    window::mpi_win_fence(win);
+    let world = window::mpi_win_get_group(win);
+    if rank % 2 == 1 {
+        let target = group::mpi_group_incl(&world, &[rank - 1]);
+        window::mpi_win_start(&target, win);
+        let mut old = [0];
+        window::mpi_get_accumulate(&[1000], &mut old, rank - 1, 1, &Op::replace(), win);
+        assert_eq!(old[0], 10 + rank - 1);
+        window::mpi_win_complete(win);
+    } else if rank + 1 < size {
+        let origin = group::mpi_group_incl(&world, &[rank + 1]);
+        window::mpi_win_post(&origin, win);
+        window::mpi_win_wait(win);
+        let mut slot = [0];
+        window::mpi_get(&mut slot, rank, 1, win);
+        assert_eq!(slot[0], 1000);
+    }
+
+    let exposed = window::mpi_win_free(win);
+    println!("Process {} exposed {:?}", rank, exposed);
+    finalize::mpi_finalize();
+}
@@ -0,0 +1,25 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, init, finalize, scan};
+use mpirs::op::Op;
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+
+    // Every process writes rank + 1 items; find where its output starts
+    let count = rank + 1;
+    let mut offset = vec![0];
+    scan::mpi_exscan(&[count], &mut offset, &Op::sum(), MPI_COMM_WORLD);
+
+    let mut upto = Vec::new();
+    scan::mpi_scan(&[count], &mut upto, &Op::sum(), MPI_COMM_WORLD);
+
+    let concat = Op::create(|a: &String, b: &String| format!("{}{}", a, b), false);
+    let mut order = Vec::new();
+    scan::mpi_scan(&[format!("{}", rank)], &mut order, &concat, MPI_COMM_WORLD);
+
+    println!("Process {} writes [{}, {}) after {}", rank, offset[0], upto[0], order[0]);
+    finalize::mpi_finalize();
+}
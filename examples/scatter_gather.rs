@@ -0,0 +1,49 @@
+extern crate mpirs;
+
+use mpirs::{comm_rank, num_procs, init, finalize, scatter, gather};
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+
+fn main() {
+    init::mpi_init();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+    let root = size / 2;
+
+    // Two elements per process
+    let data: Vec<usize> = (0..2 * size).collect();
+    let mut mine = Vec::new();
+    scatter::mpi_scatter(&data, &mut mine, root, MPI_COMM_WORLD);
+    assert_eq!(mine, vec![2 * rank, 2 * rank + 1]);
+
+    // Process i gets i elements, taken from the end of the buffer backwards
+    let counts: Vec<usize> = (0..size).collect();
+    let total: usize = counts.iter().sum();
+    let mut displs = vec![0; size];
+    for i in (0..size - 1).rev() {
+        displs[i] = displs[i + 1] + counts[i + 1];
+    }
+    let letters: Vec<char> = (0..total).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+    let mut part = Vec::new();
+    scatter::mpi_scatterv(&letters, &counts, &displs, &mut part, root, MPI_COMM_WORLD);
+    assert_eq!(part.len(), rank);
+
+    // Put everything back where it came from
+    let mut all = Vec::new();
+    gather::mpi_gather(&mine, &mut all, root, MPI_COMM_WORLD);
+    let mut back = vec![' '; total];
+    gather::mpi_gatherv(&part, &mut back, &counts, &displs, root, MPI_COMM_WORLD);
+
+    if rank == root {
+        assert_eq!(all, data);
+        assert_eq!(back, letters);
+        println!("Process {} gathered {:?} and {:?}", rank, all, back);
+    }
+
+    let block = scatter::mpi_iscatter(data.clone(), root, MPI_COMM_WORLD).wait();
+    let all = gather::mpi_igather(block, root, MPI_COMM_WORLD).wait();
+    if rank == root {
+        assert_eq!(all, data);
+    }
+
+    finalize::mpi_finalize();
+}
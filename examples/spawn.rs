@@ -0,0 +1,84 @@
+extern crate mpirs;
+
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+
+use mpirs::{comm_rank, num_procs, init, finalize, reduce, barrier, send, receive};
+use mpirs::{info, spawn};
+use mpirs::mpi_comm::{self, MPIComm, MPI_COMM_WORLD};
+use mpirs::comm_request::RequestProc;
+use mpirs::op::Op;
+
+const WORKERS: usize = 3;
+
+fn main() {
+    init::mpi_init();
+    match spawn::mpi_comm_get_parent() {
+        None => manager(),
+        Some(parent) => worker(parent),
+    }
+    finalize::mpi_finalize();
+}
+
+/// Start helper workers partway through the run and hand each of them a task
+fn manager() {
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
+
+    // A failed spawn fails on every manager, not just on the root
+    let failed = panic::catch_unwind(AssertUnwindSafe(|| {
+        spawn::mpi_comm_spawn("/nonexistent", &[], 1, &info::mpi_info_create(), 0, MPI_COMM_WORLD)
+    }));
+    assert!(failed.is_err());
+
+    let exe = env::current_exe().unwrap();
+    let mut hints = info::mpi_info_create();
+    info::mpi_info_set(&mut hints, "wdir", "/");
+    let workers = spawn::mpi_comm_spawn(exe.to_str().unwrap(),
+                                        &["--scale", "10"],
+                                        WORKERS,
+                                        &hints,
+                                        0,
+                                        MPI_COMM_WORLD);
+    assert!(mpi_comm::mpi_comm_test_inter(workers));
+    assert_eq!(num_procs::mpi_comm_remote_size(workers), WORKERS);
+
+    if rank == 0 {
+        for w in 0..WORKERS {
+            send::mpi_send(&(w + 1), RequestProc::Process(w), 0, workers);
+        }
+    }
+
+    // The workers contribute their results, the managers their ranks
+    let mut total = Vec::new();
+    reduce::mpi_allreduce(&[rank], &mut total, &Op::sum(), workers);
+    let tasks: usize = (1..WORKERS + 1).sum();
+    assert_eq!(total[0], tasks * 10);
+    barrier::mpi_barrier(workers);
+    println!("Manager {} of {} got {} from its workers", rank, size, total[0]);
+    mpi_comm::mpi_comm_free(workers);
+}
+
+fn worker(parent: MPIComm) {
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    assert_eq!(num_procs::mpi_get_num_procs(MPI_COMM_WORLD), WORKERS);
+    assert_eq!(env::current_dir().unwrap(), std::path::Path::new("/"));
+    let args: Vec<String> = env::args().collect();
+    assert_eq!(&args[1..], &["--scale", "10"]);
+    let scale: usize = args[2].parse().unwrap();
+
+    let mut task = 0usize;
+    receive::mpi_recv(&mut task, RequestProc::Process(0), 0, parent);
+
+    let managers = num_procs::mpi_comm_remote_size(parent);
+    let mut total = Vec::new();
+    reduce::mpi_allreduce(&[task * scale], &mut total, &Op::sum(), parent);
+    assert_eq!(total[0], (0..managers).sum());
+
+    // The workers' own world is separate from that of the managers
+    let mut sum = Vec::new();
+    reduce::mpi_allreduce(&[rank], &mut sum, &Op::sum(), MPI_COMM_WORLD);
+    assert_eq!(sum[0], (0..WORKERS).sum());
+    barrier::mpi_barrier(parent);
+    println!("Worker {} computed {}", rank, task * scale);
+}
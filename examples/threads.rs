@@ -0,0 +1,52 @@
+extern crate mpirs;
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use mpirs::{comm_rank, send, receive, init, finalize};
+use mpirs::init::ThreadLevel;
+use mpirs::comm_request::RequestProc;
+use mpirs::mpi_comm::MPI_COMM_WORLD;
+
+const NUM_THREADS: u64 = 4;
+
+fn main() {
+    let provided = init::mpi_init_thread(ThreadLevel::Multiple);
+    assert_eq!(provided, ThreadLevel::Serialized);
+
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    // Calls are serialized: the threads take turns, in order, by the number of the next one
+    let turn = Arc::new((Mutex::new(0), Condvar::new()));
+    let mut handles = Vec::new();
+
+    for t in 0..NUM_THREADS {
+        let turn = turn.clone();
+        handles.push(thread::spawn(move || {
+            let &(ref next, ref cvar) = &*turn;
+            let mut next = next.lock().unwrap();
+            while *next != t {
+                next = cvar.wait(next).unwrap();
+            }
+
+            if rank == 0 {
+                let message = format!("hello from thread {}", t);
+                send::mpi_send(&message, RequestProc::Process(1), t, MPI_COMM_WORLD);
+            } else if rank == 1 {
+                let mut message = String::new();
+                receive::mpi_recv(&mut message, RequestProc::Process(0), t, MPI_COMM_WORLD);
+                assert_eq!(message, format!("hello from thread {}", t));
+                println!("Thread {} got \"{}\"", t, message);
+            }
+
+            *next += 1;
+            cvar.notify_all();
+        }));
+    }
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert!(init::mpi_is_thread_main());
+    finalize::mpi_finalize();
+}
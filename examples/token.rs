@@ -25,8 +25,8 @@ fn main() {
     unsafe {
         libc::usleep(32000);
     }
-    let rank = comm_rank::mpi_comm_rank();
-    let size = num_procs::mpi_get_num_procs();
+    let rank = comm_rank::mpi_comm_rank(MPI_COMM_WORLD);
+    let size = num_procs::mpi_get_num_procs(MPI_COMM_WORLD);
 
     if rank == 0 {
         let mut token = Token { val: 65 };
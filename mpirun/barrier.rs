@@ -0,0 +1,113 @@
+//! Barriers waiting in mpirun
+//!
+//! A barrier is identified by the context of its communicator and its epoch. The first process
+//! to enter it fixes its members, and it is released once every member has entered.
+
+use std::collections::HashMap;
+
+#[derive(Debug)]
+struct Waiting<S> {
+    members: Vec<usize>,
+    entered: Vec<(usize, S)>,
+}
+
+#[derive(Debug)]
+pub struct Barriers<S> {
+    waiting: HashMap<(u64, u64), Waiting<S>>,
+}
+
+impl<S> Barriers<S> {
+    pub fn new() -> Barriers<S> {
+        Barriers { waiting: HashMap::new() }
+    }
+
+    /// Process `rank`, waiting on `stream`, entered a barrier of communicator `comm`. Returns the
+    /// streams of all members once the last of them entered.
+    pub fn enter(&mut self,
+                 context: u64,
+                 epoch: u64,
+                 members: Vec<usize>,
+                 rank: usize,
+                 stream: S,
+                 comm: &str)
+                 -> Option<Vec<S>> {
+        let key = (context, epoch);
+        let released = {
+            let waiting = self.waiting.entry(key).or_insert_with(|| {
+                Waiting {
+                    members: members.clone(),
+                    entered: Vec::new(),
+                }
+            });
+
+            if waiting.members != members {
+                panic!("Process {} entered barrier {} on {} with different members",
+                       rank,
+                       epoch,
+                       comm);
+            }
+            if !waiting.members.contains(&rank) {
+                panic!("Process {} is not a member of barrier {} on {}", rank, epoch, comm);
+            }
+            if waiting.entered.iter().any(|&(r, _)| r == rank) {
+                panic!("Process {} entered barrier {} on {} twice", rank, epoch, comm);
+            }
+
+            waiting.entered.push((rank, stream));
+            waiting.entered.len() == waiting.members.len()
+        };
+
+        if released {
+            let waiting = self.waiting.remove(&key).unwrap();
+            Some(waiting.entered.into_iter().map(|(_, s)| s).collect())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn barrier_release_all() {
+        let mut barriers = Barriers::new();
+        assert!(barriers.enter(1, 0, vec![0, 1, 2], 2, 'c', "comm").is_none());
+        assert!(barriers.enter(1, 0, vec![0, 1, 2], 0, 'a', "comm").is_none());
+        assert_eq!(barriers.enter(1, 0, vec![0, 1, 2], 1, 'b', "comm"), Some(vec!['c', 'a', 'b']));
+    }
+
+    #[test]
+    fn barrier_subset() {
+        let mut barriers = Barriers::new();
+        assert!(barriers.enter(3, 0, vec![1, 3], 3, 'd', "comm").is_none());
+        assert_eq!(barriers.enter(3, 0, vec![1, 3], 1, 'b', "comm"), Some(vec!['d', 'b']));
+    }
+
+    #[test]
+    fn barrier_epochs_apart() {
+        // A process entering the next barrier early must not release the current one
+        let mut barriers = Barriers::new();
+        assert!(barriers.enter(1, 0, vec![0, 1], 0, 'a', "comm").is_none());
+        assert!(barriers.enter(1, 1, vec![0, 1], 0, 'a', "comm").is_none());
+        assert!(barriers.enter(3, 0, vec![0, 1], 1, 'b', "comm").is_none());
+        assert_eq!(barriers.enter(1, 0, vec![0, 1], 1, 'b', "comm"), Some(vec!['a', 'b']));
+        assert_eq!(barriers.enter(1, 1, vec![0, 1], 1, 'b', "comm"), Some(vec!['a', 'b']));
+    }
+
+    #[test]
+    #[should_panic]
+    fn barrier_not_member() {
+        let mut barriers = Barriers::new();
+        barriers.enter(1, 0, vec![0, 1], 2, 'c', "comm");
+    }
+
+    #[test]
+    #[should_panic]
+    fn barrier_twice() {
+        let mut barriers = Barriers::new();
+        barriers.enter(1, 0, vec![0, 1], 0, 'a', "comm");
+        barriers.enter(1, 0, vec![0, 1], 0, 'a', "comm");
+    }
+}
@@ -43,6 +43,7 @@ struct MailboxKey {
     key_type: MessageTy,
     actor: RequestProc,
     tag: u64,
+    context: u64,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -52,11 +53,12 @@ enum MessageTy {
 }
 
 impl MailboxKey {
-    fn new(kt: MessageTy, p: RequestProc, t: u64) -> MailboxKey {
+    fn new(kt: MessageTy, p: RequestProc, t: u64, c: u64) -> MailboxKey {
         MailboxKey {
             key_type: kt,
             actor: p,
             tag: t,
+            context: c,
         }
     }
 }
@@ -165,8 +167,8 @@ impl Mailbox {
         self.stream_map.insert(self.id, cloned_stream);
         self.id += 1;
 
-        let h1_key = MailboxKey::new(mtype, req.src().unwrap(), req.tag());
-        let h2_key = MailboxKey::new(mtype, req.dst().unwrap(), req.tag());
+        let h1_key = MailboxKey::new(mtype, req.src().unwrap(), req.tag(), req.context());
+        let h2_key = MailboxKey::new(mtype, req.dst().unwrap(), req.tag(), req.context());
 
         if !self.h1.contains_key(&h1_key) {
             self.h1.insert(h1_key, VecDeque::new());
@@ -191,23 +193,33 @@ impl Mailbox {
         if req.is_send() {
             let mut keys = vec![KT::H1(MailboxKey::new(MessageTy::MRecv,
                                                        req.src().unwrap(),
-                                                       req.tag())),
+                                                       req.tag(),
+                                                       req.context())),
                                 KT::H1(MailboxKey::new(MessageTy::MRecv,
                                                        RequestProc::Any,
-                                                       req.tag()))];
+                                                       req.tag(),
+                                                       req.context()))];
             if !req.is_dst_any() {
-                keys.push(KT::H2(MailboxKey::new(MessageTy::MRecv, req.dst().unwrap(), req.tag())));
+                keys.push(KT::H2(MailboxKey::new(MessageTy::MRecv,
+                                                 req.dst().unwrap(),
+                                                 req.tag(),
+                                                 req.context())));
             }
             keys
         } else if req.is_recv() {
             let mut keys = vec![KT::H2(MailboxKey::new(MessageTy::MSend,
                                                        req.dst().unwrap(),
-                                                       req.tag())),
+                                                       req.tag(),
+                                                       req.context())),
                                 KT::H2(MailboxKey::new(MessageTy::MSend,
                                                        RequestProc::Any,
-                                                       req.tag()))];
+                                                       req.tag(),
+                                                       req.context()))];
             if !req.is_src_any() {
-                keys.push(KT::H1(MailboxKey::new(MessageTy::MSend, req.src().unwrap(), req.tag())));
+                keys.push(KT::H1(MailboxKey::new(MessageTy::MSend,
+                                                 req.src().unwrap(),
+                                                 req.tag(),
+                                                 req.context())));
             }
             keys
         } else {
@@ -226,39 +238,26 @@ impl Mailbox {
 
         let mut found: Option<Mail> = None;
 
-        loop {
-            if k >= v3.len() {
-                break;
-            }
-            if i >= v1.len() && j >= v2.len() {
-                break;
+        // All lists are sorted by id: find the oldest mail of v3 that is also in v1 or v2
+        while k < v3.len() {
+            while i < v1.len() && v1[i] < v3[k] {
+                i += 1;
             }
-
-            if i < v1.len() {
-                if v1[i] < v3[k] {
-                    i += 1;
-                } else if v1[i] == v3[k] {
-                    found = get_mut_value!(self, keys[0]).unwrap().remove(i);
-                    get_mut_value!(self, keys[2]).unwrap().remove(k);
-                    break;
-                }
+            while j < v2.len() && v2[j] < v3[k] {
+                j += 1;
             }
 
-            if j < v2.len() {
-                if v2[j] < v3[k] {
-                    j += 1;
-                } else if v2[j] == v3[k] {
-                    found = get_mut_value!(self, keys[1]).unwrap().remove(j);
-                    get_mut_value!(self, keys[2]).unwrap().remove(k);
-                    break;
-                }
+            if i < v1.len() && v1[i] == v3[k] {
+                found = get_mut_value!(self, keys[0]).unwrap().remove(i);
+                get_mut_value!(self, keys[2]).unwrap().remove(k);
+                break;
             }
-
-            if i < v1.len() && j < v2.len() {
-                if v1[i] > v3[k] && v2[j] > v3[k] {
-                    k += 1;
-                }
+            if j < v2.len() && v2[j] == v3[k] {
+                found = get_mut_value!(self, keys[1]).unwrap().remove(j);
+                get_mut_value!(self, keys[2]).unwrap().remove(k);
+                break;
             }
+            k += 1;
         }
         found
     }
@@ -288,9 +287,17 @@ impl Mailbox {
 mod test {
     use super::*;
     use mpirs::comm_request::{CommRequest, CommRequestType, MType, RequestProc};
+    use std::net::{TcpListener, TcpStream};
 
     const COMM_TAG: u64 = 42;
 
+    fn get_tcp_stream() -> TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let _ = listener.accept();
+        stream
+    }
+
     #[test]
     fn box_insert_get_proc_to_proc() {
         let mut mailbox = Mailbox::new();
@@ -396,6 +403,33 @@ mod test {
         assert!(mailbox.pop_matching_mail(&req_recv).is_none());
     }
 
+    #[test]
+    fn box_recv_skips_other_pairs() {
+        let mut mailbox = Mailbox::new();
+        let send = |src, dst| {
+            CommRequest::<u64>::new(Some(RequestProc::Process(src)),
+                                    Some(RequestProc::Process(dst)),
+                                    COMM_TAG,
+                                    Some(5u64),
+                                    CommRequestType::Message(MType::MSend),
+                                    1000u32)
+        };
+        let req_recv = CommRequest::<u64>::new(Some(RequestProc::Process(0)),
+                                               Some(RequestProc::Process(1)),
+                                               COMM_TAG,
+                                               None,
+                                               CommRequestType::Message(MType::MRecv),
+                                               1000u32);
+
+        // Older mail from the source and newer mail to the destination, neither between them
+        mailbox.insert_mail(&send(0, 2), &get_tcp_stream());
+        mailbox.insert_mail(&send(3, 1), &get_tcp_stream());
+        assert!(mailbox.pop_matching_mail(&req_recv).is_none());
+
+        mailbox.insert_mail(&send(0, 1), &get_tcp_stream());
+        assert!(mailbox.pop_matching_mail(&req_recv).is_some());
+    }
+
     #[test]
     fn box_inorder() {
         let mut mailbox = Mailbox::new();
@@ -490,4 +524,69 @@ mod test {
         assert_eq!(rep.unwrap().0.id, 0);
         assert!(mailbox.pop_matching_mail(&req).is_none());
     }
+
+    #[test]
+    fn box_same_pid_distinct_tags() {
+        // Two threads of the same process each post a receive. The send must complete the one
+        // whose envelope it matches, not the one that was posted first.
+        let mut mailbox = Mailbox::new();
+        let req_recv = CommRequest::<u64>::new(Some(RequestProc::Process(0)),
+                                               Some(RequestProc::Process(1)),
+                                               COMM_TAG,
+                                               None,
+                                               CommRequestType::Message(MType::MRecv),
+                                               1000u32);
+
+        let req_recv_1 = CommRequest::<u64>::new(Some(RequestProc::Process(0)),
+                                                 Some(RequestProc::Process(1)),
+                                                 COMM_TAG + 1,
+                                                 None,
+                                                 CommRequestType::Message(MType::MRecv),
+                                                 1000u32);
+
+        let req = CommRequest::<u64>::new(Some(RequestProc::Process(0)),
+                                          Some(RequestProc::Process(1)),
+                                          COMM_TAG + 1,
+                                          Some(5u64),
+                                          CommRequestType::Message(MType::MSend),
+                                          2000u32);
+
+        mailbox.insert_mail(&req_recv, &get_tcp_stream());
+        mailbox.insert_mail(&req_recv_1, &get_tcp_stream());
+
+        let rep = mailbox.pop_matching_mail(&req);
+        assert!(rep.is_some());
+        assert_eq!(rep.unwrap().0.id, 1);
+        assert!(mailbox.pop_matching_mail(&req).is_none());
+    }
+
+    #[test]
+    fn box_distinct_contexts() {
+        // Collective traffic must not match a user receive with the same envelope
+        let mut mailbox = Mailbox::new();
+        let req_recv = CommRequest::<u64>::new(Some(RequestProc::Process(0)),
+                                               Some(RequestProc::Process(1)),
+                                               COMM_TAG,
+                                               None,
+                                               CommRequestType::Message(MType::MRecv),
+                                               1000u32);
+
+        let mut req = CommRequest::<u64>::new(Some(RequestProc::Process(0)),
+                                              Some(RequestProc::Process(1)),
+                                              COMM_TAG,
+                                              Some(5u64),
+                                              CommRequestType::Message(MType::MSend),
+                                              2000u32);
+        req.set_context(1);
+
+        mailbox.insert_mail(&req_recv, &get_tcp_stream());
+        assert!(mailbox.pop_matching_mail(&req).is_none());
+
+        let mut req_recv_1 = req_recv.clone();
+        req_recv_1.set_context(1);
+        mailbox.insert_mail(&req_recv_1, &get_tcp_stream());
+        let rep = mailbox.pop_matching_mail(&req);
+        assert!(rep.is_some());
+        assert_eq!(rep.unwrap().0.id, 1);
+    }
 }
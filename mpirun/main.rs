@@ -1,7 +1,14 @@
 //! Implements mpirun equivalent
 //!
 //! ## Usage
-//! `mpirun -n <num_of_procs> /path/to/executable`
+//! `mpirun -n <num_of_procs> /path/to/executable [<args>...]`
+//!
+//! With `--trace`, every message mpirun matches is printed to stderr together with the name of
+//! its communicator.
+//!
+//! mpirun listens on the port given by `--port`, `MPIRS_PORT` or 31337, and passes it on to its
+//! processes in `MPIRS_PORT`. Jobs that connect to each other need mpiruns on different ports.
+//! `mpirun --name-server` starts no processes, it only serves the names jobs publish.
 //!
 //! ## Implementation details.
 //! mpirun spawns
@@ -13,29 +20,47 @@ extern crate rustc_serialize;
 extern crate mpirs;
 
 mod mailbox;
+mod barrier;
+mod names;
+mod procs;
+mod ports;
+mod window;
 
-use std::process::{Command};
-use std::io::Read;
+use std::process::{Child, Command};
+use std::io::{self, Read};
 use std::io::Write;
-use std::net::{TcpListener, TcpStream};
-use std::collections::HashMap;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::thread;
 
 use rustc_serialize::json;
 
 use docopt::Docopt;
 
-use mpirs::comm_request::{CommRequest, CommRequestType, ControlTy, RequestProc};
+use mpirs::comm_request::{CommRequest, CommRequestType, ControlTy, RequestProc, RmaReply,
+                          SpawnCommand};
+use mpirs::utils;
 use mailbox::Mailbox;
+use barrier::Barriers;
+use names::Names;
+use procs::Procs;
+use ports::Ports;
+use window::{Access, AccessKind, Windows};
 
 static USAGE: &'static str = "
 mpirs. Run MPI Programs in rust.
 Usage:
   mpirs [options] \
-                              [<executable>]
+                              [<executable>] [<args>...]
 
 Options:
   -n --num=<num_of_procs>   Define the \
                               number of processes to spawn
+  -t --trace                Print every \
+                              message matched.
+  -p --port=<port>          Port to listen \
+                              on.
+  --name-server             Start no \
+                              processes, only serve names.
   -h --help                 Show this \
                               help screen.
 ";
@@ -43,7 +68,11 @@ Options:
 #[derive(Debug, RustcDecodable)]
 struct Args {
     arg_executable: String,
+    arg_args: Vec<String>,
     flag_num: Option<usize>,
+    flag_trace: bool,
+    flag_port: Option<u16>,
+    flag_name_server: bool,
 }
 
 fn make_ack() -> CommRequest<String> {
@@ -55,42 +84,111 @@ fn make_ack() -> CommRequest<String> {
                      0)
 }
 
+/// Processes close their half of the connection once a request is written, so read till EOF.
 fn read_from_stream(stream: &mut TcpStream) -> String {
-    let mut bytes_read = [0; 2048];
     let mut str_in = String::new();
-    loop {
-        let n = stream.read(&mut bytes_read).expect("Read Error:");
-        str_in = format!("{}{}",
-                         str_in,
-                         String::from_utf8(bytes_read[0..n].to_vec()).unwrap());
-        if n < 2048 {
-            break;
+    stream.read_to_string(&mut str_in).expect("Read Error:");
+    str_in
+}
+
+/// Reply to a request on an RMA window
+fn reply_rma(stream: &mut TcpStream, result: Result<Vec<String>, String>) {
+    let reply = match result {
+        Ok(elems) => RmaReply::Done(elems),
+        Err(e) => RmaReply::Failed(e),
+    };
+    let _ = stream.write_all(json::encode(&reply).unwrap().as_bytes());
+}
+
+/// Pass `request` on to the mpirun at `addr` and its reply back on `stream`. The reply may take
+/// until the other job catches up, so the request is relayed on a thread of its own. If that
+/// mpirun cannot be reached or gives no reply, the reply is `null`, which the joining process and
+/// the sender of a message both take as failure.
+fn relay(addr: String, request: String, mut stream: TcpStream) {
+    thread::spawn(move || {
+        let reply = match forward(&addr, &request) {
+            Ok(ref reply) if !reply.is_empty() => reply.clone(),
+            Ok(_) => {
+                eprintln!("[mpirun] No reply from mpirun at {}", addr);
+                "null".to_string()
+            }
+            Err(e) => {
+                eprintln!("[mpirun] Cannot reach mpirun at {}: {}", addr, e);
+                "null".to_string()
+            }
+        };
+        let _ = stream.write_all(reply.as_bytes());
+    });
+}
+
+fn forward(addr: &str, request: &str) -> io::Result<String> {
+    let mut remote = TcpStream::connect(addr)?;
+    remote.write_all(request.as_bytes())?;
+    remote.shutdown(Shutdown::Write)?;
+    let mut reply = String::new();
+    remote.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+/// Start the processes of `commands`, telling them the `port` of mpirun, and return their ids.
+/// If one of them cannot be started, the ones already started are killed.
+fn spawn(commands: &[SpawnCommand], port: u16) -> Result<Vec<u32>, String> {
+    let mut children: Vec<Child> = Vec::new();
+    for c in commands {
+        for _ in 0..c.maxprocs {
+            let mut command = Command::new(&c.command);
+            command.args(&c.args).env("MPIRS_PORT", port.to_string());
+            if let Some(ref wdir) = c.wdir {
+                command.current_dir(wdir);
+            }
+            match command.spawn() {
+                Ok(child) => children.push(child),
+                Err(e) => {
+                    for child in &mut children {
+                        let _ = child.kill();
+                    }
+                    return Err(format!("Failed to spawn {}: {}", c.command, e));
+                }
+            }
         }
     }
-    str_in
+    Ok(children.iter().map(|c| c.id()).collect())
 }
 
 fn main() {
     let args: Args = Docopt::new(USAGE).and_then(|d| d.decode()).unwrap_or_else(|e| e.exit());
-    let num_procs = args.flag_num.unwrap_or(4);
+    let num_procs = if args.flag_name_server { 0 } else { args.flag_num.unwrap_or(4) };
+    let port = args.flag_port
+                   .or_else(|| utils::port_from_env("MPIRS_PORT"))
+                   .unwrap_or(utils::DEFAULT_PORT);
+    let addr = format!("127.0.0.1:{}", port);
 
     let bin = args.arg_executable.clone();
 
-    let mut rank_map = HashMap::new();
+    // Listen before spawning, so that processes can reach mpirun as soon as they start
+    let listener = TcpListener::bind(&*addr).unwrap();
+    let mut procs = Procs::new();
 
-    for i in 0..num_procs {
+    let mut pids = Vec::new();
+    for _ in 0..num_procs {
         let child = Command::new(&bin)
+                        .args(&args.arg_args)
+                        .env("MPIRS_PORT", port.to_string())
                         .spawn()
                         .expect("Failed to spawn process!");
 
-        rank_map.insert(child.id(), i);
+        pids.push(child.id());
     }
+    // MPI_COMM_WORLD has context 0
+    procs.add_world(&pids, 0, None);
 
-    let listener = TcpListener::bind("127.0.0.1:31337").unwrap();
     let mut mailbox = Mailbox::new();
-    let mut exit_count = 0;
 
-    let mut barrier_wait = Vec::new();
+    let mut barriers = Barriers::new();
+    let mut names = Names::new();
+    let mut ports: Ports<TcpStream> = Ports::new(addr);
+    let mut next_context = ports::first_context(port);
+    let mut windows: Windows<TcpStream> = Windows::new();
 
     for stream in listener.incoming() {
         match stream {
@@ -101,25 +199,250 @@ fn main() {
                     match *ctrl {
                         ControlTy::Nop => {},
                         ControlTy::Barrier => {
-                            barrier_wait.push(stream.try_clone().unwrap());
-                            if barrier_wait.len() == num_procs {
-                                while let Some(ref mut st) = barrier_wait.pop() {
+                            let members = json::decode(&req.data().expect("No barrier members"))
+                                              .expect("Invalid barrier members");
+                            if let Some(streams) = barriers.enter(req.context(),
+                                                                  req.tag(),
+                                                                  members,
+                                                                  procs.rank(req.pid()),
+                                                                  stream.try_clone().unwrap(),
+                                                                  &names.describe(req.context())) {
+                                for ref mut st in streams {
                                     let ack = json::encode(&make_ack()).unwrap();
-                                    st.write(ack.as_bytes());
+                                    st.write_all(ack.as_bytes());
                                 }
                             }
                         },
                         ControlTy::GetMyRank => {
-                            let to_send = format!("{}", rank_map[&req.pid()]);
-                            stream.write(to_send.as_bytes());
+                            let to_send = format!("{}", procs.rank(req.pid()));
+                            stream.write_all(to_send.as_bytes());
                         }
                         ControlTy::NumProcs => {
-                            let to_send = format!("{}", rank_map.keys().len());
-                            stream.write(to_send.as_bytes());
+                            let to_send = format!("{}", procs.world(req.pid()).members.len());
+                            stream.write_all(to_send.as_bytes());
+                        }
+                        ControlTy::AllocContext => {
+                            let count: u64 = json::decode(&req.data().expect("No context count"))
+                                                 .expect("Invalid context count");
+                            stream.write_all(format!("{}", next_context).as_bytes());
+                            next_context += count;
+                        }
+                        ControlTy::SetName => {
+                            let (context, name): (u64, String) =
+                                json::decode(&req.data().expect("No communicator name"))
+                                    .expect("Invalid communicator name");
+                            names.set(context, name);
+                        }
+                        ControlTy::GetWorld => {
+                            let world = json::encode(procs.world(req.pid())).unwrap();
+                            stream.write_all(world.as_bytes());
+                        }
+                        ControlTy::Spawn => {
+                            let (commands, parent): (Vec<SpawnCommand>, Vec<usize>) =
+                                json::decode(&req.data().expect("No spawn commands"))
+                                    .expect("Invalid spawn commands");
+                            // One context for the new MPI_COMM_WORLD, one for the
+                            // intercommunicator between parents and children
+                            let spawned = match spawn(&commands, port) {
+                                Ok(pids) => {
+                                    let (world, inter) = (next_context, next_context + 1);
+                                    next_context += 2;
+                                    names.set(world, "MPI_COMM_WORLD".to_owned());
+                                    Some((procs.add_world(&pids, world, Some((parent, inter))),
+                                          inter))
+                                }
+                                Err(e) => {
+                                    eprintln!("[mpirun] {}", e);
+                                    None
+                                }
+                            };
+                            stream.write_all(json::encode(&spawned).unwrap().as_bytes());
+                        }
+                        ControlTy::OpenPort => {
+                            let name = Some(ports.open());
+                            stream.write_all(json::encode(&name).unwrap().as_bytes());
+                        }
+                        ControlTy::ClosePort => {
+                            let name: String = json::decode(&req.data().expect("No port"))
+                                                   .expect("Invalid port");
+                            let closed = match ports.close(&name) {
+                                Some(waiting) => {
+                                    for ref mut st in waiting {
+                                        st.write_all(b"null");
+                                    }
+                                    true
+                                }
+                                None => false,
+                            };
+                            stream.write_all(format!("{}", closed).as_bytes());
+                        }
+                        ControlTy::Accept | ControlTy::Join => {
+                            let (name, members): (String, Vec<usize>) =
+                                json::decode(&req.data().expect("No port"))
+                                    .expect("Invalid port");
+                            let st = stream.try_clone().unwrap();
+                            let conn = match *ctrl {
+                                ControlTy::Accept => {
+                                    // The joining job knows these processes by qualified ranks
+                                    let members = members.iter()
+                                                         .map(|&r| ports::qualify(port, r))
+                                                         .collect();
+                                    ports.accept(&name, members, st)
+                                }
+                                _ => ports.join(&name, members, st),
+                            };
+                            match conn {
+                                Ok(Some(conn)) => {
+                                    let context = next_context;
+                                    next_context += 1;
+                                    let (acceptor, mut acceptor_st) = conn.acceptor;
+                                    let (joiner, mut joiner_st) = conn.joiner;
+                                    let to_acceptor = Some((joiner, context));
+                                    let to_joiner = Some((acceptor, context));
+                                    acceptor_st.write_all(json::encode(&to_acceptor)
+                                                              .unwrap()
+                                                              .as_bytes());
+                                    joiner_st.write_all(json::encode(&to_joiner)
+                                                            .unwrap()
+                                                            .as_bytes());
+                                }
+                                Ok(None) => {}
+                                Err(mut st) => {
+                                    st.write_all(b"null");
+                                }
+                            }
+                        }
+                        ControlTy::Connect => {
+                            let (name, members): (String, Vec<usize>) =
+                                json::decode(&req.data().expect("No port"))
+                                    .expect("Invalid port");
+                            let members: Vec<usize> = members.iter()
+                                                             .map(|&r| ports::qualify(port, r))
+                                                             .collect();
+                            match ports::owner(&name) {
+                                Some(owner) => {
+                                    let join = CommRequest::new(None,
+                                                                None,
+                                                                u64::max_value(),
+                                                                Some((name.clone(), members)),
+                                                                CommRequestType::Control(
+                                                                    ControlTy::Join),
+                                                                utils::pid());
+                                    relay(owner.to_owned(), json::encode(&join).unwrap(), stream);
+                                }
+                                None => {
+                                    stream.write_all(b"null");
+                                }
+                            }
+                        }
+                        ControlTy::Publish => {
+                            let (service, name): (String, String) =
+                                json::decode(&req.data().expect("No service"))
+                                    .expect("Invalid service");
+                            let published = ports.publish(service, name);
+                            stream.write_all(format!("{}", published).as_bytes());
+                        }
+                        ControlTy::Lookup => {
+                            let service: String = json::decode(&req.data().expect("No service"))
+                                                      .expect("Invalid service");
+                            let name = ports.lookup(&service);
+                            stream.write_all(json::encode(&name).unwrap().as_bytes());
+                        }
+                        ControlTy::Unpublish => {
+                            let (service, name): (String, String) =
+                                json::decode(&req.data().expect("No service"))
+                                    .expect("Invalid service");
+                            let unpublished = ports.unpublish(&service, &name);
+                            stream.write_all(format!("{}", unpublished).as_bytes());
+                        }
+                        ControlTy::WinCreate => {
+                            let (win, elems): (u64, Vec<String>) =
+                                json::decode(&req.data().expect("No window"))
+                                    .expect("Invalid window");
+                            windows.create(win, procs.rank(req.pid()), elems);
+                            reply_rma(&mut stream, Ok(Vec::new()));
+                        }
+                        ControlTy::WinFree => {
+                            let win: u64 = json::decode(&req.data().expect("No window"))
+                                               .expect("Invalid window");
+                            let elems = windows.free(win, procs.rank(req.pid()));
+                            reply_rma(&mut stream, elems);
+                        }
+                        ControlTy::Put | ControlTy::Store => {
+                            let (win, target, disp, elems): (u64, usize, usize, Vec<String>) =
+                                json::decode(&req.data().expect("No elements"))
+                                    .expect("Invalid elements");
+                            let origin = procs.rank(req.pid());
+                            if let ControlTy::Put = *ctrl {
+                                let put = Access {
+                                    origin: origin,
+                                    disp: disp,
+                                    kind: AccessKind::Put(elems),
+                                    stream: stream.try_clone().unwrap(),
+                                };
+                                if let Some((put, mut st)) = windows.access(win, target, put) {
+                                    reply_rma(&mut st, put);
+                                }
+                            } else {
+                                let (stored, next) =
+                                    windows.store(win, target, origin, disp, elems);
+                                reply_rma(&mut stream, stored.map(|_| Vec::new()));
+                                // Accesses waiting for the update may go ahead
+                                for (elems, mut st) in next {
+                                    reply_rma(&mut st, Ok(elems));
+                                }
+                            }
+                        }
+                        ControlTy::Get | ControlTy::Fetch => {
+                            let (win, target, disp, count): (u64, usize, usize, usize) =
+                                json::decode(&req.data().expect("No elements"))
+                                    .expect("Invalid elements");
+                            let access = Access {
+                                origin: procs.rank(req.pid()),
+                                disp: disp,
+                                kind: if let ControlTy::Get = *ctrl {
+                                    AccessKind::Get(count)
+                                } else {
+                                    AccessKind::Fetch(count)
+                                },
+                                stream: stream.try_clone().unwrap(),
+                            };
+                            if let Some((elems, mut st)) = windows.access(win, target, access) {
+                                reply_rma(&mut st, elems);
+                            }
+                        }
+                        ControlTy::Lock => {
+                            let (win, target, exclusive): (u64, usize, bool) =
+                                json::decode(&req.data().expect("No lock"))
+                                    .expect("Invalid lock");
+                            let origin = procs.rank(req.pid());
+                            let st = stream.try_clone().unwrap();
+                            match windows.lock(win, target, origin, exclusive, st) {
+                                Ok(Some(mut st)) => reply_rma(&mut st, Ok(Vec::new())),
+                                Ok(None) => {}
+                                Err((e, mut st)) => reply_rma(&mut st, Err(e)),
+                            }
+                        }
+                        ControlTy::Unlock => {
+                            let (win, target): (u64, usize) =
+                                json::decode(&req.data().expect("No lock"))
+                                    .expect("Invalid lock");
+                            match windows.unlock(win, target, procs.rank(req.pid())) {
+                                Ok(granted) => {
+                                    reply_rma(&mut stream, Ok(Vec::new()));
+                                    for mut st in granted {
+                                        reply_rma(&mut st, Ok(Vec::new()));
+                                    }
+                                }
+                                Err(e) => reply_rma(&mut stream, Err(e)),
+                            }
                         }
                         ControlTy::Exit => {
-                            exit_count += 1;
-                            if exit_count == num_procs {
+                            // Accesses waiting for the updates of the process may go ahead
+                            for (elems, mut st) in windows.exit(procs.rank(req.pid())) {
+                                reply_rma(&mut st, Ok(elems));
+                            }
+                            if procs.exit(req.pid()) {
                                 break;
                             }
                         },
@@ -130,27 +453,56 @@ fn main() {
 
                 if req.is_send() {
                     let pid = req.pid();
-                    req.set_src(RequestProc::Process(rank_map[&pid]))
+                    match req.src() {
+                        // Relayed by the mpirun of another job, which filled in the source
+                        Some(RequestProc::Process(src)) if ports::remote_port(src).is_some() => {}
+                        _ => req.set_src(RequestProc::Process(procs.rank(pid))),
+                    }
+
+                    // Messages to other jobs are matched by the mpirun of the receiver
+                    if let Some(RequestProc::Process(dest)) = req.dst() {
+                        if let Some(remote) = ports::remote_port(dest) {
+                            let src = ports::qualify(port, procs.rank(pid));
+                            req.set_src(RequestProc::Process(src));
+                            req.set_dest(RequestProc::Process(ports::local_rank(dest)));
+                            relay(format!("127.0.0.1:{}", remote),
+                                  json::encode(&req).unwrap(),
+                                  stream);
+                            continue;
+                        }
+                    }
                 } else {
                     let pid = req.pid();
-                    req.set_dest(RequestProc::Process(rank_map[&pid]))
+                    req.set_dest(RequestProc::Process(procs.rank(pid)))
                 }
 
                 if let Some((ref mail, ref mut stream_r)) = mailbox.pop_matching_mail(&req) {
+                    if args.flag_trace {
+                        let send = if req.is_send() {
+                            req.clone()
+                        } else {
+                            json::decode(&mail.req).expect("Invalid json")
+                        };
+                        eprintln!("[mpirun] {:?} -> {:?} tag {} on {}",
+                                  send.src().unwrap(),
+                                  send.dst().unwrap(),
+                                  send.tag(),
+                                  names.describe(send.context()));
+                    }
                     match req.is_send() {
                         true => {
-                            stream_r.write(&json::encode(&req)
+                            stream_r.write_all(&json::encode(&req)
                                                 .expect("json encode failed!")
                                                 .as_bytes());
                             let ack = make_ack();
-                            stream.write(&json::encode(&ack)
+                            stream.write_all(&json::encode(&ack)
                                               .expect("json encode failed!")
                                               .as_bytes());
                         }
                         false => {
-                            stream.write(mail.req.as_bytes());
+                            stream.write_all(mail.req.as_bytes());
                             let ack = make_ack();
-                            stream_r.write(&json::encode(&ack)
+                            stream_r.write_all(&json::encode(&ack)
                                                 .expect("json encode failed!")
                                                 .as_bytes());
                         }
@@ -0,0 +1,61 @@
+//! Names of communicators
+//!
+//! Processes tell mpirun the names they give their communicators, keyed by context id. Each
+//! context id covers two matching contexts, `2 * id` for point-to-point messages and `2 * id + 1`
+//! for the traffic of collectives.
+
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub struct Names {
+    names: HashMap<u64, String>,
+}
+
+impl Names {
+    pub fn new() -> Names {
+        let mut names = HashMap::new();
+        // MPI_COMM_WORLD has context 0
+        names.insert(0, "MPI_COMM_WORLD".to_owned());
+        Names { names: names }
+    }
+
+    /// Name the communicator with context id `context`. An empty name removes the current one.
+    pub fn set(&mut self, context: u64, name: String) {
+        if name.is_empty() {
+            self.names.remove(&context);
+        } else {
+            self.names.insert(context, name);
+        }
+    }
+
+    /// Describe the communicator a message with matching context `context` travels on
+    pub fn describe(&self, context: u64) -> String {
+        let comm = match self.names.get(&(context / 2)) {
+            Some(name) => name.clone(),
+            None => format!("context {}", context / 2),
+        };
+        if context % 2 == 1 {
+            format!("{} (collective)", comm)
+        } else {
+            comm
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names_describe() {
+        let mut names = Names::new();
+        assert_eq!(names.describe(0), "MPI_COMM_WORLD");
+        assert_eq!(names.describe(1), "MPI_COMM_WORLD (collective)");
+        assert_eq!(names.describe(6), "context 3");
+
+        names.set(3, "rows".to_owned());
+        assert_eq!(names.describe(7), "rows (collective)");
+        names.set(3, String::new());
+        assert_eq!(names.describe(6), "context 3");
+    }
+}
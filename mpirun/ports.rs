@@ -0,0 +1,235 @@
+//! Ports and names for connecting independent jobs
+//!
+//! Every job runs under its own mpirun, told apart by the port mpirun listens on. Processes of
+//! another job are addressed by qualified world ranks, which carry the port of their mpirun in
+//! the upper bits. mpirun relays messages to such processes to the mpirun of their job. Context
+//! ids are allocated from a range of their own for each port too, so communicators spanning jobs
+//! never clash with those of either job.
+//!
+//! A port named `<address of mpirun>/<number>` is opened by a job waiting for others to connect.
+//! The accept of the job and the join the connecting mpirun sends on behalf of its job meet
+//! here, whichever comes first.
+
+use std::collections::{HashMap, VecDeque};
+
+use mpirs::comm_request::RANK_BITS;
+
+/// World rank of the process with world rank `rank` in the job of the mpirun at `port`, as seen
+/// by other jobs
+pub fn qualify(port: u16, rank: usize) -> usize {
+    (port as usize) << RANK_BITS | rank
+}
+
+/// Port of the mpirun running the process with world rank `rank`, `None` for processes of this
+/// job
+pub fn remote_port(rank: usize) -> Option<u16> {
+    match rank >> RANK_BITS {
+        0 => None,
+        port => Some(port as u16),
+    }
+}
+
+/// World rank of a process within its own job
+pub fn local_rank(rank: usize) -> usize {
+    rank & ((1 << RANK_BITS) - 1)
+}
+
+/// First context id the mpirun at `port` hands out. `MPI_COMM_WORLD` has context 0 in every job.
+pub fn first_context(port: u16) -> u64 {
+    (port as u64) << RANK_BITS | 1
+}
+
+/// Address of the mpirun owning the port `name`
+pub fn owner(name: &str) -> Option<&str> {
+    let mut parts = name.rsplitn(2, '/');
+    match (parts.next(), parts.next()) {
+        (Some(_), Some(addr)) => Some(addr),
+        _ => None,
+    }
+}
+
+/// Accept and join that met on a port. Each side gives the world ranks of its processes and
+/// the stream to reply to.
+#[derive(Debug)]
+pub struct Connection<S> {
+    pub acceptor: (Vec<usize>, S),
+    pub joiner: (Vec<usize>, S),
+}
+
+#[derive(Debug)]
+struct Waiting<S> {
+    accepts: VecDeque<(Vec<usize>, S)>,
+    joins: VecDeque<(Vec<usize>, S)>,
+}
+
+#[derive(Debug)]
+pub struct Ports<S> {
+    addr: String,
+    next_port: u64,
+    open: HashMap<String, Waiting<S>>,
+    services: HashMap<String, String>,
+}
+
+impl<S> Ports<S> {
+    /// Ports of the mpirun listening at `addr`
+    pub fn new(addr: String) -> Ports<S> {
+        Ports {
+            addr: addr,
+            next_port: 0,
+            open: HashMap::new(),
+            services: HashMap::new(),
+        }
+    }
+
+    pub fn open(&mut self) -> String {
+        let name = format!("{}/{}", self.addr, self.next_port);
+        self.next_port += 1;
+        self.open.insert(name.clone(),
+                         Waiting {
+                             accepts: VecDeque::new(),
+                             joins: VecDeque::new(),
+                         });
+        name
+    }
+
+    /// Close port `name`. Returns the streams of the accepts and joins still waiting on it,
+    /// `None` if the port is not open.
+    pub fn close(&mut self, name: &str) -> Option<Vec<S>> {
+        self.open.remove(name).map(|waiting| {
+            waiting.accepts
+                   .into_iter()
+                   .chain(waiting.joins.into_iter())
+                   .map(|(_, s)| s)
+                   .collect()
+        })
+    }
+
+    /// The processes `members` accept on port `name`. Returns the connection once a join
+    /// arrived, or gives `stream` back if the port is not open.
+    pub fn accept(&mut self,
+                  name: &str,
+                  members: Vec<usize>,
+                  stream: S)
+                  -> Result<Option<Connection<S>>, S> {
+        match self.open.get_mut(name) {
+            Some(waiting) => {
+                Ok(match waiting.joins.pop_front() {
+                    Some(joiner) => {
+                        Some(Connection {
+                            acceptor: (members, stream),
+                            joiner: joiner,
+                        })
+                    }
+                    None => {
+                        waiting.accepts.push_back((members, stream));
+                        None
+                    }
+                })
+            }
+            None => Err(stream),
+        }
+    }
+
+    /// The processes `members` of another job join on port `name`. Returns the connection once
+    /// an accept arrived, or gives `stream` back if the port is not open.
+    pub fn join(&mut self,
+                name: &str,
+                members: Vec<usize>,
+                stream: S)
+                -> Result<Option<Connection<S>>, S> {
+        match self.open.get_mut(name) {
+            Some(waiting) => {
+                Ok(match waiting.accepts.pop_front() {
+                    Some(acceptor) => {
+                        Some(Connection {
+                            acceptor: acceptor,
+                            joiner: (members, stream),
+                        })
+                    }
+                    None => {
+                        waiting.joins.push_back((members, stream));
+                        None
+                    }
+                })
+            }
+            None => Err(stream),
+        }
+    }
+
+    /// Publish `port` under `service`. Fails if the service is already published.
+    pub fn publish(&mut self, service: String, port: String) -> bool {
+        if self.services.contains_key(&service) {
+            return false;
+        }
+        self.services.insert(service, port);
+        true
+    }
+
+    pub fn lookup(&self, service: &str) -> Option<String> {
+        self.services.get(service).cloned()
+    }
+
+    /// Withdraw `port` published under `service`. Fails if it is not published there.
+    pub fn unpublish(&mut self, service: &str, port: &str) -> bool {
+        if self.services.get(service).map(|p| p == port) != Some(true) {
+            return false;
+        }
+        self.services.remove(service);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ports_accept_then_join() {
+        let mut ports = Ports::new("127.0.0.1:4000".to_owned());
+        let name = ports.open();
+        assert_eq!(owner(&name), Some("127.0.0.1:4000"));
+
+        assert!(ports.accept(&name, vec![0, 1], 'a').unwrap().is_none());
+        let conn = ports.join(&name, vec![qualify(5000, 0)], 'j').unwrap().unwrap();
+        assert_eq!(conn.acceptor, (vec![0, 1], 'a'));
+        assert_eq!(conn.joiner, (vec![qualify(5000, 0)], 'j'));
+    }
+
+    #[test]
+    fn ports_join_then_accept() {
+        let mut ports = Ports::new("127.0.0.1:4000".to_owned());
+        let name = ports.open();
+        assert!(ports.join(&name, vec![7], 'j').unwrap().is_none());
+        let conn = ports.accept(&name, vec![0], 'a').unwrap().unwrap();
+        assert_eq!((conn.acceptor.1, conn.joiner.1), ('a', 'j'));
+    }
+
+    #[test]
+    fn ports_closed() {
+        let mut ports = Ports::new("127.0.0.1:4000".to_owned());
+        let name = ports.open();
+        assert!(ports.accept(&name, vec![0], 'a').unwrap().is_none());
+        assert_eq!(ports.close(&name), Some(vec!['a']));
+        assert_eq!(ports.close(&name), None);
+        assert_eq!(ports.join(&name, vec![0], 'j').unwrap_err(), 'j');
+    }
+
+    #[test]
+    fn ports_names() {
+        let mut ports: Ports<()> = Ports::new("127.0.0.1:4000".to_owned());
+        assert!(ports.publish("sim".to_owned(), "p0".to_owned()));
+        assert!(!ports.publish("sim".to_owned(), "p1".to_owned()));
+        assert_eq!(ports.lookup("sim"), Some("p0".to_owned()));
+        assert!(!ports.unpublish("sim", "p1"));
+        assert!(ports.unpublish("sim", "p0"));
+        assert_eq!(ports.lookup("sim"), None);
+    }
+
+    #[test]
+    fn ports_qualified_ranks() {
+        let rank = qualify(31337, 5);
+        assert_eq!((remote_port(rank), local_rank(rank)), (Some(31337), 5));
+        assert_eq!(remote_port(5), None);
+        assert!(first_context(31337) > first_context(31336));
+    }
+}
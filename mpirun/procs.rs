@@ -0,0 +1,100 @@
+//! Processes run by mpirun
+//!
+//! Every process gets a world rank, numbering all processes of the run in the order they were
+//! started. Processes started together form a world: those mpirun starts itself, and those of
+//! every spawn. mpirun is done once every process has exited, however many were spawned.
+
+use std::collections::HashMap;
+
+use mpirs::comm_request::World;
+
+#[derive(Debug)]
+pub struct Procs {
+    /// World ranks by process id
+    ranks: HashMap<u32, usize>,
+    worlds: Vec<World>,
+    /// World of each process, indexed by world rank
+    world_of: Vec<usize>,
+    exited: usize,
+}
+
+impl Procs {
+    pub fn new() -> Procs {
+        Procs {
+            ranks: HashMap::new(),
+            worlds: Vec::new(),
+            world_of: Vec::new(),
+            exited: 0,
+        }
+    }
+
+    /// Add the processes `pids`, started together, as a world with context id `context`.
+    /// Returns their world ranks.
+    pub fn add_world(&mut self,
+                     pids: &[u32],
+                     context: u64,
+                     parent: Option<(Vec<usize>, u64)>)
+                     -> Vec<usize> {
+        let first = self.world_of.len();
+        let members: Vec<usize> = (first..first + pids.len()).collect();
+        for (&pid, &rank) in pids.iter().zip(members.iter()) {
+            self.ranks.insert(pid, rank);
+            self.world_of.push(self.worlds.len());
+        }
+        self.worlds.push(World {
+            members: members.clone(),
+            context: context,
+            parent: parent,
+        });
+        members
+    }
+
+    pub fn rank(&self, pid: u32) -> usize {
+        *self.ranks.get(&pid).unwrap_or_else(|| panic!("Request from unknown process {}", pid))
+    }
+
+    pub fn world(&self, pid: u32) -> &World {
+        &self.worlds[self.world_of[self.rank(pid)]]
+    }
+
+    /// Process `pid` exited. Returns whether it was the last one.
+    pub fn exit(&mut self, pid: u32) -> bool {
+        self.rank(pid);
+        self.exited += 1;
+        self.exited == self.ranks.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn procs_spawned_world() {
+        let mut procs = Procs::new();
+        assert_eq!(procs.add_world(&[10, 11], 0, None), vec![0, 1]);
+        assert_eq!(procs.add_world(&[20, 21, 22], 3, Some((vec![0, 1], 4))), vec![2, 3, 4]);
+
+        assert_eq!(procs.rank(21), 3);
+        assert_eq!(procs.world(11).members, vec![0, 1]);
+        let world = procs.world(22);
+        assert_eq!((world.members.clone(), world.context), (vec![2, 3, 4], 3));
+        assert_eq!(world.parent, Some((vec![0, 1], 4)));
+    }
+
+    #[test]
+    fn procs_exit_counts_spawned() {
+        let mut procs = Procs::new();
+        procs.add_world(&[10, 11], 0, None);
+        assert!(!procs.exit(10));
+        procs.add_world(&[20], 1, Some((vec![0, 1], 2)));
+        assert!(!procs.exit(11));
+        assert!(procs.exit(20));
+    }
+
+    #[test]
+    #[should_panic]
+    fn procs_unknown() {
+        Procs::new().rank(10);
+    }
+}
@@ -0,0 +1,634 @@
+//! Memory of RMA windows
+//!
+//! mpirun acts as the target of one-sided communication: it keeps the memory every process
+//! exposes in a window, identified by the window and the world rank of the process. Elements are
+//! stored as the JSON the processes sent, mpirun never looks inside them.
+//!
+//! Accumulates combine values with operations only the processes know. The origin fetches the
+//! elements, which holds them, then stores the result, which releases them again. Puts, gets and
+//! fetches of held elements wait in line, as do those overlapping an access waiting before them,
+//! so the update is atomic and accesses to the same element happen in the order they arrived.
+//!
+//! Passive-target epochs lock the memory of a target for one origin or share it among several.
+//! Lock requests are granted in the order they arrive, so shared locks never starve an exclusive
+//! one waiting before them.
+
+use std::collections::{HashMap, VecDeque};
+
+/// An access to the memory of a process by process `origin`, with the stream to reply to
+#[derive(Debug)]
+pub struct Access<S> {
+    pub origin: usize,
+    pub disp: usize,
+    pub kind: AccessKind,
+    pub stream: S,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessKind {
+    /// Write the elements
+    Put(Vec<String>),
+    /// Read this many elements
+    Get(usize),
+    /// Read this many elements for an update, holding them until it is stored
+    Fetch(usize),
+}
+
+impl<S> Access<S> {
+    fn end(&self) -> usize {
+        self.disp +
+        match self.kind {
+            AccessKind::Put(ref elems) => elems.len(),
+            AccessKind::Get(count) | AccessKind::Fetch(count) => count,
+        }
+    }
+
+    fn overlaps(&self, other: &Access<S>) -> bool {
+        self.disp < other.end() && other.disp < self.end()
+    }
+}
+
+/// A lock request waiting to be granted, with the stream to reply to
+#[derive(Debug)]
+struct LockRequest<S> {
+    origin: usize,
+    exclusive: bool,
+    stream: S,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Lock {
+    Free,
+    Shared(Vec<usize>),
+    Exclusive(usize),
+}
+
+#[derive(Debug)]
+struct Memory<S> {
+    elems: Vec<String>,
+    /// Origin of the update holding each element, if any
+    held: Vec<Option<usize>>,
+    /// Accesses waiting for held elements, in the order they arrived
+    waiting: VecDeque<Access<S>>,
+    lock: Lock,
+    lock_requests: VecDeque<LockRequest<S>>,
+}
+
+impl<S> Memory<S> {
+    fn grantable(&self, exclusive: bool) -> bool {
+        match self.lock {
+            Lock::Free => true,
+            Lock::Shared(_) => !exclusive,
+            Lock::Exclusive(_) => false,
+        }
+    }
+
+    fn grant(&mut self, origin: usize, exclusive: bool) {
+        if exclusive {
+            self.lock = Lock::Exclusive(origin);
+        } else if let Lock::Shared(ref mut holders) = self.lock {
+            holders.push(origin);
+        } else {
+            self.lock = Lock::Shared(vec![origin]);
+        }
+    }
+
+    /// Release the lock of `origin`. Returns whether it held one.
+    fn release(&mut self, origin: usize) -> bool {
+        self.lock = match self.lock {
+            Lock::Exclusive(holder) if holder == origin => Lock::Free,
+            Lock::Shared(ref holders) if holders.contains(&origin) => {
+                let rest: Vec<usize> = holders.iter().cloned().filter(|&h| h != origin).collect();
+                if rest.is_empty() {
+                    Lock::Free
+                } else {
+                    Lock::Shared(rest)
+                }
+            }
+            _ => return false,
+        };
+        true
+    }
+
+    /// Grant the lock requests at the front of the line that can be granted now. Returns their
+    /// streams.
+    fn grant_waiting(&mut self) -> Vec<S> {
+        let mut granted = Vec::new();
+        while self.lock_requests.front().map(|r| self.grantable(r.exclusive)) == Some(true) {
+            let request = self.lock_requests.pop_front().unwrap();
+            self.grant(request.origin, request.exclusive);
+            granted.push(request.stream);
+        }
+        granted
+    }
+
+    fn is_held(&self, access: &Access<S>) -> bool {
+        self.held[access.disp..access.end()].iter().any(|h| h.is_some())
+    }
+
+    /// Carry out `access`, which must be within bounds, and return the elements to reply with
+    fn perform(&mut self, access: &Access<S>) -> Vec<String> {
+        let range = access.disp..access.end();
+        match access.kind {
+            AccessKind::Put(ref elems) => {
+                for (i, e) in elems.iter().enumerate() {
+                    self.elems[access.disp + i] = e.clone();
+                }
+                Vec::new()
+            }
+            AccessKind::Get(_) => self.elems[range].to_vec(),
+            AccessKind::Fetch(_) => {
+                for h in &mut self.held[range.clone()] {
+                    *h = Some(access.origin);
+                }
+                self.elems[range].to_vec()
+            }
+        }
+    }
+
+    /// Carry out the waiting accesses that no longer wait for held elements or for an
+    /// overlapping access before them. Returns their replies.
+    fn advance(&mut self) -> Vec<(Vec<String>, S)> {
+        let mut replies = Vec::new();
+        let mut still: VecDeque<Access<S>> = VecDeque::new();
+        while let Some(access) = self.waiting.pop_front() {
+            if self.is_held(&access) || still.iter().any(|w| w.overlaps(&access)) {
+                still.push_back(access);
+            } else {
+                let elems = self.perform(&access);
+                replies.push((elems, access.stream));
+            }
+        }
+        self.waiting = still;
+        replies
+    }
+}
+
+#[derive(Debug)]
+pub struct Windows<S> {
+    memory: HashMap<(u64, usize), Memory<S>>,
+}
+
+impl<S> Windows<S> {
+    pub fn new() -> Windows<S> {
+        Windows { memory: HashMap::new() }
+    }
+
+    /// Process `rank` exposes `elems` in window `win`
+    pub fn create(&mut self, win: u64, rank: usize, elems: Vec<String>) {
+        let n = elems.len();
+        self.memory.insert((win, rank),
+                           Memory {
+                               elems: elems,
+                               held: vec![None; n],
+                               waiting: VecDeque::new(),
+                               lock: Lock::Free,
+                               lock_requests: VecDeque::new(),
+                           });
+    }
+
+    /// Remove the memory process `rank` exposes in window `win` and return its elements
+    pub fn free(&mut self, win: u64, rank: usize) -> Result<Vec<String>, String> {
+        match self.memory.remove(&(win, rank)) {
+            Some(memory) => Ok(memory.elems),
+            None => Err(format!("Process {} has no memory in window {}", rank, win)),
+        }
+    }
+
+    fn range(&mut self,
+             win: u64,
+             rank: usize,
+             disp: usize,
+             count: usize)
+             -> Result<&mut Memory<S>, String> {
+        let memory = match self.memory.get_mut(&(win, rank)) {
+            Some(memory) => memory,
+            None => return Err(format!("Process {} has no memory in window {}", rank, win)),
+        };
+        if disp + count > memory.elems.len() {
+            return Err(format!("Elements {}..{} outside the {} elements of process {} in \
+                                window {}",
+                               disp,
+                               disp + count,
+                               memory.elems.len(),
+                               rank,
+                               win));
+        }
+        Ok(memory)
+    }
+
+    /// Access the memory of process `rank` in window `win`. Returns the reply: the elements read,
+    /// none for a put, or the error. `None` if the access has to wait.
+    pub fn access(&mut self,
+                  win: u64,
+                  rank: usize,
+                  access: Access<S>)
+                  -> Option<(Result<Vec<String>, String>, S)> {
+        let count = access.end() - access.disp;
+        let memory = match self.range(win, rank, access.disp, count) {
+            Ok(memory) => memory,
+            Err(e) => return Some((Err(e), access.stream)),
+        };
+        if memory.is_held(&access) || memory.waiting.iter().any(|w| w.overlaps(&access)) {
+            memory.waiting.push_back(access);
+            return None;
+        }
+        let elems = memory.perform(&access);
+        Some((Ok(elems), access.stream))
+    }
+
+    /// Store the result of the update of process `origin` and release its elements. Also returns
+    /// the replies to the accesses that were waiting for them.
+    pub fn store(&mut self,
+                 win: u64,
+                 rank: usize,
+                 origin: usize,
+                 disp: usize,
+                 elems: Vec<String>)
+                 -> (Result<(), String>, Vec<(Vec<String>, S)>) {
+        let memory = match self.range(win, rank, disp, elems.len()) {
+            Ok(memory) => memory,
+            Err(e) => return (Err(e), Vec::new()),
+        };
+        let range = disp..disp + elems.len();
+        if memory.held[range.clone()].iter().any(|&h| h != Some(origin)) {
+            return (Err(format!("Update of process {} in window {} was never fetched by {}",
+                                rank,
+                                win,
+                                origin)),
+                    Vec::new());
+        }
+        for (i, e) in elems.into_iter().enumerate() {
+            memory.elems[disp + i] = e;
+        }
+        for h in &mut memory.held[range] {
+            *h = None;
+        }
+        (Ok(()), memory.advance())
+    }
+
+    /// Process `origin` locks the memory of process `rank` in window `win`. Returns `stream` if
+    /// the lock is granted at once, `None` if the request has to wait.
+    pub fn lock(&mut self,
+                win: u64,
+                rank: usize,
+                origin: usize,
+                exclusive: bool,
+                stream: S)
+                -> Result<Option<S>, (String, S)> {
+        let memory = match self.memory.get_mut(&(win, rank)) {
+            Some(memory) => memory,
+            None => {
+                return Err((format!("Process {} has no memory in window {}", rank, win), stream))
+            }
+        };
+        let holds = match memory.lock {
+            Lock::Free => false,
+            Lock::Shared(ref holders) => holders.contains(&origin),
+            Lock::Exclusive(holder) => holder == origin,
+        };
+        if holds || memory.lock_requests.iter().any(|r| r.origin == origin) {
+            return Err((format!("Process {} already locks process {} in window {}",
+                                origin,
+                                rank,
+                                win),
+                        stream));
+        }
+
+        if memory.lock_requests.is_empty() && memory.grantable(exclusive) {
+            memory.grant(origin, exclusive);
+            return Ok(Some(stream));
+        }
+        memory.lock_requests.push_back(LockRequest {
+            origin: origin,
+            exclusive: exclusive,
+            stream: stream,
+        });
+        Ok(None)
+    }
+
+    /// Process `origin` releases its lock on process `rank` in window `win`. Returns the streams
+    /// of the requests granted in turn.
+    pub fn unlock(&mut self, win: u64, rank: usize, origin: usize) -> Result<Vec<S>, String> {
+        let memory = match self.memory.get_mut(&(win, rank)) {
+            Some(memory) => memory,
+            None => return Err(format!("Process {} has no memory in window {}", rank, win)),
+        };
+        if !memory.release(origin) {
+            return Err(format!("Process {} does not lock process {} in window {}",
+                               origin,
+                               rank,
+                               win));
+        }
+        Ok(memory.grant_waiting())
+    }
+
+    /// Process `rank` exited: its windows go, and so do the accesses and lock requests it left
+    /// waiting, the elements its updates hold and its locks. Returns the replies to the accesses
+    /// and lock requests that were waiting for them.
+    pub fn exit(&mut self, rank: usize) -> Vec<(Vec<String>, S)> {
+        self.memory.retain(|&(_, r), _| r != rank);
+        let mut replies = Vec::new();
+        for memory in self.memory.values_mut() {
+            memory.waiting.retain(|w| w.origin != rank);
+            for h in &mut memory.held {
+                if *h == Some(rank) {
+                    *h = None;
+                }
+            }
+            replies.extend(memory.advance());
+
+            memory.lock_requests.retain(|r| r.origin != rank);
+            memory.release(rank);
+            replies.extend(memory.grant_waiting().into_iter().map(|st| (Vec::new(), st)));
+        }
+        replies
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Barrier, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use std::thread;
+    use std::time::Duration;
+
+    fn elems(values: &[u32]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn access<S>(origin: usize, disp: usize, kind: AccessKind, stream: S) -> Access<S> {
+        Access {
+            origin: origin,
+            disp: disp,
+            kind: kind,
+            stream: stream,
+        }
+    }
+
+    /// Windows shared by threads standing in for the origins. Replies travel over channels, as
+    /// they travel over the streams of the origins in mpirun.
+    type Shared = Arc<Mutex<Windows<Sender<Vec<String>>>>>;
+
+    /// Wait for the reply to an origin, failing rather than hanging when a broken lock left
+    /// the origin waiting for good
+    fn reply(rx: Receiver<Vec<String>>) -> Vec<String> {
+        rx.recv_timeout(Duration::from_secs(10)).expect("No reply to origin")
+    }
+
+    fn shared(values: &[u32]) -> Shared {
+        let mut windows = Windows::new();
+        windows.create(1, 0, elems(values));
+        Arc::new(Mutex::new(windows))
+    }
+
+    /// Access element `disp` and wait until it is the origin's turn
+    fn single(windows: &Shared, origin: usize, disp: usize, kind: AccessKind) -> Option<u32> {
+        let (tx, rx) = channel();
+        let done = windows.lock().unwrap().access(1, 0, access(origin, disp, kind, tx));
+        if let Some((values, tx)) = done {
+            tx.send(values.unwrap()).unwrap();
+        }
+        reply(rx).get(0).map(|v| v.parse().unwrap())
+    }
+
+    fn store(windows: &Shared, origin: usize, disp: usize, value: u32) {
+        let (stored, next) = windows.lock().unwrap().store(1, 0, origin, disp, elems(&[value]));
+        stored.unwrap();
+        for (values, tx) in next {
+            tx.send(values).unwrap();
+        }
+    }
+
+    /// Add one to element `disp`, holding the update open for a moment
+    fn increment(windows: &Shared, origin: usize, disp: usize) {
+        let old = single(windows, origin, disp, AccessKind::Fetch(1)).unwrap();
+        thread::sleep(Duration::from_millis(1));
+        store(windows, origin, disp, old + 1);
+    }
+
+    fn lock(windows: &Shared, origin: usize, exclusive: bool) {
+        let (tx, rx) = channel();
+        if let Some(tx) = windows.lock().unwrap().lock(1, 0, origin, exclusive, tx).unwrap() {
+            tx.send(Vec::new()).unwrap();
+        }
+        reply(rx);
+    }
+
+    fn unlock(windows: &Shared, origin: usize) {
+        for tx in windows.lock().unwrap().unlock(1, 0, origin).unwrap() {
+            tx.send(Vec::new()).unwrap();
+        }
+    }
+
+    /// Run `origins` threads at once, each calling `f` with its origin
+    fn contend<F>(origins: usize, f: F)
+        where F: Fn(usize) + Send + Sync + 'static
+    {
+        let start = Arc::new(Barrier::new(origins));
+        let f = Arc::new(f);
+        let threads: Vec<_> = (0..origins)
+                                  .map(|origin| {
+                                      let (start, f) = (start.clone(), f.clone());
+                                      thread::spawn(move || {
+                                          start.wait();
+                                          f(origin);
+                                      })
+                                  })
+                                  .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn window_put_get() {
+        let mut windows = Windows::new();
+        windows.create(1, 0, elems(&[0, 0, 0]));
+        let put = windows.access(1, 0, access(2, 1, AccessKind::Put(elems(&[5, 6])), 'p'));
+        assert_eq!(put.map(|(r, s)| (r.unwrap(), s)), Some((Vec::new(), 'p')));
+        let get = windows.access(1, 0, access(2, 0, AccessKind::Get(3), 'g'));
+        assert_eq!(get.map(|(r, s)| (r.unwrap(), s)), Some((elems(&[0, 5, 6]), 'g')));
+        let put = windows.access(1, 0, access(2, 2, AccessKind::Put(elems(&[7, 8])), 'p'));
+        assert!(put.unwrap().0.is_err());
+        assert!(windows.access(1, 1, access(2, 0, AccessKind::Get(1), 'g')).unwrap().0.is_err());
+        assert_eq!(windows.free(1, 0).unwrap(), elems(&[0, 5, 6]));
+    }
+
+    #[test]
+    fn window_fetches_wait_in_line() {
+        let mut windows = Windows::new();
+        windows.create(1, 0, elems(&[1, 2]));
+        let fetch = |origin, stream| access(origin, 0, AccessKind::Fetch(2), stream);
+
+        let (values, stream) = windows.access(1, 0, fetch(1, 'a')).unwrap();
+        assert_eq!((values.unwrap(), stream), (elems(&[1, 2]), 'a'));
+        assert!(windows.access(1, 0, fetch(2, 'b')).is_none());
+        assert!(windows.access(1, 0, fetch(3, 'c')).is_none());
+
+        assert!(windows.store(1, 0, 2, 0, elems(&[0, 0])).0.is_err());
+        let (stored, next) = windows.store(1, 0, 1, 0, elems(&[3, 4]));
+        assert!(stored.is_ok());
+        assert_eq!(next, vec![(elems(&[3, 4]), 'b')]);
+        let (_, next) = windows.store(1, 0, 2, 0, elems(&[3, 9]));
+        assert_eq!(next, vec![(elems(&[3, 9]), 'c')]);
+        let (_, next) = windows.store(1, 0, 3, 0, elems(&[0, 0]));
+        assert!(next.is_empty());
+        assert!(windows.store(1, 0, 3, 0, elems(&[0])).0.is_err());
+    }
+
+    #[test]
+    fn window_updates_queue_up() {
+        // Eight origins increment the same counter, all fetching before any of them stores
+        let mut windows = Windows::new();
+        windows.create(1, 0, elems(&[0]));
+        let mut granted = Vec::new();
+        for origin in 0..8 {
+            let fetch = access(origin, 0, AccessKind::Fetch(1), origin);
+            if let Some((values, origin)) = windows.access(1, 0, fetch) {
+                granted.push((values.unwrap(), origin));
+            }
+        }
+
+        let mut order = Vec::new();
+        while let Some((values, origin)) = granted.pop() {
+            assert!(granted.is_empty());
+            order.push(origin);
+            let count: u32 = values[0].parse().unwrap();
+            let (stored, next) = windows.store(1, 0, origin, 0, elems(&[count + 1]));
+            stored.unwrap();
+            granted.extend(next);
+        }
+        assert_eq!(order, (0..8).collect::<Vec<_>>());
+        assert_eq!(windows.free(1, 0).unwrap(), elems(&[8]));
+    }
+
+    #[test]
+    fn window_puts_and_gets_wait_for_updates() {
+        let mut windows = Windows::new();
+        windows.create(1, 0, elems(&[1, 2, 3]));
+        assert!(windows.access(1, 0, access(1, 0, AccessKind::Fetch(1), 'f')).is_some());
+
+        // Element 0 is held. The put waits for it, the get behind it for the put; element 2 is
+        // free.
+        assert!(windows.access(1, 0, access(2, 0, AccessKind::Put(elems(&[7, 8])), 'p')).is_none());
+        assert!(windows.access(1, 0, access(3, 1, AccessKind::Get(1), 'g')).is_none());
+        let (value, _) = windows.access(1, 0, access(3, 2, AccessKind::Get(1), 'h')).unwrap();
+        assert_eq!(value.unwrap(), elems(&[3]));
+
+        let (_, next) = windows.store(1, 0, 1, 0, elems(&[5]));
+        assert_eq!(next, vec![(Vec::new(), 'p'), (elems(&[8]), 'g')]);
+        let (values, _) = windows.access(1, 0, access(3, 0, AccessKind::Get(3), 'g')).unwrap();
+        assert_eq!(values.unwrap(), elems(&[7, 8, 3]));
+    }
+
+    #[test]
+    fn window_exclusive_locks_one_at_a_time() {
+        let mut windows = Windows::new();
+        windows.create(1, 0, elems(&[0]));
+        assert_eq!(windows.lock(1, 0, 1, true, 'a').unwrap(), Some('a'));
+        assert_eq!(windows.lock(1, 0, 2, true, 'b').unwrap(), None);
+        assert_eq!(windows.lock(1, 0, 3, false, 'c').unwrap(), None);
+        assert!(windows.lock(1, 0, 1, false, 'x').is_err());
+
+        assert_eq!(windows.unlock(1, 0, 1).unwrap(), vec!['b']);
+        assert!(windows.unlock(1, 0, 1).is_err());
+        assert_eq!(windows.unlock(1, 0, 2).unwrap(), vec!['c']);
+        assert_eq!(windows.unlock(1, 0, 3).unwrap(), Vec::<char>::new());
+    }
+
+    #[test]
+    fn window_shared_locks_do_not_overtake() {
+        let mut windows = Windows::new();
+        windows.create(1, 0, elems(&[0]));
+        assert_eq!(windows.lock(1, 0, 1, false, 'a').unwrap(), Some('a'));
+        assert_eq!(windows.lock(1, 0, 2, false, 'b').unwrap(), Some('b'));
+        // A shared lock behind a waiting exclusive one waits too
+        assert_eq!(windows.lock(1, 0, 3, true, 'c').unwrap(), None);
+        assert_eq!(windows.lock(1, 0, 4, false, 'd').unwrap(), None);
+        assert_eq!(windows.lock(1, 0, 5, false, 'e').unwrap(), None);
+
+        assert_eq!(windows.unlock(1, 0, 1).unwrap(), Vec::<char>::new());
+        assert_eq!(windows.unlock(1, 0, 2).unwrap(), vec!['c']);
+        assert_eq!(windows.unlock(1, 0, 3).unwrap(), vec!['d', 'e']);
+    }
+
+    #[test]
+    fn window_exit_releases_origin() {
+        let mut windows = Windows::new();
+        windows.create(1, 0, elems(&[1]));
+        windows.create(1, 1, elems(&[2]));
+        assert!(windows.access(1, 0, access(1, 0, AccessKind::Fetch(1), 'a')).is_some());
+        assert!(windows.access(1, 0, access(1, 0, AccessKind::Get(1), 'b')).is_none());
+        assert!(windows.access(1, 0, access(2, 0, AccessKind::Fetch(1), 'c')).is_none());
+        assert_eq!(windows.lock(1, 0, 1, true, 'l').unwrap(), Some('l'));
+        assert_eq!(windows.lock(1, 0, 3, true, 'm').unwrap(), None);
+
+        // Process 1 exits in the middle of its update and its epoch: its memory goes, process 2
+        // gets the element it waits for and process 3 the lock
+        assert_eq!(windows.exit(1), vec![(elems(&[1]), 'c'), (Vec::new(), 'm')]);
+        assert!(windows.free(1, 1).is_err());
+        assert!(windows.store(1, 0, 2, 0, elems(&[4])).0.is_ok());
+        assert!(windows.unlock(1, 0, 3).is_ok());
+    }
+
+    #[test]
+    fn window_compare_and_swap_under_contention() {
+        // Sixteen origins race to claim an empty slot, as mpi_compare_and_swap does: fetch,
+        // compare with 0 and store their own number or the old value back. Gets of the slot
+        // mixed in never see it between fetch and store.
+        let windows = shared(&[0]);
+        let winners = Arc::new(Mutex::new(Vec::new()));
+        let (w, ws) = (windows.clone(), winners.clone());
+        contend(16, move |origin| {
+            let seen = single(&w, origin, 0, AccessKind::Get(1)).unwrap();
+            let old = single(&w, origin, 0, AccessKind::Fetch(1)).unwrap();
+            assert!(old == 0 || seen <= old);
+            // Give the other origins every chance to slip in before the store
+            thread::sleep(Duration::from_millis(1));
+            let claim = origin as u32 + 1;
+            store(&w, origin, 0, if old == 0 { claim } else { old });
+            if old == 0 {
+                ws.lock().unwrap().push(claim);
+            }
+        });
+
+        let winners = winners.lock().unwrap();
+        assert_eq!(winners.len(), 1);
+        assert_eq!(single(&windows, 0, 0, AccessKind::Get(1)), Some(winners[0]));
+    }
+
+    #[test]
+    fn window_locks_and_updates_interleave() {
+        // Origins take turns between an exclusive read-modify-write of element 0 with get and
+        // put, updates of element 1 without a lock, and updates of element 1 under a shared lock
+        const ORIGINS: usize = 8;
+        const ROUNDS: u32 = 20;
+        let windows = shared(&[0, 0]);
+        let exclusive = Arc::new(AtomicUsize::new(0));
+        let (w, ex) = (windows.clone(), exclusive.clone());
+        contend(ORIGINS, move |origin| {
+            for _ in 0..ROUNDS {
+                lock(&w, origin, true);
+                assert_eq!(ex.fetch_add(1, Ordering::SeqCst), 0);
+                let value = single(&w, origin, 0, AccessKind::Get(1)).unwrap();
+                thread::sleep(Duration::from_millis(1));
+                single(&w, origin, 0, AccessKind::Put(elems(&[value + 1])));
+                ex.fetch_sub(1, Ordering::SeqCst);
+                unlock(&w, origin);
+
+                increment(&w, origin, 1);
+
+                lock(&w, origin, false);
+                assert_eq!(ex.load(Ordering::SeqCst), 0);
+                increment(&w, origin, 1);
+                unlock(&w, origin);
+            }
+        });
+
+        let total = ORIGINS as u32 * ROUNDS;
+        assert_eq!(windows.lock().unwrap().free(1, 0).unwrap(), elems(&[total, 2 * total]));
+    }
+}
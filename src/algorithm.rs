@@ -0,0 +1,300 @@
+//! Selection of collective algorithms
+//!
+//! Collectives with several implementations pick one from a decision table, based on the number
+//! of processes and the number of elements of the call. Both are the same on every process, so
+//! all processes agree on the algorithm without talking to each other.
+//!
+//! The decision tables can be overridden to tune for a machine, either through the environment,
+//! which mpirun passes on to every process:
+//!
+//! | Variable              | Values                                         |
+//! |-----------------------|------------------------------------------------|
+//! | `MPIRS_BCAST`         | `binomial`, `pipelined`, `scatter_allgather`   |
+//! | `MPIRS_ALLREDUCE`     | `reduce_bcast`, `recursive_doubling`, `ring`   |
+//! | `MPIRS_ALLTOALL`      | `pairwise`, `bruck`                            |
+//! | `MPIRS_BCAST_SEGMENT` | elements per segment of a pipelined broadcast  |
+//! | `MPIRS_BARRIER`       | `central`, `dissemination`                     |
+//!
+//! or at runtime with the `set_*` functions, which every process must call with the same values
+//! before its next collective.
+
+use std::env;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::usize;
+
+/// Algorithms of `mpi_bcast_vec`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BcastAlgorithm {
+    /// Every process forwards the whole vector down a binomial tree
+    Binomial,
+    /// The vector flows down a chain of processes in segments of `MPIRS_BCAST_SEGMENT` elements
+    Pipelined,
+    /// Chunks are scattered down a binomial tree and then gathered around a ring
+    ScatterAllgather,
+}
+
+/// Algorithms of `mpi_allreduce`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllreduceAlgorithm {
+    /// Reduce to one process and broadcast the result
+    ReduceBcast,
+    /// Processes exchange partial results with a partner at doubling distances
+    RecursiveDoubling,
+    /// Ring reduce-scatter followed by a ring allgather. Only for commutative operations, others
+    /// fall back to recursive doubling.
+    Ring,
+}
+
+/// Algorithms of `mpi_alltoall`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlltoallAlgorithm {
+    /// Exchange blocks directly with every other process in turn
+    Pairwise,
+    /// Forward blocks through intermediate processes in log(n) steps
+    Bruck,
+}
+
+/// Algorithms of `mpi_barrier`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarrierAlgorithm {
+    /// mpirun counts the processes entering the barrier and releases them together
+    Central,
+    /// Processes signal partners at doubling distances, without going through mpirun
+    Dissemination,
+}
+
+impl FromStr for BcastAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BcastAlgorithm, String> {
+        match s {
+            "binomial" => Ok(BcastAlgorithm::Binomial),
+            "pipelined" => Ok(BcastAlgorithm::Pipelined),
+            "scatter_allgather" => Ok(BcastAlgorithm::ScatterAllgather),
+            _ => Err(format!("Unknown broadcast algorithm {}", s)),
+        }
+    }
+}
+
+impl FromStr for AllreduceAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AllreduceAlgorithm, String> {
+        match s {
+            "reduce_bcast" => Ok(AllreduceAlgorithm::ReduceBcast),
+            "recursive_doubling" => Ok(AllreduceAlgorithm::RecursiveDoubling),
+            "ring" => Ok(AllreduceAlgorithm::Ring),
+            _ => Err(format!("Unknown allreduce algorithm {}", s)),
+        }
+    }
+}
+
+impl FromStr for AlltoallAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AlltoallAlgorithm, String> {
+        match s {
+            "pairwise" => Ok(AlltoallAlgorithm::Pairwise),
+            "bruck" => Ok(AlltoallAlgorithm::Bruck),
+            _ => Err(format!("Unknown alltoall algorithm {}", s)),
+        }
+    }
+}
+
+impl FromStr for BarrierAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BarrierAlgorithm, String> {
+        match s {
+            "central" => Ok(BarrierAlgorithm::Central),
+            "dissemination" => Ok(BarrierAlgorithm::Dissemination),
+            _ => Err(format!("Unknown barrier algorithm {}", s)),
+        }
+    }
+}
+
+/// A row of a decision table. It applies to calls with fewer than `procs_below` processes and
+/// fewer than `count_below` elements.
+struct Rule<A> {
+    procs_below: usize,
+    count_below: usize,
+    algorithm: A,
+}
+
+/// Rows are tried in order, the last one must apply to every call
+fn decide<A: Copy>(rules: &[Rule<A>], n: usize, count: usize) -> A {
+    rules.iter()
+         .find(|r| n < r.procs_below && count < r.count_below)
+         .expect("Decision table has no default")
+         .algorithm
+}
+
+static BCAST_RULES: [Rule<BcastAlgorithm>; 3] = [
+    Rule { procs_below: usize::MAX, count_below: 4096, algorithm: BcastAlgorithm::Binomial },
+    Rule { procs_below: 8, count_below: usize::MAX, algorithm: BcastAlgorithm::Pipelined },
+    Rule {
+        procs_below: usize::MAX,
+        count_below: usize::MAX,
+        algorithm: BcastAlgorithm::ScatterAllgather,
+    },
+];
+
+static ALLREDUCE_RULES: [Rule<AllreduceAlgorithm>; 2] = [
+    Rule {
+        procs_below: usize::MAX,
+        count_below: 1024,
+        algorithm: AllreduceAlgorithm::RecursiveDoubling,
+    },
+    Rule { procs_below: usize::MAX, count_below: usize::MAX, algorithm: AllreduceAlgorithm::Ring },
+];
+
+/// `count` is the number of elements sent to each process
+static ALLTOALL_RULES: [Rule<AlltoallAlgorithm>; 3] = [
+    Rule { procs_below: 8, count_below: usize::MAX, algorithm: AlltoallAlgorithm::Pairwise },
+    Rule { procs_below: usize::MAX, count_below: 256, algorithm: AlltoallAlgorithm::Bruck },
+    Rule {
+        procs_below: usize::MAX,
+        count_below: usize::MAX,
+        algorithm: AlltoallAlgorithm::Pairwise,
+    },
+];
+
+static BARRIER_RULES: [Rule<BarrierAlgorithm>; 2] = [
+    Rule { procs_below: 16, count_below: usize::MAX, algorithm: BarrierAlgorithm::Central },
+    Rule {
+        procs_below: usize::MAX,
+        count_below: usize::MAX,
+        algorithm: BarrierAlgorithm::Dissemination,
+    },
+];
+
+const DEFAULT_BCAST_SEGMENT: usize = 1024;
+
+struct Params {
+    bcast: Option<BcastAlgorithm>,
+    allreduce: Option<AllreduceAlgorithm>,
+    alltoall: Option<AlltoallAlgorithm>,
+    barrier: Option<BarrierAlgorithm>,
+    bcast_segment: usize,
+}
+
+fn from_env<T: FromStr<Err = String>>(var: &str) -> Option<T> {
+    env::var(var).ok().map(|v| v.parse().unwrap_or_else(|e| panic!("{}: {}", var, e)))
+}
+
+impl Params {
+    fn from_env() -> Params {
+        let segment = env::var("MPIRS_BCAST_SEGMENT").ok().map(|v| {
+            v.parse().unwrap_or_else(|_| panic!("MPIRS_BCAST_SEGMENT: {} is not a count", v))
+        });
+
+        Params {
+            bcast: from_env("MPIRS_BCAST"),
+            allreduce: from_env("MPIRS_ALLREDUCE"),
+            alltoall: from_env("MPIRS_ALLTOALL"),
+            barrier: from_env("MPIRS_BARRIER"),
+            bcast_segment: check_segment(segment.unwrap_or(DEFAULT_BCAST_SEGMENT)),
+        }
+    }
+}
+
+fn check_segment(count: usize) -> usize {
+    if count == 0 {
+        panic!("Broadcast segments must hold at least one element");
+    }
+    count
+}
+
+lazy_static! {
+    static ref PARAMS: RwLock<Params> = RwLock::new(Params::from_env());
+}
+
+/// Always use `alg` for broadcasts of vectors, or go back to the decision table with `None`
+pub fn set_bcast_algorithm(alg: Option<BcastAlgorithm>) {
+    PARAMS.write().unwrap().bcast = alg;
+}
+
+/// Always use `alg` for allreduce, or go back to the decision table with `None`
+pub fn set_allreduce_algorithm(alg: Option<AllreduceAlgorithm>) {
+    PARAMS.write().unwrap().allreduce = alg;
+}
+
+/// Always use `alg` for alltoall, or go back to the decision table with `None`
+pub fn set_alltoall_algorithm(alg: Option<AlltoallAlgorithm>) {
+    PARAMS.write().unwrap().alltoall = alg;
+}
+
+/// Always use `alg` for barriers, or go back to the decision table with `None`
+pub fn set_barrier_algorithm(alg: Option<BarrierAlgorithm>) {
+    PARAMS.write().unwrap().barrier = alg;
+}
+
+/// Set the number of elements per segment of a pipelined broadcast
+pub fn set_bcast_segment(count: usize) {
+    PARAMS.write().unwrap().bcast_segment = check_segment(count);
+}
+
+pub(crate) fn bcast(n: usize, count: usize) -> BcastAlgorithm {
+    PARAMS.read().unwrap().bcast.unwrap_or_else(|| decide(&BCAST_RULES, n, count))
+}
+
+pub(crate) fn bcast_segment() -> usize {
+    PARAMS.read().unwrap().bcast_segment
+}
+
+pub(crate) fn allreduce(n: usize, count: usize, commutative: bool) -> AllreduceAlgorithm {
+    let forced = PARAMS.read().unwrap().allreduce;
+    let alg = forced.unwrap_or_else(|| decide(&ALLREDUCE_RULES, n, count));
+    match alg {
+        AllreduceAlgorithm::Ring if !commutative => AllreduceAlgorithm::RecursiveDoubling,
+        _ => alg,
+    }
+}
+
+pub(crate) fn alltoall(n: usize, count: usize) -> AlltoallAlgorithm {
+    PARAMS.read().unwrap().alltoall.unwrap_or_else(|| decide(&ALLTOALL_RULES, n, count))
+}
+
+pub(crate) fn barrier(n: usize) -> BarrierAlgorithm {
+    PARAMS.read().unwrap().barrier.unwrap_or_else(|| decide(&BARRIER_RULES, n, 0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn algorithm_bcast_table() {
+        assert_eq!(decide(&BCAST_RULES, 64, 100), BcastAlgorithm::Binomial);
+        assert_eq!(decide(&BCAST_RULES, 4, 100000), BcastAlgorithm::Pipelined);
+        assert_eq!(decide(&BCAST_RULES, 16, 100000), BcastAlgorithm::ScatterAllgather);
+    }
+
+    #[test]
+    fn algorithm_allreduce_table() {
+        assert_eq!(decide(&ALLREDUCE_RULES, 4, 10), AllreduceAlgorithm::RecursiveDoubling);
+        assert_eq!(decide(&ALLREDUCE_RULES, 4, 10000), AllreduceAlgorithm::Ring);
+    }
+
+    #[test]
+    fn algorithm_alltoall_table() {
+        assert_eq!(decide(&ALLTOALL_RULES, 4, 1), AlltoallAlgorithm::Pairwise);
+        assert_eq!(decide(&ALLTOALL_RULES, 32, 1), AlltoallAlgorithm::Bruck);
+        assert_eq!(decide(&ALLTOALL_RULES, 32, 1000), AlltoallAlgorithm::Pairwise);
+    }
+
+    #[test]
+    fn algorithm_barrier_table() {
+        assert_eq!(decide(&BARRIER_RULES, 4, 0), BarrierAlgorithm::Central);
+        assert_eq!(decide(&BARRIER_RULES, 64, 0), BarrierAlgorithm::Dissemination);
+    }
+
+    #[test]
+    fn algorithm_parse() {
+        assert_eq!("pipelined".parse(), Ok(BcastAlgorithm::Pipelined));
+        assert_eq!("ring".parse(), Ok(AllreduceAlgorithm::Ring));
+        assert_eq!("bruck".parse(), Ok(AlltoallAlgorithm::Bruck));
+        assert!("linear".parse::<BcastAlgorithm>().is_err());
+    }
+}
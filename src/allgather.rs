@@ -0,0 +1,155 @@
+//! Implements mpi_allgather and mpi_allgatherv
+//!
+//! Blocks travel around a ring: in every step each process passes the block it received last to
+//! its right neighbour, so after n - 1 steps every process holds all blocks and no single process
+//! has to relay the whole buffer.
+//!
+//! On an intercommunicator every group receives the blocks of the other group: each group
+//! gathers its own blocks, the leaders swap them and broadcast what they received.
+
+use rustc_serialize::Decodable;
+use mpi_comm::{MPIComm, coll_context, remote_coll_context, next_inter_tag, mpi_comm_test_inter};
+use bcast::bcast;
+use mpi_request::CollRequest;
+use comm_request::RequestProc;
+use std::fmt::Debug;
+use rustc_serialize::Encodable;
+use sendrecv::sendrecv;
+use comm_rank::mpi_comm_rank;
+use num_procs::{mpi_get_num_procs, mpi_comm_remote_size};
+use layout;
+
+/// Collect the block of every process, indexed by rank
+pub(crate) fn ring_allgather<T>(block: Vec<T>, comm: MPIComm, tag: u64) -> Vec<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+    let left = RequestProc::Process((rank + n - 1) % n);
+    let right = RequestProc::Process((rank + 1) % n);
+
+    let mut blocks = vec![Vec::new(); n];
+    blocks[rank] = block;
+
+    for step in 0..n - 1 {
+        let send_idx = (rank + n - step) % n;
+        let recv_idx = (rank + n - step - 1) % n;
+        let mut incoming: Vec<T> = Vec::new();
+        sendrecv(&blocks[send_idx], right, tag, &mut incoming, left, tag, coll_context(comm));
+        blocks[recv_idx] = incoming;
+    }
+    blocks
+}
+
+/// Gather `sendbuf` of every process, all of the same size, into `recvbuf` of all processes in
+/// rank order. On an intercommunicator, `recvbuf` receives the blocks of the remote group.
+pub fn mpi_allgather<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    allgather(sendbuf, recvbuf, comm, next_inter_tag(comm));
+}
+
+/// Start gathering `sendbuf` of every process. `wait` returns the blocks in rank order.
+pub fn mpi_iallgather<T>(sendbuf: Vec<T>, comm: MPIComm) -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = Vec::new();
+        allgather(&sendbuf, &mut recvbuf, comm, tag);
+        recvbuf
+    })
+}
+
+fn allgather<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let blocks = ring_allgather(sendbuf.to_vec(), comm, tag);
+    if !mpi_comm_test_inter(comm) {
+        *recvbuf = layout::concat(blocks, sendbuf.len());
+        return;
+    }
+
+    if mpi_comm_rank(comm) == 0 {
+        let local = layout::concat(blocks, sendbuf.len());
+        let leader = RequestProc::Process(0);
+        sendrecv(&local, leader, tag, recvbuf, leader, tag, remote_coll_context(comm));
+    }
+    bcast(recvbuf, 0, comm, tag);
+}
+
+/// Gather `sendbuf` of every process into `recvbuf` of all processes. The block of rank `i` has
+/// `recvcounts[i]` elements and is placed at offset `displs[i]`. `recvbuf` must already be large
+/// enough to hold every block. On an intercommunicator, `recvbuf` receives the blocks of the
+/// remote group, which `recvcounts` and `displs` describe.
+pub fn mpi_allgatherv<T>(sendbuf: &[T],
+                         recvbuf: &mut Vec<T>,
+                         recvcounts: &[usize],
+                         displs: &[usize],
+                         comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    allgatherv(sendbuf, recvbuf, recvcounts, displs, comm, next_inter_tag(comm));
+}
+
+/// Start gathering `sendbuf` of every process into `recvbuf`. `wait` returns `recvbuf` with the
+/// blocks placed as in `mpi_allgatherv`.
+pub fn mpi_iallgatherv<T>(sendbuf: Vec<T>,
+                          recvbuf: Vec<T>,
+                          recvcounts: Vec<usize>,
+                          displs: Vec<usize>,
+                          comm: MPIComm)
+                          -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = recvbuf;
+        allgatherv(&sendbuf, &mut recvbuf, &recvcounts, &displs, comm, tag);
+        recvbuf
+    })
+}
+
+fn allgatherv<T>(sendbuf: &[T],
+                 recvbuf: &mut Vec<T>,
+                 recvcounts: &[usize],
+                 displs: &[usize],
+                 comm: MPIComm,
+                 tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if mpi_comm_test_inter(comm) {
+        return inter_allgatherv(sendbuf, recvbuf, recvcounts, displs, comm, tag);
+    }
+
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+
+    layout::check_layout(recvcounts, displs, n);
+    if sendbuf.len() != recvcounts[rank] {
+        panic!("Send buffer size does not match receive count of process {}", rank);
+    }
+
+    let blocks = ring_allgather(sendbuf.to_vec(), comm, tag);
+    layout::place(recvbuf, blocks, recvcounts, displs);
+}
+
+fn inter_allgatherv<T>(sendbuf: &[T],
+                       recvbuf: &mut Vec<T>,
+                       recvcounts: &[usize],
+                       displs: &[usize],
+                       comm: MPIComm,
+                       tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    layout::check_layout(recvcounts, displs, mpi_comm_remote_size(comm));
+    let local = ring_allgather(sendbuf.to_vec(), comm, tag);
+
+    let mut blocks: Vec<Vec<T>> = Vec::new();
+    if mpi_comm_rank(comm) == 0 {
+        let leader = RequestProc::Process(0);
+        sendrecv(&local, leader, tag, &mut blocks, leader, tag, remote_coll_context(comm));
+    }
+    bcast(&mut blocks, 0, comm, tag);
+    layout::place(recvbuf, blocks, recvcounts, displs);
+}
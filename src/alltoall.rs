@@ -0,0 +1,284 @@
+//! Implements mpi_alltoall, mpi_alltoallv and mpi_alltoallw
+//!
+//! Every process sends a separate block to every other process. The exchange is done pairwise:
+//! in step `s` a process sends to `rank + s` while receiving from `rank - s`, so no process is
+//! ever waiting on a peer that is itself blocked on a send.
+//!
+//! Small blocks of `mpi_alltoall` may instead use Bruck's algorithm, which needs only log(n)
+//! steps by forwarding blocks through other processes. `algorithm` decides which one to use.
+//!
+//! On an intercommunicator every process sends a block to every process of the other group and
+//! receives one from each of them, walking the other group in rank order.
+
+use rustc_serialize::{json, Decodable};
+use mpi_comm::{MPIComm, coll_context, remote_coll_context, next_inter_tag, mpi_comm_test_inter};
+use mpi_request::CollRequest;
+use comm_request::RequestProc;
+use std::fmt::Debug;
+use rustc_serialize::Encodable;
+use sendrecv::sendrecv;
+use comm_rank::mpi_comm_rank;
+use num_procs::{mpi_get_num_procs, mpi_comm_remote_size};
+use layout;
+use algorithm::{self, AlltoallAlgorithm};
+
+/// A value of any type, encoded for `mpi_alltoallw`
+#[derive(Debug, Clone, RustcEncodable, RustcDecodable)]
+pub struct Block(String);
+
+/// Send `blocks[i]` to process `i` and return the blocks received, indexed by source rank
+pub(crate) fn pairwise_exchange<B>(blocks: Vec<B>, comm: MPIComm, tag: u64) -> Vec<B>
+    where B: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+
+    let mut outgoing: Vec<Option<B>> = blocks.into_iter().map(Some).collect();
+    let mut received: Vec<Option<B>> = vec![None; n];
+    received[rank] = outgoing[rank].take();
+
+    for step in 1..n {
+        let dest = (rank + step) % n;
+        let src = (rank + n - step) % n;
+        let mut incoming: Option<B> = None;
+        sendrecv(&outgoing[dest].take(),
+                 RequestProc::Process(dest),
+                 tag,
+                 &mut incoming,
+                 RequestProc::Process(src),
+                 tag,
+                 coll_context(comm));
+        received[src] = incoming;
+    }
+
+    received.into_iter().map(|b| b.expect("Missing block in alltoall")).collect()
+}
+
+/// Same as `pairwise_exchange`, in log(n) steps
+fn bruck_exchange<B>(blocks: Vec<B>, comm: MPIComm, tag: u64) -> Vec<B>
+    where B: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+
+    // Slot `i` holds the block that still has to travel `i` processes to the right
+    let mut outgoing: Vec<Option<B>> = blocks.into_iter().map(Some).collect();
+    let mut slots: Vec<Option<B>> = (0..n).map(|i| outgoing[(rank + i) % n].take()).collect();
+
+    let mut k = 1;
+    while k < n {
+        let moving: Vec<usize> = (0..n).filter(|i| i & k != 0).collect();
+        let batch: Vec<B> = moving.iter()
+                                  .map(|&i| slots[i].take().expect("Missing block in alltoall"))
+                                  .collect();
+        let mut incoming: Vec<B> = Vec::new();
+        sendrecv(&batch,
+                 RequestProc::Process((rank + k) % n),
+                 tag,
+                 &mut incoming,
+                 RequestProc::Process((rank + n - k) % n),
+                 tag,
+                 coll_context(comm));
+        for (&i, block) in moving.iter().zip(incoming.into_iter()) {
+            slots[i] = Some(block);
+        }
+        k <<= 1;
+    }
+
+    // Slot `i` now holds the block that came from process `rank - i`
+    let mut received: Vec<Option<B>> = vec![None; n];
+    for (i, block) in slots.into_iter().enumerate() {
+        received[(rank + n - i) % n] = block;
+    }
+    received.into_iter().map(|b| b.expect("Missing block in alltoall")).collect()
+}
+
+/// Send `blocks[i]` to process `i` of the other group of intercommunicator `comm` and return the
+/// blocks received, indexed by source rank. Every exchange only waits on exchanges with processes
+/// of lower rank, so the groups cannot deadlock.
+fn inter_exchange<B>(blocks: Vec<B>, comm: MPIComm, tag: u64) -> Vec<B>
+    where B: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    blocks.into_iter()
+          .enumerate()
+          .map(|(i, block)| {
+              let peer = RequestProc::Process(i);
+              let mut incoming: Option<B> = None;
+              sendrecv(&Some(block),
+                       peer,
+                       tag,
+                       &mut incoming,
+                       peer,
+                       tag,
+                       remote_coll_context(comm));
+              incoming.expect("Missing block in alltoall")
+          })
+          .collect()
+}
+
+/// Send `blocks[i]` to process `i`, of the other group on an intercommunicator, and return the
+/// blocks received, indexed by source rank
+fn exchange<B>(blocks: Vec<B>, comm: MPIComm, tag: u64) -> Vec<B>
+    where B: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if mpi_comm_test_inter(comm) {
+        inter_exchange(blocks, comm, tag)
+    } else {
+        pairwise_exchange(blocks, comm, tag)
+    }
+}
+
+/// Number of processes blocks are sent to: the other group on an intercommunicator
+fn peers(comm: MPIComm) -> usize {
+    if mpi_comm_test_inter(comm) {
+        mpi_comm_remote_size(comm)
+    } else {
+        mpi_get_num_procs(comm)
+    }
+}
+
+/// Send the `i`th of n equal blocks of `sendbuf` to process `i`. `recvbuf` receives the blocks
+/// of all processes in rank order.
+pub fn mpi_alltoall<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    alltoall(sendbuf, recvbuf, comm, next_inter_tag(comm));
+}
+
+/// Start sending the `i`th of n equal blocks of `sendbuf` to process `i`. `wait` returns the
+/// blocks received from all processes in rank order.
+pub fn mpi_ialltoall<T>(sendbuf: Vec<T>, comm: MPIComm) -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = Vec::new();
+        alltoall(&sendbuf, &mut recvbuf, comm, tag);
+        recvbuf
+    })
+}
+
+fn alltoall<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = peers(comm);
+    let blocks = layout::split_equal(sendbuf, n);
+    if mpi_comm_test_inter(comm) {
+        // The other group may send blocks of another size than this one
+        let received = inter_exchange(blocks, comm, tag);
+        let count = received.first().map_or(0, |b| b.len());
+        *recvbuf = layout::concat(received, count);
+        return;
+    }
+
+    let count = sendbuf.len() / n;
+    let received = match algorithm::alltoall(n, count) {
+        AlltoallAlgorithm::Pairwise => pairwise_exchange(blocks, comm, tag),
+        AlltoallAlgorithm::Bruck => bruck_exchange(blocks, comm, tag),
+    };
+    *recvbuf = layout::concat(received, count);
+}
+
+/// Send `sendcounts[i]` elements starting at `sdispls[i]` of `sendbuf` to process `i`. The block
+/// from process `i` must have `recvcounts[i]` elements and is placed at `rdispls[i]` of `recvbuf`,
+/// which must already be large enough to hold every block.
+pub fn mpi_alltoallv<T>(sendbuf: &[T],
+                        sendcounts: &[usize],
+                        sdispls: &[usize],
+                        recvbuf: &mut Vec<T>,
+                        recvcounts: &[usize],
+                        rdispls: &[usize],
+                        comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    alltoallv(sendbuf,
+              sendcounts,
+              sdispls,
+              recvbuf,
+              recvcounts,
+              rdispls,
+              comm,
+              next_inter_tag(comm));
+}
+
+/// Start the exchange of `mpi_alltoallv`. `wait` returns `recvbuf` with the received blocks in
+/// place.
+pub fn mpi_ialltoallv<T>(sendbuf: Vec<T>,
+                         sendcounts: Vec<usize>,
+                         sdispls: Vec<usize>,
+                         recvbuf: Vec<T>,
+                         recvcounts: Vec<usize>,
+                         rdispls: Vec<usize>,
+                         comm: MPIComm)
+                         -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = recvbuf;
+        alltoallv(&sendbuf,
+                  &sendcounts,
+                  &sdispls,
+                  &mut recvbuf,
+                  &recvcounts,
+                  &rdispls,
+                  comm,
+                  tag);
+        recvbuf
+    })
+}
+
+fn alltoallv<T>(sendbuf: &[T],
+                sendcounts: &[usize],
+                sdispls: &[usize],
+                recvbuf: &mut Vec<T>,
+                recvcounts: &[usize],
+                rdispls: &[usize],
+                comm: MPIComm,
+                tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = peers(comm);
+    layout::check_layout(sendcounts, sdispls, n);
+    layout::check_layout(recvcounts, rdispls, n);
+
+    let blocks = layout::split(sendbuf, sendcounts, sdispls);
+    layout::place(recvbuf, exchange(blocks, comm, tag), recvcounts, rdispls);
+}
+
+/// Send `sendbufs[i]` to process `i`, where every block may hold a different type. Blocks are
+/// built with `to_block` and read back with `from_block`. `recvbufs` receives the blocks of all
+/// processes in rank order.
+pub fn mpi_alltoallw(sendbufs: &[Block], recvbufs: &mut Vec<Block>, comm: MPIComm) {
+    alltoallw(sendbufs, recvbufs, comm, next_inter_tag(comm));
+}
+
+/// Start sending `sendbufs[i]` to process `i`. `wait` returns the blocks received from all
+/// processes in rank order.
+pub fn mpi_ialltoallw(sendbufs: Vec<Block>, comm: MPIComm) -> CollRequest<Vec<Block>> {
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbufs = Vec::new();
+        alltoallw(&sendbufs, &mut recvbufs, comm, tag);
+        recvbufs
+    })
+}
+
+fn alltoallw(sendbufs: &[Block], recvbufs: &mut Vec<Block>, comm: MPIComm, tag: u64) {
+    let n = peers(comm);
+    if sendbufs.len() != n {
+        panic!("Expected {} send blocks, got {}", n, sendbufs.len());
+    }
+
+    *recvbufs = exchange(sendbufs.to_vec(), comm, tag);
+}
+
+/// Wrap a value of any type in a block for `mpi_alltoallw`
+pub fn to_block<T: Encodable>(buf: &T) -> Block {
+    Block(json::encode(buf).expect("json encode failed!"))
+}
+
+/// Read back a value from a block received by `mpi_alltoallw`
+pub fn from_block<T: Decodable>(block: Block) -> T {
+    json::decode(&block.0).expect("Block holds a different type")
+}
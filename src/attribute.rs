@@ -0,0 +1,292 @@
+//! Attribute caching on communicators
+//!
+//! Libraries attach their own state to a communicator under a keyval. A keyval carries two
+//! callbacks: `copy` decides what a duplicate made by `mpi_comm_dup` inherits, and `delete` runs
+//! when the attribute goes away, be it replaced, deleted or freed together with its communicator.
+//! Callbacks run without any lock held, so they may call back into mpirs.
+//!
+//! `MPI_COMM_WORLD` also carries the predefined attributes `MPI_TAG_UB`, `MPI_UNIVERSE_SIZE` and
+//! `MPI_WTIME_IS_GLOBAL`, which cannot be changed. The universe size is taken from
+//! `MPIRS_UNIVERSE_SIZE` and defaults to the size of `MPI_COMM_WORLD`.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use mpi_comm::{MPIComm, MPI_COMM_WORLD};
+use num_procs::world_size;
+
+type Value = Arc<Any + Send + Sync>;
+
+/// Key under which attributes of type `T` are stored
+pub struct Keyval<T> {
+    id: u64,
+    ty: PhantomData<T>,
+}
+
+impl<T> Clone for Keyval<T> {
+    fn clone(&self) -> Keyval<T> {
+        Keyval {
+            id: self.id,
+            ty: PhantomData,
+        }
+    }
+}
+
+impl<T> Copy for Keyval<T> {}
+
+impl<T> fmt::Debug for Keyval<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Keyval({})", self.id)
+    }
+}
+
+/// Largest tag a message may carry
+pub const MPI_TAG_UB: Keyval<u64> = Keyval {
+    id: 0,
+    ty: PhantomData,
+};
+
+/// Number of processes that can usefully run at the same time
+pub const MPI_UNIVERSE_SIZE: Keyval<usize> = Keyval {
+    id: 1,
+    ty: PhantomData,
+};
+
+/// Whether the clocks of all processes are synchronized. They are: mpirun starts all processes
+/// on its own host.
+pub const MPI_WTIME_IS_GLOBAL: Keyval<bool> = Keyval {
+    id: 2,
+    ty: PhantomData,
+};
+
+const PREDEFINED: u64 = 3;
+
+struct Callbacks {
+    copy: Arc<Fn(MPIComm, &Value) -> Option<Value> + Send + Sync>,
+    delete: Arc<Fn(MPIComm, &Value) + Send + Sync>,
+    freed: bool,
+}
+
+struct Attributes {
+    keyvals: HashMap<u64, Callbacks>,
+    next_keyval: u64,
+    values: HashMap<MPIComm, HashMap<u64, Value>>,
+}
+
+lazy_static! {
+    static ref ATTRIBUTES: Mutex<Attributes> = Mutex::new(Attributes {
+        keyvals: HashMap::new(),
+        next_keyval: PREDEFINED,
+        values: HashMap::new(),
+    });
+}
+
+/// Copy callback that never hands an attribute down to duplicates
+pub fn mpi_comm_null_copy_fn<T>(_: MPIComm, _: &T) -> Option<T> {
+    None
+}
+
+/// Copy callback that gives duplicates a clone of the attribute
+pub fn mpi_comm_dup_fn<T: Clone>(_: MPIComm, value: &T) -> Option<T> {
+    Some(value.clone())
+}
+
+/// Delete callback that does nothing
+pub fn mpi_comm_null_delete_fn<T>(_: MPIComm, _: &T) {}
+
+/// Create a keyval for attributes of type `T`. `copy` is called with the original communicator
+/// and returns the attribute of the duplicate, if any. `delete` is called with the communicator
+/// an attribute is removed from.
+pub fn mpi_comm_create_keyval<T, C, D>(copy: C, delete: D) -> Keyval<T>
+    where T: 'static + Send + Sync,
+          C: 'static + Fn(MPIComm, &T) -> Option<T> + Send + Sync,
+          D: 'static + Fn(MPIComm, &T) + Send + Sync
+{
+    let callbacks = Callbacks {
+        copy: Arc::new(move |comm, value: &Value| {
+            copy(comm, value.downcast_ref().unwrap()).map(|v| Arc::new(v) as Value)
+        }),
+        delete: Arc::new(move |comm, value: &Value| delete(comm, value.downcast_ref().unwrap())),
+        freed: false,
+    };
+
+    let mut attrs = ATTRIBUTES.lock().unwrap();
+    let id = attrs.next_keyval;
+    attrs.next_keyval += 1;
+    attrs.keyvals.insert(id, callbacks);
+    Keyval {
+        id: id,
+        ty: PhantomData,
+    }
+}
+
+/// Release `keyval`. Attributes still stored under it stay until they are deleted, but no new
+/// ones can be set.
+pub fn mpi_comm_free_keyval<T>(keyval: Keyval<T>) {
+    let freed = match ATTRIBUTES.lock().unwrap().keyvals.get_mut(&keyval.id) {
+        Some(ref mut callbacks) if !callbacks.freed => {
+            callbacks.freed = true;
+            true
+        }
+        _ => false,
+    };
+    if !freed {
+        panic!("Invalid keyval {:?}", keyval);
+    }
+}
+
+/// Attach `value` to `comm` under `keyval`, replacing the attribute already stored there
+pub fn mpi_comm_set_attr<T>(comm: MPIComm, keyval: Keyval<T>, value: T)
+    where T: 'static + Send + Sync
+{
+    let delete = match ATTRIBUTES.lock().unwrap().keyvals.get(&keyval.id) {
+        Some(callbacks) if !callbacks.freed => Some(callbacks.delete.clone()),
+        _ => None,
+    };
+    let delete = match delete {
+        Some(delete) => delete,
+        None if keyval.id < PREDEFINED => panic!("Predefined attributes cannot be changed"),
+        None => panic!("Invalid keyval {:?}", keyval),
+    };
+
+    let old = ATTRIBUTES.lock()
+                        .unwrap()
+                        .values
+                        .entry(comm)
+                        .or_insert_with(HashMap::new)
+                        .insert(keyval.id, Arc::new(value));
+    if let Some(old) = old {
+        delete(comm, &old);
+    }
+}
+
+/// Attribute of `comm` stored under `keyval`, `None` if there is none
+pub fn mpi_comm_get_attr<T>(comm: MPIComm, keyval: Keyval<T>) -> Option<Arc<T>>
+    where T: 'static + Send + Sync
+{
+    let value = if keyval.id < PREDEFINED {
+        if comm != MPI_COMM_WORLD {
+            return None;
+        }
+        predefined(keyval.id)
+    } else {
+        let attrs = ATTRIBUTES.lock().unwrap();
+        match attrs.values.get(&comm).and_then(|values| values.get(&keyval.id)) {
+            Some(value) => value.clone(),
+            None => return None,
+        }
+    };
+    Some(value.downcast().unwrap())
+}
+
+/// Remove the attribute of `comm` stored under `keyval` and run its delete callback
+pub fn mpi_comm_delete_attr<T>(comm: MPIComm, keyval: Keyval<T>) {
+    if keyval.id < PREDEFINED {
+        panic!("Predefined attributes cannot be changed");
+    }
+
+    let removed = {
+        let mut attrs = ATTRIBUTES.lock().unwrap();
+        let old = attrs.values.get_mut(&comm).and_then(|values| values.remove(&keyval.id));
+        old.map(|old| (old, attrs.keyvals[&keyval.id].delete.clone()))
+    };
+    match removed {
+        Some((old, delete)) => delete(comm, &old),
+        None => panic!("No attribute for {:?} on communicator {}", keyval, comm),
+    }
+}
+
+fn predefined(id: u64) -> Value {
+    match id {
+        0 => Arc::new(u64::max_value()),
+        1 => {
+            let size = env::var("MPIRS_UNIVERSE_SIZE").ok().and_then(|s| s.parse().ok());
+            Arc::new(size.unwrap_or_else(world_size))
+        }
+        _ => Arc::new(true),
+    }
+}
+
+/// Give `dup` the attributes the copy callbacks of `comm` hand down
+pub(crate) fn copy_attrs(comm: MPIComm, dup: MPIComm) {
+    let copies: Vec<_> = {
+        let attrs = ATTRIBUTES.lock().unwrap();
+        match attrs.values.get(&comm) {
+            Some(values) => {
+                values.iter()
+                      .map(|(id, value)| (*id, value.clone(), attrs.keyvals[id].copy.clone()))
+                      .collect()
+            }
+            None => return,
+        }
+    };
+
+    let copied: HashMap<u64, Value> = copies.into_iter()
+                                            .filter_map(|(id, value, copy)| {
+                                                copy(comm, &value).map(|v| (id, v))
+                                            })
+                                            .collect();
+    ATTRIBUTES.lock().unwrap().values.insert(dup, copied);
+}
+
+/// Remove all attributes of `comm`, which is being freed, and run their delete callbacks
+pub(crate) fn delete_attrs(comm: MPIComm) {
+    let deleted: Vec<_> = {
+        let mut attrs = ATTRIBUTES.lock().unwrap();
+        match attrs.values.remove(&comm) {
+            Some(values) => {
+                values.into_iter()
+                      .map(|(id, value)| (value, attrs.keyvals[&id].delete.clone()))
+                      .collect()
+            }
+            None => return,
+        }
+    };
+
+    for (value, delete) in deleted {
+        delete(comm, &value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn attr_set_get_delete() {
+        let deleted = Arc::new(AtomicUsize::new(0));
+        let counter = deleted.clone();
+        let keyval = mpi_comm_create_keyval(mpi_comm_dup_fn,
+                                            move |_, v: &String| {
+                                                counter.fetch_add(v.len(), Ordering::SeqCst);
+                                            });
+
+        assert!(mpi_comm_get_attr(MPI_COMM_WORLD, keyval).is_none());
+        mpi_comm_set_attr(MPI_COMM_WORLD, keyval, "a".to_string());
+        mpi_comm_set_attr(MPI_COMM_WORLD, keyval, "bb".to_string());
+        assert_eq!(*mpi_comm_get_attr(MPI_COMM_WORLD, keyval).unwrap(), "bb");
+        assert_eq!(deleted.load(Ordering::SeqCst), 1);
+
+        mpi_comm_delete_attr(MPI_COMM_WORLD, keyval);
+        assert!(mpi_comm_get_attr(MPI_COMM_WORLD, keyval).is_none());
+        assert_eq!(deleted.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn attr_set_freed_keyval() {
+        let keyval = mpi_comm_create_keyval(mpi_comm_null_copy_fn, mpi_comm_null_delete_fn);
+        mpi_comm_free_keyval(keyval);
+        mpi_comm_set_attr(MPI_COMM_WORLD, keyval, 1u32);
+    }
+
+    #[test]
+    fn attr_predefined() {
+        assert_eq!(*mpi_comm_get_attr(MPI_COMM_WORLD, MPI_TAG_UB).unwrap(), u64::max_value());
+        assert_eq!(*mpi_comm_get_attr(MPI_COMM_WORLD, MPI_WTIME_IS_GLOBAL).unwrap(), true);
+    }
+}
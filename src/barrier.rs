@@ -1,26 +1,107 @@
-//! Implements mpi_barrier
+//! Implements mpi_barrier and mpi_ibarrier
+//!
+//! Every barrier is identified by its communicator and an epoch, the sequence number of the
+//! collective on that communicator. A process that already entered the next barrier therefore
+//! never counts towards the current one.
+//!
+//! Small jobs let mpirun count the members of the communicator as they enter. Large jobs use a
+//! dissemination barrier instead: in round `k` every process signals `rank + 2^k` and waits for
+//! `rank - 2^k`, so all processes are released after log(n) rounds without a central counter.
+//!
+//! On an intercommunicator each group synchronizes on its own, then the leaders swap a signal
+//! and release their groups.
 use rustc_serialize::json;
 use comm_request::CommRequest;
 use comm_request::CommRequestType;
 use comm_request::ControlTy;
-use std::io::prelude::*;
+use comm_request::RequestProc;
+use mpi_comm::{MPIComm, coll_context, remote_coll_context, members, next_inter_tag,
+               mpi_comm_test_inter, spans_jobs};
+use bcast::bcast;
+use mpi_request::CollRequest;
+use sendrecv::sendrecv;
+use comm_rank::mpi_comm_rank;
+use num_procs::mpi_get_num_procs;
+use algorithm::{self, BarrierAlgorithm};
 use std::net::TcpStream;
 use utils;
 
-pub fn mpi_barrier() {
+/// Block until all processes of `comm` entered the barrier
+pub fn mpi_barrier(comm: MPIComm) {
+    barrier(comm, next_inter_tag(comm));
+}
+
+/// Enter a barrier without waiting for the others. `wait` returns once all processes of `comm`
+/// entered it.
+pub fn mpi_ibarrier(comm: MPIComm) -> CollRequest<()> {
+    let epoch = next_inter_tag(comm);
+    CollRequest::spawn(move || barrier(comm, epoch))
+}
+
+fn barrier(comm: MPIComm, epoch: u64) {
+    if mpi_comm_test_inter(comm) {
+        return inter_barrier(comm, epoch);
+    }
+    match algorithm::barrier(mpi_get_num_procs(comm)) {
+        // mpirun only counts the processes of its own job
+        BarrierAlgorithm::Central if !spans_jobs(comm) => central_barrier(comm, epoch),
+        _ => dissemination_barrier(comm, epoch),
+    }
+}
+
+fn central_barrier(comm: MPIComm, epoch: u64) {
+    // mpirun releases the barrier once every member has entered it
+    let members = members(comm).to_vec();
+
     let pid = utils::pid();
-    let tag = u64::max_value();
-    let commreq = CommRequest::<u32>::new(None,
-                                          None,
-                                          tag,
-                                          None,
-                                          CommRequestType::Control(ControlTy::Barrier),
-                                          pid);
+    let mut commreq = CommRequest::<Vec<usize>>::new(None,
+                                                     None,
+                                                     epoch,
+                                                     Some(members),
+                                                     CommRequestType::Control(ControlTy::Barrier),
+                                                     pid);
+    commreq.set_context(coll_context(comm).id());
 
     let commreq_json = json::encode(&commreq).expect("Cannot encode to json");
-    let mut stream = TcpStream::connect("127.0.0.1:31337").unwrap();
-    let _ = stream.write(&commreq_json.as_bytes());
+    let mut stream = TcpStream::connect(utils::mpirun_addr()).unwrap();
+    utils::write_stream(&mut stream, &commreq_json);
 
     // Discard the ACK
     let _ = utils::read_stream(&mut stream);
 }
+
+fn dissemination_barrier(comm: MPIComm, epoch: u64) {
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+
+    let mut d = 1;
+    while d < n {
+        let mut signal = false;
+        sendrecv(&true,
+                 RequestProc::Process((rank + d) % n),
+                 epoch,
+                 &mut signal,
+                 RequestProc::Process((rank + n - d) % n),
+                 epoch,
+                 coll_context(comm));
+        d <<= 1;
+    }
+}
+
+fn inter_barrier(comm: MPIComm, epoch: u64) {
+    // Central barriers of both groups would share the same context and epoch in mpirun
+    dissemination_barrier(comm, epoch);
+
+    let mut signal = false;
+    if mpi_comm_rank(comm) == 0 {
+        let leader = RequestProc::Process(0);
+        sendrecv(&true,
+                 leader,
+                 epoch,
+                 &mut signal,
+                 leader,
+                 epoch,
+                 remote_coll_context(comm));
+    }
+    bcast(&mut signal, 0, comm, epoch);
+}
@@ -1,24 +1,236 @@
+//! Implements mpi_bcast and mpi_bcast_vec
+//!
+//! Broadcasts run over a binomial tree rooted at `root`: every process receives from exactly one
+//! parent and forwards to its children, so the broadcast completes in log(n) rounds and every
+//! message goes to an explicit peer.
+//!
+//! Vectors can also be pipelined down a chain of processes in segments, or split in n chunks that
+//! are scattered down the same tree and then gathered on every process around a ring. Both keep
+//! the root from sending the whole vector more than once. `algorithm` decides which one to use.
+//!
+//! On an intercommunicator the root sends the buffer to the leader of the other group, which
+//! broadcasts it within that group.
+
 use rustc_serialize::Decodable;
-use mpi_comm::MPIComm;
-use comm_request::RequestProc;
+use mpi_comm::{MPIComm, InterRole, coll_context, remote_coll_context, next_inter_tag,
+               inter_role, mpi_comm_test_inter};
+use mpi_request::CollRequest;
+use comm_request::{CommRequest, RequestProc};
 use std::fmt::Debug;
+use std::sync::mpsc::Receiver;
 use rustc_serialize::Encodable;
-use send::mpi_send;
-use receive::mpi_recv;
+use send::{send, isend};
+use receive::recv;
+use receiver_traits::Message;
+use allgather::ring_allgather;
 use comm_rank::mpi_comm_rank;
 use num_procs::mpi_get_num_procs;
+use algorithm::{self, BcastAlgorithm};
+use std::cmp;
 
 // Functions in the Broadcast module
 pub fn mpi_bcast<T>(buf: &mut T, root: usize, comm: MPIComm)
     where T: 'static + Debug + Clone + Encodable + Decodable + Send
 {
-    let n = mpi_get_num_procs();;
-    let tag = u64::max_value();
-    if mpi_comm_rank() == root {
-        for _ in 0..n - 1 {
-            mpi_send(buf, RequestProc::Any, tag, comm);
+    let tag = next_inter_tag(comm);
+    if mpi_comm_test_inter(comm) {
+        inter_bcast(buf, root, comm, tag);
+    } else {
+        bcast(buf, root, comm, tag);
+    }
+}
+
+/// Start broadcasting `buf` of `root`. `wait` returns the broadcast value on every process.
+pub fn mpi_ibcast<T>(buf: T, root: usize, comm: MPIComm) -> CollRequest<T>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut buf = buf;
+        if mpi_comm_test_inter(comm) {
+            inter_bcast(&mut buf, root, comm, tag);
+        } else {
+            bcast(&mut buf, root, comm, tag);
+        }
+        buf
+    })
+}
+
+pub(crate) fn bcast<T>(buf: &mut T, root: usize, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+    let vrank = (rank + n - root) % n;
+
+    let mut mask = 1;
+    while mask < n {
+        if vrank & mask != 0 {
+            let parent = (vrank - mask + root) % n;
+            recv(buf, RequestProc::Process(parent), tag, coll_context(comm));
+            break;
+        }
+        mask <<= 1;
+    }
+
+    mask >>= 1;
+    while mask > 0 {
+        if vrank + mask < n {
+            let child = (vrank + mask + root) % n;
+            send(buf, RequestProc::Process(child), tag, coll_context(comm));
+        }
+        mask >>= 1;
+    }
+}
+
+/// Broadcast `buf` from `root` to the leader of the other group of intercommunicator `comm`,
+/// which passes it on to its group
+fn inter_bcast<T>(buf: &mut T, root: usize, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    match inter_role(root, comm) {
+        InterRole::Root => send(buf, RequestProc::Process(0), tag, remote_coll_context(comm)),
+        InterRole::Idle => {}
+        InterRole::Remote(root) => {
+            if mpi_comm_rank(comm) == 0 {
+                recv(buf, RequestProc::Process(root), tag, remote_coll_context(comm));
+            }
+            bcast(buf, 0, comm, tag);
+        }
+    }
+}
+
+/// Broadcast the `count` elements of `buf` on `root` to all processes. `count` must be the same
+/// on every process.
+pub fn mpi_bcast_vec<T>(buf: &mut Vec<T>, count: usize, root: usize, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    bcast_vec(buf, count, root, comm, next_inter_tag(comm));
+}
+
+/// Start broadcasting the `count` elements of `buf` of `root`. `wait` returns the broadcast
+/// vector on every process.
+pub fn mpi_ibcast_vec<T>(buf: Vec<T>,
+                         count: usize,
+                         root: usize,
+                         comm: MPIComm)
+                         -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut buf = buf;
+        bcast_vec(&mut buf, count, root, comm, tag);
+        buf
+    })
+}
+
+fn bcast_vec<T>(buf: &mut Vec<T>, count: usize, root: usize, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if mpi_comm_test_inter(comm) {
+        return inter_bcast(buf, root, comm, tag);
+    }
+
+    match algorithm::bcast(mpi_get_num_procs(comm), count) {
+        BcastAlgorithm::Binomial => bcast(buf, root, comm, tag),
+        BcastAlgorithm::Pipelined => bcast_pipelined(buf, count, root, comm, tag),
+        BcastAlgorithm::ScatterAllgather => bcast_scatter_allgather(buf, count, root, comm, tag),
+    }
+}
+
+fn bcast_pipelined<T>(buf: &mut Vec<T>, count: usize, root: usize, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+    let vrank = (rank + n - root) % n;
+    let prev = RequestProc::Process((rank + n - 1) % n);
+    let next = RequestProc::Process((rank + 1) % n);
+    let segment = algorithm::bcast_segment();
+
+    if vrank == 0 {
+        if buf.len() != count {
+            panic!("Broadcast buffer holds {} elements, expected {}", buf.len(), count);
         }
     } else {
-        mpi_recv(buf, RequestProc::Process(root), tag, comm);
+        buf.clear();
+    }
+
+    // Forwarding a segment overlaps with receiving the next one. Only one segment is in flight
+    // to the next process at a time, so segments arrive in order.
+    let mut in_flight: Option<Receiver<CommRequest<Vec<T>>>> = None;
+    let mut start = 0;
+    while start < count {
+        let end = cmp::min(start + segment, count);
+        let seg = if vrank == 0 {
+            buf[start..end].to_vec()
+        } else {
+            let mut seg: Vec<T> = Vec::new();
+            recv(&mut seg, prev, tag, coll_context(comm));
+            buf.extend_from_slice(&seg);
+            seg
+        };
+
+        if vrank + 1 < n {
+            if let Some(rx) = in_flight.take() {
+                rx.wait();
+            }
+            in_flight = Some(isend(&seg, next, tag, coll_context(comm)));
+        }
+        start = end;
+    }
+
+    if let Some(rx) = in_flight {
+        rx.wait();
+    }
+}
+
+fn bcast_scatter_allgather<T>(buf: &mut Vec<T>,
+                              count: usize,
+                              root: usize,
+                              comm: MPIComm,
+                              tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+    let vrank = (rank + n - root) % n;
+
+    // Chunks this process is responsible for, starting with its own chunk `vrank`
+    let mut held: Vec<Vec<T>> = Vec::new();
+    if vrank == 0 {
+        if buf.len() != count {
+            panic!("Broadcast buffer holds {} elements, expected {}", buf.len(), count);
+        }
+        held = (0..n).map(|v| buf[v * count / n..(v + 1) * count / n].to_vec()).collect();
+    }
+
+    let mut mask = 1;
+    while mask < n {
+        if vrank & mask != 0 {
+            let parent = (vrank - mask + root) % n;
+            recv(&mut held, RequestProc::Process(parent), tag, coll_context(comm));
+            break;
+        }
+        mask <<= 1;
+    }
+
+    mask >>= 1;
+    while mask > 0 {
+        if vrank + mask < n {
+            let child = (vrank + mask + root) % n;
+            let theirs = held.split_off(mask);
+            send(&theirs, RequestProc::Process(child), tag, coll_context(comm));
+        }
+        mask >>= 1;
+    }
+
+    let mine = held.into_iter().next().expect("No chunk received in broadcast");
+    let chunks = ring_allgather(mine, comm, tag);
+
+    buf.clear();
+    for v in 0..n {
+        buf.extend_from_slice(&chunks[(v + root) % n]);
     }
 }
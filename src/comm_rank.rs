@@ -3,11 +3,17 @@ use libc;
 use comm_request::CommRequest;
 use comm_request::CommRequestType;
 use comm_request::ControlTy;
-use std::io::prelude::*;
 use std::net::TcpStream;
+use mpi_comm::{self, MPIComm};
 use utils;
 
-pub fn mpi_comm_rank() -> usize {
+/// Rank of the calling process in `comm`
+pub fn mpi_comm_rank(comm: MPIComm) -> usize {
+    mpi_comm::rank(comm)
+}
+
+/// World rank of the calling process, its number among all processes of the run
+pub(crate) fn world_rank() -> usize {
     let pid = utils::pid();
     let tag: u64 = u64::max_value();
     let mut rank: Option<usize> = None;
@@ -19,10 +25,10 @@ pub fn mpi_comm_rank() -> usize {
                                           pid);
     let commreq_json = json::encode(&commreq).unwrap();
 
-    let mut stream = TcpStream::connect("127.0.0.1:31337").unwrap();
-    let _ = stream.write(&commreq_json.as_bytes());
+    let mut stream = TcpStream::connect(utils::mpirun_addr()).unwrap();
+    utils::write_stream(&mut stream, &commreq_json);
 
-    let mut str_in = utils::read_stream(&mut stream);
+    let str_in = utils::read_stream(&mut stream);
     if !str_in.is_empty() {
         rank = usize::from_str_radix(&str_in, 10).ok();
     }
@@ -6,6 +6,8 @@
 use rustc_serialize::{Encodable, Decodable, json};
 use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::sync::mpsc::{channel, Receiver};
+use utils;
 
 /// Differentiate between communications and simlpe requests
 #[derive(Debug, Copy, Clone, RustcEncodable, RustcDecodable)]
@@ -34,6 +36,76 @@ pub enum ControlTy {
     Exit,
     Nop,
     Barrier,
+    /// Reserve unused context ids for new communicators
+    AllocContext,
+    /// Tell mpirun the name of a communicator, given by its context id
+    SetName,
+    /// Get the `World` of the process
+    GetWorld,
+    /// Start new processes, described by `SpawnCommand`s
+    Spawn,
+    /// Open a port other jobs can connect to
+    OpenPort,
+    ClosePort,
+    /// Wait for a job to connect to a port of this mpirun
+    Accept,
+    /// Connect to a port of another mpirun
+    Connect,
+    /// Sent by an mpirun to the mpirun owning a port, on behalf of a `Connect`
+    Join,
+    /// Publish a port under a service name
+    Publish,
+    Lookup,
+    Unpublish,
+    /// Expose memory in an RMA window
+    WinCreate,
+    WinFree,
+    Put,
+    Get,
+    /// Fetch elements of a window for an atomic update, which `Store` completes
+    Fetch,
+    Store,
+    /// Lock the memory of a process in a window, replied to once the lock is granted
+    Lock,
+    Unlock,
+}
+
+/// Reply of mpirun to the requests on RMA windows
+#[derive(Debug, Clone, RustcEncodable, RustcDecodable)]
+pub enum RmaReply {
+    /// The elements read, as JSON
+    Done(Vec<String>),
+    Failed(String),
+}
+
+/// World ranks of processes in other jobs, connected through `port`, carry the port of their
+/// mpirun above the lowest `RANK_BITS` bits
+pub const RANK_BITS: usize = 32;
+
+// Qualified world ranks need room for the port above `RANK_BITS` bits
+#[cfg(not(target_pointer_width = "64"))]
+compile_error!("mpirs needs a 64-bit usize to qualify the world ranks of connected jobs");
+
+/// The processes started together with a process, which form its `MPI_COMM_WORLD`
+#[derive(Debug, Clone, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub struct World {
+    /// Process numbers of the members, in rank order
+    pub members: Vec<usize>,
+    /// Context id of `MPI_COMM_WORLD`
+    pub context: u64,
+    /// For spawned processes, the group that spawned them and the context id of the
+    /// intercommunicator between the two
+    pub parent: Option<(Vec<usize>, u64)>,
+}
+
+/// Processes to start with `ControlTy::Spawn`
+#[derive(Debug, Clone, RustcEncodable, RustcDecodable)]
+pub struct SpawnCommand {
+    pub command: String,
+    pub args: Vec<String>,
+    pub maxprocs: usize,
+    /// Working directory of the new processes, that of mpirun if `None`
+    pub wdir: Option<String>,
 }
 
 #[derive(Debug, Copy, Clone, RustcEncodable, RustcDecodable, PartialEq, Eq, Hash)]
@@ -42,6 +114,9 @@ pub enum RequestProc {
     Process(usize),
     /// No specific process
     Any,
+    /// No process at all, such as the missing neighbour at the edge of a grid. Sends and
+    /// receives on it complete at once without data and never reach mpirun.
+    ProcNull,
 }
 
 #[derive(Debug, Clone, RustcEncodable, RustcDecodable)]
@@ -51,6 +126,9 @@ pub struct CommRequest<T: Debug + Clone + Encodable + Decodable> {
     dest: Option<RequestProc>,
     /// Message Tag
     tag: u64,
+    /// Matching context. Set by mpirs, never by the user: messages only match within the same
+    /// context, which keeps point-to-point and collective traffic of every communicator apart.
+    context: u64,
     pty: PhantomData<T>,
     /// Actual data to be sent
     data: Option<String>,
@@ -77,6 +155,7 @@ impl<T: Debug + Clone + Encodable + Decodable> CommRequest<T> {
             src: src,
             dest: dest,
             tag: tag,
+            context: 0,
             data: json_data,
             pty: PhantomData,
             req_ty: ty,
@@ -96,6 +175,10 @@ impl<T: Debug + Clone + Encodable + Decodable> CommRequest<T> {
         self.tag
     }
 
+    pub fn context(&self) -> u64 {
+        self.context
+    }
+
     pub fn data(&self) -> Option<String> {
         self.data.clone()
     }
@@ -116,6 +199,10 @@ impl<T: Debug + Clone + Encodable + Decodable> CommRequest<T> {
         self.dest = Some(dst)
     }
 
+    pub fn set_context(&mut self, context: u64) {
+        self.context = context
+    }
+
     pub fn is_send(&self) -> bool {
         if let CommRequestType::Message(MType::MSend) = self.req_ty {
             true
@@ -169,7 +256,58 @@ pub trait Extract {
 impl<T: Clone + Debug + Encodable + Decodable> Extract for CommRequest<T> {
     type DType = T;
     fn data(&self) -> Option<Self::DType> {
-        let x: Option<T> = json::decode(self.data.as_ref().unwrap()).ok();
+        let x: Option<T> = self.data.as_ref().and_then(|data| json::decode(data).ok());
         x
     }
 }
+
+/// Handle to `req`, already completed
+fn completed<T>(req: CommRequest<T>) -> Receiver<CommRequest<T>>
+    where T: Debug + Clone + Encodable + Decodable
+{
+    let (tx, rx) = channel();
+    let _ = tx.send(req);
+    rx
+}
+
+/// Handle to the empty status of a send or receive of type `ty` on `RequestProc::ProcNull`,
+/// already completed: ProcNull as the peer, tag 0 and no data
+pub(crate) fn proc_null<T>(ty: MType) -> Receiver<CommRequest<T>>
+    where T: Debug + Clone + Encodable + Decodable
+{
+    let (src, dest) = match ty {
+        MType::MSend => (None, Some(RequestProc::ProcNull)),
+        MType::MRecv => (Some(RequestProc::ProcNull), None),
+    };
+    completed(CommRequest::new(src, dest, 0, None, CommRequestType::Message(ty), utils::pid()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn proc_null_completes_at_once() {
+        let rx = proc_null::<u32>(MType::MSend);
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn proc_null_recv_status_is_empty() {
+        let status = proc_null::<Vec<u32>>(MType::MRecv).recv().unwrap();
+        assert!(status.is_recv());
+        assert_eq!(status.src(), Some(RequestProc::ProcNull));
+        assert_eq!(status.dst(), None);
+        assert_eq!(status.tag(), 0);
+        assert_eq!(Extract::data(&status), None);
+    }
+
+    #[test]
+    fn proc_null_send_status_names_dest() {
+        let status = proc_null::<Vec<u32>>(MType::MSend).recv().unwrap();
+        assert!(status.is_send());
+        assert_eq!(status.src(), None);
+        assert_eq!(status.dst(), Some(RequestProc::ProcNull));
+        assert_eq!(Extract::data(&status), None);
+    }
+}
@@ -2,7 +2,6 @@ use rustc_serialize::json;
 use comm_request::CommRequest;
 use comm_request::CommRequestType;
 use comm_request::ControlTy;
-use std::io::prelude::*;
 use std::net::TcpStream;
 use utils;
 
@@ -17,6 +16,6 @@ pub fn mpi_finalize() {
                                           pid);
     let commreq_json = json::encode(&commreq).unwrap();
 
-    let mut stream = TcpStream::connect("127.0.0.1:31337").unwrap();
-    let _ = stream.write(&commreq_json.as_bytes());
+    let mut stream = TcpStream::connect(utils::mpirun_addr()).unwrap();
+    utils::write_stream(&mut stream, &commreq_json);
 }
@@ -1,43 +1,198 @@
-use rustc_serialize::json;
+//! Implements mpi_gather and mpi_gatherv
+//!
+//! The root receives the block of every other process directly, in rank order, and copies its
+//! own block locally.
+//!
+//! On an intercommunicator the root gathers the blocks of the other group: that group gathers
+//! them to its leader, which sends them all to the root at once.
+
 use rustc_serialize::Decodable;
-use mpi_comm::MPIComm;
+use mpi_comm::{MPIComm, InterRole, coll_context, remote_coll_context, next_inter_tag,
+               inter_role, mpi_comm_test_inter};
+use mpi_request::CollRequest;
 use comm_request::RequestProc;
 use std::fmt::Debug;
 use rustc_serialize::Encodable;
-use send::mpi_send;
-use receive::mpi_recv;
+use send::send;
+use receive::recv;
 use comm_rank::mpi_comm_rank;
 use num_procs::mpi_get_num_procs;
-use std::u64;
-
-// Functions in the Gather module
-pub fn mpi_gatherv<T>(sendbuf: &mut T, recvbuf: &mut Vec<T>, 
-			 recvcount: Vec<usize>, displs: Vec<usize>, root: usize, comm: MPIComm) 
-			 where T: 'static + Debug + Clone + Encodable + Decodable + Send {
-
-		let n = mpi_get_num_procs();
-		let tag = u64::MAX;
-		if mpi_comm_rank() == root {
-
-			// find total recv buffer size
-			let count = recvcount.iter().fold(0, |mut sum, &x| {sum += x; sum});
-			recvbuf.reserve(count);
-
-			for i in 0..n {
-					let mut buf_str: String = String::new();
-			    mpi_recv(&mut buf_str, RequestProc::Process(i), tag, comm);
-			    let buf: Vec<T> = json::decode(&buf_str).unwrap();
-
-			    if buf.len() != recvcount[i] {
-			    	panic!("Received more than specified buffer size");
-			    }
-
-			    for j in 0..buf.len() {
-			    	recvbuf[displs[i]+j] = buf[j].clone();
-			    }
-			}
-		}
-		
-		mpi_send(sendbuf, RequestProc::Process(root), tag, comm);
-		
+use layout;
+
+/// Gather `sendbuf` of every process, all of the same size, into `recvbuf` of `root` in rank
+/// order. `recvbuf` is left untouched on all other processes.
+pub fn mpi_gather<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, root: usize, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    gather(sendbuf, recvbuf, root, comm, next_inter_tag(comm));
+}
+
+/// Start gathering `sendbuf` of every process to `root`. `wait` returns the gathered blocks on
+/// `root` and an empty vector on all other processes.
+pub fn mpi_igather<T>(sendbuf: Vec<T>, root: usize, comm: MPIComm) -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = Vec::new();
+        gather(&sendbuf, &mut recvbuf, root, comm, tag);
+        recvbuf
+    })
+}
+
+/// Gather `sendbuf` of every process into `recvbuf` of `root`. The block of process `i` must have
+/// `recvcounts[i]` elements and is placed at offset `displs[i]`. `recvbuf` must already be large
+/// enough to hold every block. `recvbuf`, `recvcounts` and `displs` are only used on `root`.
+pub fn mpi_gatherv<T>(sendbuf: &[T],
+                      recvbuf: &mut Vec<T>,
+                      recvcounts: &[usize],
+                      displs: &[usize],
+                      root: usize,
+                      comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    gatherv(sendbuf, recvbuf, recvcounts, displs, root, comm, next_inter_tag(comm));
+}
+
+/// Start gathering `sendbuf` of every process into `recvbuf` of `root`. `wait` returns `recvbuf`
+/// with the blocks placed as in `mpi_gatherv`.
+pub fn mpi_igatherv<T>(sendbuf: Vec<T>,
+                       recvbuf: Vec<T>,
+                       recvcounts: Vec<usize>,
+                       displs: Vec<usize>,
+                       root: usize,
+                       comm: MPIComm)
+                       -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = recvbuf;
+        gatherv(&sendbuf, &mut recvbuf, &recvcounts, &displs, root, comm, tag);
+        recvbuf
+    })
+}
+
+fn gather<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, root: usize, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if mpi_comm_test_inter(comm) {
+        // The root sends no block, all of them come from the other group
+        if let Some(blocks) = inter_gather_blocks(sendbuf, root, comm, tag) {
+            let count = blocks.first().map_or(0, |b| b.len());
+            *recvbuf = layout::concat(blocks, count);
+        }
+        return;
+    }
+
+    if let Some(blocks) = gather_blocks(sendbuf, root, comm, tag) {
+        *recvbuf = layout::concat(blocks, sendbuf.len());
+    }
+}
+
+fn gatherv<T>(sendbuf: &[T],
+              recvbuf: &mut Vec<T>,
+              recvcounts: &[usize],
+              displs: &[usize],
+              root: usize,
+              comm: MPIComm,
+              tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if mpi_comm_test_inter(comm) {
+        if let Some(blocks) = inter_gather_blocks(sendbuf, root, comm, tag) {
+            layout::check_layout(recvcounts, displs, blocks.len());
+            layout::place(recvbuf, blocks, recvcounts, displs);
+        }
+        return;
+    }
+
+    if mpi_comm_rank(comm) == root {
+        layout::check_layout(recvcounts, displs, mpi_get_num_procs(comm));
+    }
+
+    if let Some(blocks) = gather_blocks(sendbuf, root, comm, tag) {
+        layout::place(recvbuf, blocks, recvcounts, displs);
+    }
+}
+
+/// Returns the blocks of all processes, indexed by rank, on `root` and `None` elsewhere
+fn gather_blocks<T>(sendbuf: &[T], root: usize, comm: MPIComm, tag: u64) -> Option<Vec<Vec<T>>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if mpi_comm_rank(comm) != root {
+        send(&sendbuf.to_vec(), RequestProc::Process(root), tag, coll_context(comm));
+        return None;
+    }
+
+    Some(collect(sendbuf, root, mpi_get_num_procs(comm), |i| {
+        let mut block: Vec<T> = Vec::new();
+        recv(&mut block, RequestProc::Process(i), tag, coll_context(comm));
+        block
+    }))
+}
+
+/// Returns the blocks of the other group of intercommunicator `comm`, indexed by rank, on `root`
+/// and `None` elsewhere
+fn inter_gather_blocks<T>(sendbuf: &[T],
+                          root: usize,
+                          comm: MPIComm,
+                          tag: u64)
+                          -> Option<Vec<Vec<T>>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    match inter_role(root, comm) {
+        InterRole::Root => {
+            let mut blocks: Vec<Vec<T>> = Vec::new();
+            recv(&mut blocks, RequestProc::Process(0), tag, remote_coll_context(comm));
+            Some(blocks)
+        }
+        InterRole::Idle => None,
+        InterRole::Remote(root) => {
+            if let Some(blocks) = gather_blocks(sendbuf, 0, comm, tag) {
+                send(&blocks, RequestProc::Process(root), tag, remote_coll_context(comm));
+            }
+            None
+        }
+    }
+}
+
+/// Take the block of every process `i` but `root` from `recv_from`, in rank order, and return all
+/// n blocks with `sendbuf` as the one of `root`
+fn collect<T, F>(sendbuf: &[T], root: usize, n: usize, mut recv_from: F) -> Vec<Vec<T>>
+    where T: Clone,
+          F: FnMut(usize) -> Vec<T>
+{
+    (0..n).map(|i| if i == root { sendbuf.to_vec() } else { recv_from(i) }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gather_root_block_in_place() {
+        let mut order = Vec::new();
+        let blocks = collect(&[20, 21], 2, 4, |i| {
+            order.push(i);
+            vec![10 * i, 10 * i + 1]
+        });
+        assert_eq!(order, vec![0, 1, 3]);
+        assert_eq!(layout::concat(blocks, 2), vec![0, 1, 10, 11, 20, 21, 30, 31]);
+    }
+
+    #[test]
+    fn gatherv_uneven_counts() {
+        let counts = [1, 3, 0, 2];
+        let displs = [5, 0, 3, 3];
+        layout::check_layout(&counts, &displs, 4);
+        let blocks = collect(&['b', 'c', 'd'], 1, 4, |i| match i {
+            0 => vec!['f'],
+            3 => vec!['x', 'y'],
+            _ => Vec::new(),
+        });
+        let mut recvbuf = vec![' '; 6];
+        layout::place(&mut recvbuf, blocks, &counts, &displs);
+        assert_eq!(recvbuf, vec!['b', 'c', 'd', 'x', 'y', 'f']);
+    }
 }
@@ -0,0 +1,204 @@
+//! Process groups
+//!
+//! A group is an ordered set of processes. Groups are local objects: building and querying them
+//! never involves other processes.
+
+use mpi_comm::{self, MPIComm, MPI_COMM_WORLD};
+
+/// Result of `mpi_group_compare`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupCompare {
+    /// Same processes in the same order
+    Ident,
+    /// Same processes in a different order
+    Similar,
+    Unequal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    /// World ranks of the members, indexed by their rank in the group
+    ranks: Vec<usize>,
+}
+
+impl Group {
+    pub(crate) fn world_ranks(&self) -> &[usize] {
+        &self.ranks
+    }
+}
+
+/// Group of the processes of `comm`, in rank order
+pub fn mpi_comm_group(comm: MPIComm) -> Group {
+    Group { ranks: mpi_comm::members(comm).to_vec() }
+}
+
+/// Remote group of intercommunicator `comm`, in rank order
+pub fn mpi_comm_remote_group(comm: MPIComm) -> Group {
+    Group { ranks: mpi_comm::remote_members(comm).to_vec() }
+}
+
+pub fn mpi_group_size(group: &Group) -> usize {
+    group.ranks.len()
+}
+
+/// Rank of the calling process in `group`, `None` if it is not a member
+pub fn mpi_group_rank(group: &Group) -> Option<usize> {
+    let me = mpi_comm::members(MPI_COMM_WORLD)[mpi_comm::rank(MPI_COMM_WORLD)];
+    group.ranks.iter().position(|&r| r == me)
+}
+
+/// Group of the processes with the given ranks in `group`, in that order
+pub fn mpi_group_incl(group: &Group, ranks: &[usize]) -> Group {
+    let mut members = Vec::with_capacity(ranks.len());
+    for &r in ranks {
+        if r >= group.ranks.len() {
+            panic!("Rank {} out of range for a group of {}", r, group.ranks.len());
+        }
+        if members.contains(&group.ranks[r]) {
+            panic!("Rank {} included twice", r);
+        }
+        members.push(group.ranks[r]);
+    }
+    Group { ranks: members }
+}
+
+/// Group of the processes of `group` without the given ranks, in their order in `group`
+pub fn mpi_group_excl(group: &Group, ranks: &[usize]) -> Group {
+    for &r in ranks {
+        if r >= group.ranks.len() {
+            panic!("Rank {} out of range for a group of {}", r, group.ranks.len());
+        }
+    }
+
+    let kept = (0..group.ranks.len()).filter(|r| !ranks.contains(r));
+    Group { ranks: kept.map(|r| group.ranks[r]).collect() }
+}
+
+/// Same as `mpi_group_incl` with the ranks given as `(first, last, stride)` triplets. Each triplet
+/// includes `first`, `first + stride`, ... up to and including `last`, and may count down with a
+/// negative stride.
+pub fn mpi_group_range_incl(group: &Group, ranges: &[(usize, usize, isize)]) -> Group {
+    let mut ranks = Vec::new();
+    for &(first, last, stride) in ranges {
+        if stride == 0 || (stride > 0 && first > last) || (stride < 0 && first < last) {
+            panic!("Invalid rank range ({}, {}, {})", first, last, stride);
+        }
+
+        let mut r = first as isize;
+        while (stride > 0 && r <= last as isize) || (stride < 0 && r >= last as isize) {
+            ranks.push(r as usize);
+            r += stride;
+        }
+    }
+    mpi_group_incl(group, &ranks)
+}
+
+/// Processes of `group1` followed by those of `group2` that are not in `group1`
+pub fn mpi_group_union(group1: &Group, group2: &Group) -> Group {
+    let mut ranks = group1.ranks.clone();
+    ranks.extend(group2.ranks.iter().filter(|r| !group1.ranks.contains(r)));
+    Group { ranks: ranks }
+}
+
+/// Processes of `group1` that are also in `group2`, in their order in `group1`
+pub fn mpi_group_intersection(group1: &Group, group2: &Group) -> Group {
+    Group { ranks: group1.ranks.iter().cloned().filter(|r| group2.ranks.contains(r)).collect() }
+}
+
+/// Processes of `group1` that are not in `group2`, in their order in `group1`
+pub fn mpi_group_difference(group1: &Group, group2: &Group) -> Group {
+    Group { ranks: group1.ranks.iter().cloned().filter(|r| !group2.ranks.contains(r)).collect() }
+}
+
+/// Rank in `group2` of each process with the given rank in `group1`, `None` for processes that
+/// are not in `group2`
+pub fn mpi_group_translate_ranks(group1: &Group,
+                                 ranks: &[usize],
+                                 group2: &Group)
+                                 -> Vec<Option<usize>> {
+    ranks.iter()
+         .map(|&r| {
+             let world = match group1.ranks.get(r) {
+                 Some(w) => w,
+                 None => panic!("Rank {} out of range for a group of {}", r, group1.ranks.len()),
+             };
+             group2.ranks.iter().position(|w2| w2 == world)
+         })
+         .collect()
+}
+
+pub fn mpi_group_compare(group1: &Group, group2: &Group) -> GroupCompare {
+    if group1.ranks == group2.ranks {
+        return GroupCompare::Ident;
+    }
+
+    let mut sorted1 = group1.ranks.clone();
+    let mut sorted2 = group2.ranks.clone();
+    sorted1.sort();
+    sorted2.sort();
+    if sorted1 == sorted2 {
+        GroupCompare::Similar
+    } else {
+        GroupCompare::Unequal
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn group(ranks: &[usize]) -> Group {
+        Group { ranks: ranks.to_vec() }
+    }
+
+    #[test]
+    fn group_incl_excl() {
+        let g = group(&[10, 11, 12, 13, 14]);
+        assert_eq!(mpi_group_incl(&g, &[3, 0]), group(&[13, 10]));
+        assert_eq!(mpi_group_excl(&g, &[3, 0]), group(&[11, 12, 14]));
+        assert_eq!(mpi_group_size(&mpi_group_excl(&g, &[0, 1, 2, 3, 4])), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn group_incl_twice() {
+        mpi_group_incl(&group(&[0, 1, 2]), &[1, 1]);
+    }
+
+    #[test]
+    fn group_range_incl() {
+        let g = group(&[0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(mpi_group_range_incl(&g, &[(0, 6, 3), (7, 4, -2)]),
+                   group(&[0, 3, 6, 7, 5]));
+        assert_eq!(mpi_group_range_incl(&g, &[(2, 2, 1)]), group(&[2]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn group_range_wrong_direction() {
+        mpi_group_range_incl(&group(&[0, 1, 2]), &[(2, 0, 1)]);
+    }
+
+    #[test]
+    fn group_set_operations() {
+        let a = group(&[4, 1, 3]);
+        let b = group(&[3, 0, 4]);
+        assert_eq!(mpi_group_union(&a, &b), group(&[4, 1, 3, 0]));
+        assert_eq!(mpi_group_intersection(&a, &b), group(&[4, 3]));
+        assert_eq!(mpi_group_difference(&a, &b), group(&[1]));
+    }
+
+    #[test]
+    fn group_translate() {
+        let a = group(&[4, 1, 3]);
+        let b = group(&[3, 0, 4]);
+        assert_eq!(mpi_group_translate_ranks(&a, &[0, 1, 2], &b), vec![Some(2), None, Some(0)]);
+    }
+
+    #[test]
+    fn group_compare() {
+        assert_eq!(mpi_group_compare(&group(&[1, 2]), &group(&[1, 2])), GroupCompare::Ident);
+        assert_eq!(mpi_group_compare(&group(&[1, 2]), &group(&[2, 1])), GroupCompare::Similar);
+        assert_eq!(mpi_group_compare(&group(&[1, 2]), &group(&[1, 3])), GroupCompare::Unequal);
+    }
+}
@@ -0,0 +1,111 @@
+//! Info objects
+//!
+//! An `Info` holds hints as key/value strings, passed to operations that can make use of them.
+//! Hints never change the result of an operation, so unknown keys are kept but ignored. Keys
+//! keep the order they were first set in.
+
+/// Longest key an `Info` accepts
+pub const MPI_MAX_INFO_KEY: usize = 255;
+/// Longest value an `Info` accepts
+pub const MPI_MAX_INFO_VAL: usize = 1024;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Info {
+    entries: Vec<(String, String)>,
+}
+
+/// Create an `Info` without any hints
+pub fn mpi_info_create() -> Info {
+    Info::default()
+}
+
+pub fn mpi_info_dup(info: &Info) -> Info {
+    info.clone()
+}
+
+/// Set `key` to `value`, replacing its current value
+pub fn mpi_info_set(info: &mut Info, key: &str, value: &str) {
+    if key.is_empty() || key.len() > MPI_MAX_INFO_KEY {
+        panic!("Info key must have 1 to {} characters: {:?}", MPI_MAX_INFO_KEY, key);
+    }
+    if value.len() > MPI_MAX_INFO_VAL {
+        panic!("Info value of {} exceeds {} characters", key, MPI_MAX_INFO_VAL);
+    }
+
+    match info.entries.iter_mut().find(|e| e.0 == key) {
+        Some(entry) => entry.1 = value.to_string(),
+        None => info.entries.push((key.to_string(), value.to_string())),
+    }
+}
+
+/// Value of `key`, `None` if it is not set
+pub fn mpi_info_get(info: &Info, key: &str) -> Option<String> {
+    info.entries.iter().find(|e| e.0 == key).map(|e| e.1.clone())
+}
+
+/// Length of the value of `key`, `None` if it is not set
+pub fn mpi_info_get_valuelen(info: &Info, key: &str) -> Option<usize> {
+    info.entries.iter().find(|e| e.0 == key).map(|e| e.1.len())
+}
+
+/// Remove `key`, which must be set
+pub fn mpi_info_delete(info: &mut Info, key: &str) {
+    match info.entries.iter().position(|e| e.0 == key) {
+        Some(i) => {
+            info.entries.remove(i);
+        }
+        None => panic!("Info key {:?} is not set", key),
+    }
+}
+
+/// Number of keys set. Together with `mpi_info_get_nthkey` this iterates over all hints.
+pub fn mpi_info_get_nkeys(info: &Info) -> usize {
+    info.entries.len()
+}
+
+/// Key number `n`, counting from 0 in the order the keys were first set
+pub fn mpi_info_get_nthkey(info: &Info, n: usize) -> String {
+    match info.entries.get(n) {
+        Some(entry) => entry.0.clone(),
+        None => panic!("Info has {} keys, no key {}", info.entries.len(), n),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn info_set_get_delete() {
+        let mut info = mpi_info_create();
+        mpi_info_set(&mut info, "alpha", "1");
+        mpi_info_set(&mut info, "beta", "two");
+        mpi_info_set(&mut info, "alpha", "3");
+        assert_eq!(mpi_info_get(&info, "alpha"), Some("3".to_string()));
+        assert_eq!(mpi_info_get_valuelen(&info, "beta"), Some(3));
+
+        let dup = mpi_info_dup(&info);
+        mpi_info_delete(&mut info, "alpha");
+        assert_eq!(mpi_info_get(&info, "alpha"), None);
+        assert_eq!(mpi_info_get(&dup, "alpha"), Some("3".to_string()));
+    }
+
+    #[test]
+    fn info_keys_in_order() {
+        let mut info = mpi_info_create();
+        for key in &["c", "a", "b"] {
+            mpi_info_set(&mut info, key, "x");
+        }
+        mpi_info_set(&mut info, "a", "y");
+        let keys: Vec<String> = (0..mpi_info_get_nkeys(&info))
+                                    .map(|n| mpi_info_get_nthkey(&info, n))
+                                    .collect();
+        assert_eq!(keys, vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn info_delete_missing() {
+        mpi_info_delete(&mut mpi_info_create(), "missing");
+    }
+}
@@ -1,17 +1,64 @@
+//! Implements mpi_init and mpi_init_thread
+//!
+//! All threads of a process share its rank. Every call into mpirs opens its own connection to
+//! mpirun and receives its reply on that connection, so any thread may make calls. mpirs does not
+//! keep calls made by several threads at the same time apart, though: collectives in particular
+//! would take each other's messages. The most it provides is therefore
+//! `ThreadLevel::Serialized`.
+
 use rustc_serialize::json;
 use libc;
 use comm_request::CommRequest;
 use comm_request::CommRequestType;
 use comm_request::ControlTy;
-use std::io::prelude::*;
+use std::cmp;
 use std::net::TcpStream;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, ThreadId};
 use utils;
 
+/// Level of thread support requested from and provided by `mpi_init_thread`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThreadLevel {
+    /// Only one thread will execute
+    Single,
+    /// The process may be multi-threaded, but only the main thread makes mpirs calls
+    Funneled,
+    /// Any thread may make mpirs calls, but never two at the same time
+    Serialized,
+    /// Any thread may make mpirs calls at any time
+    Multiple,
+}
+
+impl ThreadLevel {
+    fn from_usize(level: usize) -> ThreadLevel {
+        match level {
+            0 => ThreadLevel::Single,
+            1 => ThreadLevel::Funneled,
+            2 => ThreadLevel::Serialized,
+            _ => ThreadLevel::Multiple,
+        }
+    }
+}
+
+static PROVIDED: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    static ref MAIN_THREAD: Mutex<Option<ThreadId>> = Mutex::new(None);
+}
+
 pub fn mpi_init() {
+    mpi_init_thread(ThreadLevel::Single);
+}
+
+/// Initialize mpirs, requesting the given level of thread support. Returns the level that is
+/// actually provided: the requested one, but never more than `ThreadLevel::Serialized`.
+pub fn mpi_init_thread(required: ThreadLevel) -> ThreadLevel {
     let pid = utils::pid();
     let tag: u64 = u64::max_value();
     loop {
-        if let Ok(ref mut stream) = TcpStream::connect("127.0.0.1:31337") {
+        if let Ok(ref mut stream) = TcpStream::connect(utils::mpirun_addr()) {
             let commreq = CommRequest::<u32>::new(None,
                                                   None,
                                                   tag,
@@ -20,11 +67,26 @@ pub fn mpi_init() {
                                                   pid);
 
             let commreq_json = json::encode(&commreq).unwrap();
-            let _ = stream.write(&commreq_json.as_bytes());
+            utils::write_stream(stream, &commreq_json);
             break;
         }
         unsafe {
             libc::usleep(1000);
         }
     }
+
+    let provided = cmp::min(required, ThreadLevel::Serialized);
+    PROVIDED.store(provided as usize, Ordering::SeqCst);
+    *MAIN_THREAD.lock().unwrap() = Some(thread::current().id());
+    provided
+}
+
+/// Level of thread support provided by the call to `mpi_init_thread`
+pub fn mpi_query_thread() -> ThreadLevel {
+    ThreadLevel::from_usize(PROVIDED.load(Ordering::SeqCst))
+}
+
+/// Check if the calling thread is the one that initialized mpirs
+pub fn mpi_is_thread_main() -> bool {
+    *MAIN_THREAD.lock().unwrap() == Some(thread::current().id())
 }
@@ -0,0 +1,158 @@
+//! Buffer layouts of the vector collectives
+//!
+//! The `v` variants of collectives describe a buffer as one block per process: block `i` holds
+//! `counts[i]` elements starting at `displs[i]`. These helpers validate such a layout and move
+//! blocks in and out of a buffer.
+
+/// Panic unless there is one count and one displacement for each of `n` processes
+pub fn check_layout(counts: &[usize], displs: &[usize], n: usize) {
+    if counts.len() != n || displs.len() != n {
+        panic!("Expected {} counts and displacements, got {} and {}",
+               n,
+               counts.len(),
+               displs.len());
+    }
+}
+
+/// Number of elements a buffer needs to hold every block
+pub fn extent(counts: &[usize], displs: &[usize]) -> usize {
+    counts.iter().zip(displs.iter()).map(|(c, d)| c + d).max().unwrap_or(0)
+}
+
+/// Displacements of blocks stored back to back in rank order
+pub fn packed_displs(counts: &[usize]) -> Vec<usize> {
+    let mut displs = Vec::with_capacity(counts.len());
+    let mut start = 0;
+    for c in counts {
+        displs.push(start);
+        start += *c;
+    }
+    displs
+}
+
+/// Cut `buf` into `n` blocks of equal size
+pub fn split_equal<T: Clone>(buf: &[T], n: usize) -> Vec<Vec<T>> {
+    if buf.len() % n != 0 {
+        panic!("Buffer of {} elements cannot be split among {} processes", buf.len(), n);
+    }
+
+    let count = buf.len() / n;
+    (0..n).map(|i| buf[i * count..(i + 1) * count].to_vec()).collect()
+}
+
+/// Cut the blocks described by `counts` and `displs` out of `buf`
+pub fn split<T: Clone>(buf: &[T], counts: &[usize], displs: &[usize]) -> Vec<Vec<T>> {
+    let needed = extent(counts, displs);
+    if buf.len() < needed {
+        panic!("Buffer holds {} elements, layout needs {}", buf.len(), needed);
+    }
+
+    counts.iter().zip(displs.iter()).map(|(&c, &d)| buf[d..d + c].to_vec()).collect()
+}
+
+/// Write `blocks` into `buf` at the places described by `counts` and `displs`. Blocks must have
+/// the expected sizes and must not overlap, and `buf` must be large enough to hold all of them.
+pub fn place<T>(buf: &mut Vec<T>, blocks: Vec<Vec<T>>, counts: &[usize], displs: &[usize]) {
+    let needed = extent(counts, displs);
+    if buf.len() < needed {
+        panic!("Receive buffer holds {} elements, {} needed", buf.len(), needed);
+    }
+
+    let mut order: Vec<usize> = (0..counts.len()).filter(|&i| counts[i] > 0).collect();
+    order.sort_by_key(|&i| displs[i]);
+    for w in order.windows(2) {
+        if displs[w[0]] + counts[w[0]] > displs[w[1]] {
+            panic!("Blocks of processes {} and {} overlap", w[0], w[1]);
+        }
+    }
+
+    for (i, block) in blocks.into_iter().enumerate() {
+        if block.len() != counts[i] {
+            panic!("Process {} sent {} elements, expected {}", i, block.len(), counts[i]);
+        }
+
+        for (j, x) in block.into_iter().enumerate() {
+            buf[displs[i] + j] = x;
+        }
+    }
+}
+
+/// Concatenate blocks that must all hold `count` elements
+pub fn concat<T>(blocks: Vec<Vec<T>>, count: usize) -> Vec<T> {
+    let mut buf = Vec::with_capacity(blocks.len() * count);
+    for (i, block) in blocks.into_iter().enumerate() {
+        if block.len() != count {
+            panic!("Process {} sent {} elements, expected {}", i, block.len(), count);
+        }
+        buf.extend(block);
+    }
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layout_scatter_equal() {
+        let blocks = split_equal(&[1, 2, 3, 4, 5, 6], 3);
+        assert_eq!(blocks, vec![vec![1, 2], vec![3, 4], vec![5, 6]]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn layout_scatter_uneven() {
+        split_equal(&[1, 2, 3, 4, 5], 3);
+    }
+
+    #[test]
+    fn layout_scatterv_displs() {
+        // Blocks need not be in rank order and may leave gaps
+        let blocks = split(&[0, 1, 2, 3, 4, 5, 6], &[2, 0, 3], &[4, 0, 0]);
+        assert_eq!(blocks, vec![vec![4, 5], vec![], vec![0, 1, 2]]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn layout_scatterv_past_end() {
+        split(&[0, 1, 2], &[2, 2], &[0, 2]);
+    }
+
+    #[test]
+    fn layout_gather_concat() {
+        assert_eq!(concat(vec![vec![1], vec![2], vec![3]], 1), vec![1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn layout_gather_wrong_size() {
+        concat(vec![vec![1], vec![2, 3]], 1);
+    }
+
+    #[test]
+    fn layout_gatherv_place() {
+        let mut buf = vec![0; 6];
+        place(&mut buf, vec![vec![7, 7], vec![], vec![9]], &[2, 0, 1], &[3, 0, 0]);
+        assert_eq!(buf, vec![9, 0, 0, 7, 7, 0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn layout_gatherv_overlap() {
+        let mut buf = vec![0; 4];
+        place(&mut buf, vec![vec![1, 1], vec![2, 2]], &[2, 2], &[0, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn layout_gatherv_short_buffer() {
+        let mut buf = Vec::with_capacity(4);
+        place(&mut buf, vec![vec![1, 1], vec![2, 2]], &[2, 2], &[0, 2]);
+    }
+
+    #[test]
+    fn layout_packed() {
+        assert_eq!(packed_displs(&[2, 0, 3, 1]), vec![0, 2, 2, 5]);
+        assert_eq!(extent(&[2, 0, 3, 1], &[0, 2, 2, 5]), 6);
+    }
+}
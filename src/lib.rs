@@ -2,16 +2,26 @@
 
 extern crate rustc_serialize;
 extern crate libc;
+#[macro_use]
+extern crate lazy_static;
 
 pub mod mpi_datatype;
 pub mod mpi_comm;
+pub mod info;
+pub mod group;
+pub mod attribute;
+pub mod topology;
 pub mod mpi_request;
 pub mod comm_request;
 pub mod receiver_traits;
+pub mod op;
+pub mod algorithm;
+mod layout;
 
 pub mod init;
 pub mod finalize;
 pub mod send;
+pub mod sendrecv;
 pub mod wait;
 pub mod receive;
 pub mod bcast;
@@ -20,25 +30,58 @@ pub mod num_procs;
 pub mod scatter;
 pub mod barrier;
 pub mod gather;
+pub mod reduce;
+pub mod allgather;
+pub mod alltoall;
+pub mod scan;
+pub mod reduce_scatter;
+pub mod neighbor;
+pub mod spawn;
+pub mod port;
+pub mod window;
 
 pub mod utils {
     use libc;
-    use std::io::Read;
-    
+    use std::env;
+    use std::io::{Read, Write};
+    use std::net::{Shutdown, TcpStream};
+
+    /// Port mpirun listens on unless told otherwise
+    pub const DEFAULT_PORT: u16 = 31337;
+
+    lazy_static! {
+        static ref MPIRUN_ADDR: String =
+            format!("127.0.0.1:{}", port_from_env("MPIRS_PORT").unwrap_or(DEFAULT_PORT));
+    }
+
     pub fn pid() -> u32 {
         unsafe { libc::getpid()  as u32 }
     }
 
+    /// Port given by the environment variable `var`, if it is set
+    pub fn port_from_env(var: &str) -> Option<u16> {
+        env::var(var).ok().map(|port| {
+            port.parse().unwrap_or_else(|_| panic!("Invalid port in {}: {}", var, port))
+        })
+    }
+
+    /// Address of the mpirun that started this process. mpirun passes its port in `MPIRS_PORT`.
+    pub fn mpirun_addr() -> &'static str {
+        &MPIRUN_ADDR
+    }
+
+    /// Read until the other end closes the connection. A short read does not mean the message
+    /// is complete, especially when several threads have requests in flight at once.
     pub fn read_stream<T: Read>(stream: &mut T) -> String {
         let mut str_in = String::new();
-        let mut bytes_read = [0; 2048];
-        loop {
-            let n = stream.read(&mut bytes_read).expect("Read Error:");
-            str_in = format!("{}{}", str_in, String::from_utf8(bytes_read[0..n].to_vec()).unwrap());
-            if n < 2048 {
-                break;
-            }
-        }
+        stream.read_to_string(&mut str_in).expect("Read Error:");
         str_in
     }
+
+    /// Write a complete request and close our half of the connection so that mpirun knows where
+    /// the request ends.
+    pub fn write_stream(stream: &mut TcpStream, data: &str) {
+        stream.write_all(data.as_bytes()).expect("Write Error:");
+        let _ = stream.shutdown(Shutdown::Write);
+    }
 }
@@ -1,3 +1,741 @@
-// Type alias for MPIComm
-pub type MPIComm = u64;
-pub const MPI_COMM_WORLD: u64 = 0;
\ No newline at end of file
+//! Communicators
+//!
+//! An `MPIComm` is a handle to a communicator of this process. A communicator is a group of
+//! processes, listed by their world rank, together with a context id. Messages only
+//! match within the context they were sent in, so traffic on different communicators never
+//! mixes, even between the same processes. Each context id gives a communicator two matching
+//! contexts: one for point-to-point messages and one for the messages collectives exchange
+//! internally.
+//!
+//! Context ids are handed out by mpirun. Creating a communicator is collective over its parent:
+//! rank 0 of the parent allocates the ids and broadcasts them, so all members agree.
+//!
+//! An intercommunicator connects two disjoint groups. Ranks and sizes refer to the local group,
+//! but point-to-point messages are addressed to ranks of the remote group. Collectives combine
+//! the work of both groups through their leaders, rank 0 of each group. In a collective with a
+//! root, data flows between the root and the other group: the root passes `MPI_ROOT`, the rest of
+//! its group `MPI_PROC_NULL`, and the other group the rank of the root in the remote group. As in
+//! MPI, scans are only defined on intra-communicators, as are `mpi_comm_split` and
+//! `mpi_comm_create`.
+//!
+//! World ranks number all processes of a run. For the processes mpirun starts itself, they are
+//! the ranks in `MPI_COMM_WORLD`. Processes spawned later get the following numbers and a
+//! `MPI_COMM_WORLD` of their own, with an intercommunicator to the processes that spawned them.
+//!
+//! Communicators can be given a name for debugging. Names are announced to mpirun, which shows
+//! them in its traces and error messages.
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex, RwLock};
+use rustc_serialize::{json, Decodable, Encodable};
+use comm_request::{CommRequest, CommRequestType, ControlTy, RequestProc, World, RANK_BITS};
+use comm_rank::world_rank;
+use allgather::ring_allgather;
+use bcast::bcast;
+use send::send;
+use receive::recv;
+use sendrecv::sendrecv;
+use group::Group;
+use attribute;
+use info::Info;
+use topology::Topology;
+use std::net::TcpStream;
+use utils;
+
+/// Handle to a communicator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MPIComm(u64);
+
+pub const MPI_COMM_WORLD: MPIComm = MPIComm(0);
+
+/// Root of a collective on an intercommunicator, as passed by the root itself
+pub const MPI_ROOT: usize = usize::max_value();
+
+/// Root of a collective on an intercommunicator, as passed by the other processes of the root's
+/// group, which take no part in it
+pub const MPI_PROC_NULL: usize = usize::max_value() - 1;
+
+/// Longest name a communicator can be given
+pub const MPI_MAX_OBJECT_NAME: usize = 128;
+
+/// Result of `mpi_comm_compare`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommCompare {
+    /// Both handles refer to the same communicator
+    Ident,
+    /// Same processes in the same order, but a different context
+    Congruent,
+    /// Same processes in a different order
+    Similar,
+    Unequal,
+}
+
+struct Communicator {
+    /// World ranks of the members, indexed by their rank in the communicator
+    group: Arc<Vec<usize>>,
+    rank: usize,
+    /// World ranks of the remote group of an intercommunicator
+    remote: Option<Arc<Vec<usize>>>,
+    topology: Option<Topology>,
+    info: Info,
+    context: u64,
+    coll_seq: u64,
+}
+
+struct Registry {
+    comms: HashMap<u64, Communicator>,
+    next_handle: u64,
+    /// Remote group and context of the intercommunicator to the parent of a spawned process,
+    /// until `parent` is created from them
+    world_parent: Option<(Vec<usize>, u64)>,
+    parent: Option<MPIComm>,
+}
+
+impl Registry {
+    fn get(&mut self, comm: MPIComm) -> &mut Communicator {
+        self.comms.get_mut(&comm.0).unwrap_or_else(|| panic!("Invalid communicator {:?}", comm))
+    }
+
+    fn insert(&mut self,
+              group: Vec<usize>,
+              rank: usize,
+              remote: Option<Vec<usize>>,
+              context: u64)
+              -> MPIComm {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.comms.insert(handle,
+                          Communicator {
+                              group: Arc::new(group),
+                              rank: rank,
+                              remote: remote.map(Arc::new),
+                              topology: None,
+                              info: Info::default(),
+                              context: context,
+                              coll_seq: 0,
+                          });
+        MPIComm(handle)
+    }
+}
+
+/// Shows the name of the communicator if it has one, or else the handle. Reads the names only,
+/// so communicators can be formatted while the registry is locked.
+impl fmt::Display for MPIComm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = NAMES.read().unwrap_or_else(|e| e.into_inner());
+        match names.get(&self.0) {
+            Some(name) if !name.is_empty() => write!(f, "{}", name),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+lazy_static! {
+    /// Asks mpirun about the world on first use, before anyone can lock it
+    static ref REGISTRY: Mutex<Registry> = {
+        let world: World = json::decode(&control(ControlTy::GetWorld, ()))
+                               .expect("World fetching failed");
+        let me = world_rank();
+        let world_comm = Communicator {
+            rank: world.members.iter().position(|&r| r == me).unwrap(),
+            group: Arc::new(world.members),
+            remote: None,
+            topology: None,
+            info: Info::default(),
+            context: world.context,
+            coll_seq: 0,
+        };
+        let mut comms = HashMap::new();
+        comms.insert(MPI_COMM_WORLD.0, world_comm);
+        Mutex::new(Registry {
+            comms: comms,
+            next_handle: 1,
+            world_parent: world.parent,
+            parent: None,
+        })
+    };
+
+    /// Names of the communicators, kept apart from the registry for `Display`
+    static ref NAMES: RwLock<HashMap<u64, String>> = {
+        let mut names = HashMap::new();
+        names.insert(MPI_COMM_WORLD.0, "MPI_COMM_WORLD".to_string());
+        RwLock::new(names)
+    };
+}
+
+/// A matching context together with the group that translates the ranks messages are addressed
+/// to into world ranks
+#[derive(Debug, Clone)]
+pub(crate) struct Context {
+    id: u64,
+    group: Arc<Vec<usize>>,
+    comm: MPIComm,
+}
+
+impl Context {
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(crate) fn world_proc(&self, p: RequestProc) -> RequestProc {
+        match p {
+            RequestProc::Process(r) => {
+                match self.group.get(r) {
+                    Some(&w) => RequestProc::Process(w),
+                    None => {
+                        panic!("Rank {} out of range for a group of {} on {}",
+                               r,
+                               self.group.len(),
+                               self.comm)
+                    }
+                }
+            }
+            _ => p,
+        }
+    }
+
+    /// Rank in the group of the process with world rank `p`, as reported by mpirun in the source
+    /// of a received message
+    pub(crate) fn group_proc(&self, p: RequestProc) -> RequestProc {
+        match p {
+            RequestProc::Process(w) => {
+                match self.group.iter().position(|&m| m == w) {
+                    Some(r) => RequestProc::Process(r),
+                    None => panic!("Process {} is not part of the group", w),
+                }
+            }
+            _ => p,
+        }
+    }
+}
+
+/// Context of point-to-point messages on `comm`
+pub(crate) fn p2p_context(comm: MPIComm) -> Context {
+    let mut reg = REGISTRY.lock().unwrap();
+    let c = reg.get(comm);
+    Context {
+        id: 2 * c.context,
+        group: c.remote.as_ref().unwrap_or(&c.group).clone(),
+        comm: comm,
+    }
+}
+
+/// Context of the messages collectives exchange on `comm`. User messages can never match them,
+/// whatever their tag. On an intercommunicator, messages are addressed to the local group.
+pub(crate) fn coll_context(comm: MPIComm) -> Context {
+    let mut reg = REGISTRY.lock().unwrap();
+    let c = reg.get(comm);
+    Context {
+        id: 2 * c.context + 1,
+        group: c.group.clone(),
+        comm: comm,
+    }
+}
+
+/// Context of the messages collectives exchange between the two groups of intercommunicator
+/// `comm`
+pub(crate) fn remote_coll_context(comm: MPIComm) -> Context {
+    let mut reg = REGISTRY.lock().unwrap();
+    let c = reg.get(comm);
+    Context {
+        id: 2 * c.context + 1,
+        group: c.remote.clone().expect("Not an intercommunicator"),
+        comm: comm,
+    }
+}
+
+/// Tag for the next collective on `comm`. Every process calls the collectives of a communicator
+/// in the same order, so they all draw the same tag. Collectives still in flight in the
+/// background therefore never match messages of the ones started after them.
+pub(crate) fn next_coll_tag(comm: MPIComm) -> u64 {
+    if mpi_comm_test_inter(comm) {
+        panic!("Collective not supported on intercommunicator {}, see the mpi_comm docs", comm);
+    }
+    next_inter_tag(comm)
+}
+
+/// Same as `next_coll_tag`, for the collectives that also support intercommunicators
+pub(crate) fn next_inter_tag(comm: MPIComm) -> u64 {
+    let mut reg = REGISTRY.lock().unwrap();
+    let c = reg.get(comm);
+    let tag = c.coll_seq;
+    c.coll_seq += 1;
+    tag
+}
+
+/// Part of a process in a collective with a root on an intercommunicator
+pub(crate) enum InterRole {
+    /// The root itself
+    Root,
+    /// Another process of the root's group
+    Idle,
+    /// A process of the other group, which knows the root by this rank in the remote group
+    Remote(usize),
+}
+
+/// Part that passing `root` to a collective on intercommunicator `comm` gives this process
+pub(crate) fn inter_role(root: usize, comm: MPIComm) -> InterRole {
+    match root {
+        MPI_ROOT => InterRole::Root,
+        MPI_PROC_NULL => InterRole::Idle,
+        r => {
+            let n = remote_members(comm).len();
+            if r >= n {
+                panic!("Root {} out of range for a remote group of {}", r, n);
+            }
+            InterRole::Remote(r)
+        }
+    }
+}
+
+/// World ranks of the members of `comm`, indexed by their rank in `comm`
+pub(crate) fn members(comm: MPIComm) -> Arc<Vec<usize>> {
+    REGISTRY.lock().unwrap().get(comm).group.clone()
+}
+
+/// World ranks of the remote group of intercommunicator `comm`
+pub(crate) fn remote_members(comm: MPIComm) -> Arc<Vec<usize>> {
+    REGISTRY.lock().unwrap().get(comm).remote.clone().expect("Not an intercommunicator")
+}
+
+/// Check if `comm` has members in other jobs, connected through `port`
+pub(crate) fn spans_jobs(comm: MPIComm) -> bool {
+    members(comm).iter().any(|&r| r >> RANK_BITS != 0)
+}
+
+/// Topology attached to `comm`, if any
+pub(crate) fn topology(comm: MPIComm) -> Option<Topology> {
+    REGISTRY.lock().unwrap().get(comm).topology.clone()
+}
+
+pub(crate) fn set_topology(comm: MPIComm, topology: Topology) {
+    REGISTRY.lock().unwrap().get(comm).topology = Some(topology);
+}
+
+pub(crate) fn rank(comm: MPIComm) -> usize {
+    REGISTRY.lock().unwrap().get(comm).rank
+}
+
+pub(crate) fn size(comm: MPIComm) -> usize {
+    REGISTRY.lock().unwrap().get(comm).group.len()
+}
+
+/// Context id of `comm`, unique among all communicators of the run
+pub(crate) fn context_id(comm: MPIComm) -> u64 {
+    REGISTRY.lock().unwrap().get(comm).context
+}
+
+/// Intercommunicator from the local group `group`, where the calling process has rank `rank`,
+/// to the processes of `remote`
+pub(crate) fn insert_intercomm(group: Vec<usize>,
+                               rank: usize,
+                               remote: Vec<usize>,
+                               context: u64)
+                               -> MPIComm {
+    REGISTRY.lock().unwrap().insert(group, rank, Some(remote), context)
+}
+
+/// Intercommunicator to the processes that spawned this one, `None` if mpirun started it or the
+/// intercommunicator was freed
+pub(crate) fn parent() -> Option<MPIComm> {
+    let mut reg = REGISTRY.lock().unwrap();
+    if let Some((remote, context)) = reg.world_parent.take() {
+        let (group, rank) = {
+            let world = reg.get(MPI_COMM_WORLD);
+            (world.group.to_vec(), world.rank)
+        };
+        reg.parent = Some(reg.insert(group, rank, Some(remote), context));
+    }
+
+    let parent = reg.parent;
+    parent.filter(|p| reg.comms.contains_key(&p.0))
+}
+
+/// Send a control request carrying `data` to mpirun and return its reply
+pub(crate) fn control<T>(ty: ControlTy, data: T) -> String
+    where T: Debug + Clone + Encodable + Decodable
+{
+    control_at(utils::mpirun_addr(), ty, data)
+}
+
+/// Same as `control`, for the mpirun listening at `addr`
+pub(crate) fn control_at<T>(addr: &str, ty: ControlTy, data: T) -> String
+    where T: Debug + Clone + Encodable + Decodable
+{
+    let pid = utils::pid();
+    let commreq = CommRequest::<T>::new(None,
+                                        None,
+                                        u64::max_value(),
+                                        Some(data),
+                                        CommRequestType::Control(ty),
+                                        pid);
+    let commreq_json = json::encode(&commreq).unwrap();
+
+    let mut stream = TcpStream::connect(addr).unwrap_or_else(|e| {
+        panic!("Cannot reach mpirun at {}: {}", addr, e)
+    });
+    utils::write_stream(&mut stream, &commreq_json);
+    utils::read_stream(&mut stream)
+}
+
+/// Ask mpirun for `count` unused context ids and return the first
+fn request_contexts(count: u64) -> u64 {
+    control(ControlTy::AllocContext, count).parse().expect("Context allocation failed")
+}
+
+/// Rank 0 of `comm` allocates `count` consecutive context ids and shares the first with the
+/// other processes of `comm`
+fn alloc_contexts(comm: MPIComm, count: u64, tag: u64) -> u64 {
+    let mut base = 0;
+    if rank(comm) == 0 {
+        base = request_contexts(count);
+    }
+    bcast(&mut base, 0, comm, tag);
+    base
+}
+
+/// World rank `rank` as processes of other jobs know it. Ranks of other jobs are qualified
+/// already, so comparing qualified ranks gives the same order on both sides of a connection.
+fn qualified(rank: usize) -> usize {
+    if rank >> RANK_BITS != 0 {
+        return rank;
+    }
+    let port = utils::port_from_env("MPIRS_PORT").unwrap_or(utils::DEFAULT_PORT);
+    (port as usize) << RANK_BITS | rank
+}
+
+/// The leader of intercommunicator `comm` whose process has the lower qualified world rank
+/// allocates a context id and shares it with both groups
+fn alloc_inter_context(comm: MPIComm, tag: u64) -> u64 {
+    let mut context = 0;
+    if rank(comm) == 0 {
+        let leader = RequestProc::Process(0);
+        if qualified(members(comm)[0]) < qualified(remote_members(comm)[0]) {
+            context = request_contexts(1);
+            send(&context, leader, tag, remote_coll_context(comm));
+        } else {
+            recv(&mut context, leader, tag, remote_coll_context(comm));
+        }
+    }
+    bcast(&mut context, 0, comm, tag);
+    context
+}
+
+/// Create a communicator with the same processes, topology and info hints as `comm` and a new
+/// context. The duplicate of an intercommunicator connects the same two groups. It receives the
+/// attributes the copy callbacks of their keyvals hand down. Collective over `comm`.
+pub fn mpi_comm_dup(comm: MPIComm) -> MPIComm {
+    let info = mpi_comm_get_info(comm);
+    mpi_comm_dup_with_info(comm, &info)
+}
+
+/// Same as `mpi_comm_dup`, but the duplicate gets the hints of `info` instead of those of `comm`
+pub fn mpi_comm_dup_with_info(comm: MPIComm, info: &Info) -> MPIComm {
+    let context = if mpi_comm_test_inter(comm) {
+        alloc_inter_context(comm, next_inter_tag(comm))
+    } else {
+        alloc_contexts(comm, 1, next_coll_tag(comm))
+    };
+    let dup = {
+        let mut reg = REGISTRY.lock().unwrap();
+        let (group, rank, remote, topology) = {
+            let c = reg.get(comm);
+            (c.group.to_vec(), c.rank, c.remote.as_ref().map(|r| r.to_vec()), c.topology.clone())
+        };
+        let dup = reg.insert(group, rank, remote, context);
+        reg.get(dup).topology = topology;
+        reg.get(dup).info = info.clone();
+        dup
+    };
+    attribute::copy_attrs(comm, dup);
+    dup
+}
+
+/// Replace the info hints of `comm`
+pub fn mpi_comm_set_info(comm: MPIComm, info: &Info) {
+    REGISTRY.lock().unwrap().get(comm).info = info.clone();
+}
+
+/// Info hints of `comm`. The result is a copy, changing it does not affect `comm`.
+pub fn mpi_comm_get_info(comm: MPIComm) -> Info {
+    REGISTRY.lock().unwrap().get(comm).info.clone()
+}
+
+/// Name `comm` for debugging. The name is local to this process; mpirun shows the name it was
+/// told last for the communicator.
+pub fn mpi_comm_set_name(comm: MPIComm, name: &str) {
+    if name.len() > MPI_MAX_OBJECT_NAME {
+        panic!("Communicator name exceeds {} characters: {:?}", MPI_MAX_OBJECT_NAME, name);
+    }
+
+    let context = REGISTRY.lock().unwrap().get(comm).context;
+    NAMES.write().unwrap().insert(comm.0, name.to_string());
+    control(ControlTy::SetName, (context, name.to_string()));
+}
+
+/// Name of `comm`, empty if it has none. `MPI_COMM_WORLD` is named after itself.
+pub fn mpi_comm_get_name(comm: MPIComm) -> String {
+    REGISTRY.lock().unwrap().get(comm);
+    NAMES.read().unwrap().get(&comm.0).cloned().unwrap_or_default()
+}
+
+/// Split `comm` into one communicator per color. Processes are ranked by `key` within their
+/// color, ties broken by their rank in `comm`. Processes passing no color take part in the
+/// split but get no communicator. Collective over `comm`.
+pub fn mpi_comm_split(comm: MPIComm, color: Option<usize>, key: usize) -> Option<MPIComm> {
+    mpi_comm_split_with_info(comm, color, key, &Info::default())
+}
+
+/// Same as `mpi_comm_split`, the new communicators getting the hints of `info`
+pub fn mpi_comm_split_with_info(comm: MPIComm,
+                                color: Option<usize>,
+                                key: usize,
+                                info: &Info)
+                                -> Option<MPIComm> {
+    let gather_tag = next_coll_tag(comm);
+    let alloc_tag = next_coll_tag(comm);
+    let me = rank(comm);
+    let parent = members(comm);
+
+    let entries: Vec<(Option<usize>, usize)> =
+        ring_allgather(vec![(color, key)], comm, gather_tag).into_iter().map(|e| e[0]).collect();
+
+    // One context per color, the colors being the same on every process
+    let mut colors: Vec<usize> = entries.iter().filter_map(|e| e.0).collect();
+    colors.sort();
+    colors.dedup();
+    let base = alloc_contexts(comm, colors.len() as u64, alloc_tag);
+
+    let color = match color {
+        Some(c) => c,
+        None => return None,
+    };
+
+    let mut ranked: Vec<(usize, usize)> = (0..entries.len())
+                                              .filter(|&r| entries[r].0 == Some(color))
+                                              .map(|r| (entries[r].1, r))
+                                              .collect();
+    ranked.sort();
+
+    let group = ranked.iter().map(|&(_, r)| parent[r]).collect();
+    let rank = ranked.iter().position(|&(_, r)| r == me).unwrap();
+    let context = base + colors.binary_search(&color).unwrap() as u64;
+    let mut reg = REGISTRY.lock().unwrap();
+    let split = reg.insert(group, rank, None, context);
+    reg.get(split).info = info.clone();
+    Some(split)
+}
+
+/// Create a communicator of the processes in `group`, which must be a subset of `comm`. Every
+/// process of `comm` must call this with the same group, processes outside of it get no
+/// communicator. Collective over `comm`.
+pub fn mpi_comm_create(comm: MPIComm, group: &Group) -> Option<MPIComm> {
+    mpi_comm_create_with_info(comm, group, &Info::default())
+}
+
+/// Same as `mpi_comm_create`, the new communicator getting the hints of `info`
+pub fn mpi_comm_create_with_info(comm: MPIComm, group: &Group, info: &Info) -> Option<MPIComm> {
+    let parent = members(comm);
+    let ranks = group.world_ranks();
+    if let Some(r) = ranks.iter().find(|r| !parent.contains(r)) {
+        panic!("Process {} of the group is not part of the communicator", r);
+    }
+
+    let context = alloc_contexts(comm, 1, next_coll_tag(comm));
+    let me = parent[rank(comm)];
+    ranks.iter().position(|&r| r == me).map(|rank| {
+        let mut reg = REGISTRY.lock().unwrap();
+        let created = reg.insert(ranks.to_vec(), rank, None, context);
+        reg.get(created).info = info.clone();
+        created
+    })
+}
+
+/// Release `comm` after deleting its attributes. The handle must not be used afterwards.
+pub fn mpi_comm_free(comm: MPIComm) {
+    if comm == MPI_COMM_WORLD {
+        panic!("MPI_COMM_WORLD cannot be freed");
+    }
+    if !REGISTRY.lock().unwrap().comms.contains_key(&comm.0) {
+        panic!("Invalid communicator {:?}", comm);
+    }
+
+    attribute::delete_attrs(comm);
+    if REGISTRY.lock().unwrap().comms.remove(&comm.0).is_none() {
+        panic!("Invalid communicator {:?}", comm);
+    }
+    NAMES.write().unwrap().remove(&comm.0);
+}
+
+/// Compare two communicators. Intercommunicators are compared by both of their groups.
+pub fn mpi_comm_compare(comm1: MPIComm, comm2: MPIComm) -> CommCompare {
+    if comm1 == comm2 {
+        return CommCompare::Ident;
+    }
+
+    let groups = |comm| {
+        let mut reg = REGISTRY.lock().unwrap();
+        let c = reg.get(comm);
+        (c.group.to_vec(), c.remote.as_ref().map(|r| r.to_vec()))
+    };
+    let (group1, remote1) = groups(comm1);
+    let (group2, remote2) = groups(comm2);
+    if group1 == group2 && remote1 == remote2 {
+        return CommCompare::Congruent;
+    }
+
+    let sorted = |mut g: Vec<usize>| {
+        g.sort();
+        g
+    };
+    if sorted(group1) == sorted(group2) && remote1.map(&sorted) == remote2.map(&sorted) {
+        CommCompare::Similar
+    } else {
+        CommCompare::Unequal
+    }
+}
+
+/// Check if `comm` is an intercommunicator
+pub fn mpi_comm_test_inter(comm: MPIComm) -> bool {
+    REGISTRY.lock().unwrap().get(comm).remote.is_some()
+}
+
+/// Connect the processes of `local_comm` with those of another, disjoint communicator. The two
+/// leaders introduce their groups to each other through `peer_comm`, where they address each
+/// other as `remote_leader` using `tag`. Collective over `local_comm`, whose processes must all
+/// pass the same leaders.
+pub fn mpi_intercomm_create(local_comm: MPIComm,
+                            local_leader: usize,
+                            peer_comm: MPIComm,
+                            remote_leader: usize,
+                            tag: u64)
+                            -> MPIComm {
+    mpi_intercomm_create_with_info(local_comm,
+                                   local_leader,
+                                   peer_comm,
+                                   remote_leader,
+                                   tag,
+                                   &Info::default())
+}
+
+/// Same as `mpi_intercomm_create`, the intercommunicator getting the hints of `info`
+pub fn mpi_intercomm_create_with_info(local_comm: MPIComm,
+                                      local_leader: usize,
+                                      peer_comm: MPIComm,
+                                      remote_leader: usize,
+                                      tag: u64,
+                                      info: &Info)
+                                      -> MPIComm {
+    let coll_tag = next_coll_tag(local_comm);
+    let local = members(local_comm);
+
+    // The remote group and the context of the intercommunicator
+    let mut remote: (Vec<usize>, u64) = (Vec::new(), 0);
+    if rank(local_comm) == local_leader {
+        let peer = RequestProc::Process(remote_leader);
+        sendrecv(&local.to_vec(),
+                 peer,
+                 tag,
+                 &mut remote.0,
+                 peer,
+                 tag,
+                 p2p_context(peer_comm));
+
+        // The leader with the lower qualified world rank allocates the context. On an
+        // intercommunicator, the remote leader is a rank of the remote group.
+        let peers = if mpi_comm_test_inter(peer_comm) {
+            remote_members(peer_comm)
+        } else {
+            members(peer_comm)
+        };
+        if qualified(local[local_leader]) < qualified(peers[remote_leader]) {
+            remote.1 = request_contexts(1);
+            send(&remote.1, peer, tag, p2p_context(peer_comm));
+        } else {
+            recv(&mut remote.1, peer, tag, p2p_context(peer_comm));
+        }
+    }
+    bcast(&mut remote, local_leader, local_comm, coll_tag);
+
+    let (remote, context) = remote;
+    if let Some(r) = remote.iter().find(|r| local.contains(r)) {
+        panic!("Process {} is part of both groups of an intercommunicator", r);
+    }
+
+    let rank = rank(local_comm);
+    let mut reg = REGISTRY.lock().unwrap();
+    let inter = reg.insert(local.to_vec(), rank, Some(remote), context);
+    reg.get(inter).info = info.clone();
+    inter
+}
+
+/// Merge the two groups of intercommunicator `comm` into one intra-communicator. The group
+/// passing `high = false` comes first; if both pass the same value, the group whose leader has
+/// the lower qualified world rank comes first. Collective over both groups.
+pub fn mpi_intercomm_merge(comm: MPIComm, high: bool) -> MPIComm {
+    mpi_intercomm_merge_with_info(comm, high, &Info::default())
+}
+
+/// Same as `mpi_intercomm_merge`, the merged communicator getting the hints of `info`
+pub fn mpi_intercomm_merge_with_info(comm: MPIComm, high: bool, info: &Info) -> MPIComm {
+    let tag = next_inter_tag(comm);
+    let local = members(comm);
+    let remote = remote_members(comm);
+
+    // Whether the local group comes first, and the context of the merged communicator
+    let mut merged: (bool, u64) = (false, 0);
+    if rank(comm) == 0 {
+        let leader = RequestProc::Process(0);
+        let mut remote_high = false;
+        sendrecv(&high,
+                 leader,
+                 tag,
+                 &mut remote_high,
+                 leader,
+                 tag,
+                 remote_coll_context(comm));
+
+        merged.0 = if high != remote_high {
+            !high
+        } else {
+            qualified(local[0]) < qualified(remote[0])
+        };
+        if merged.0 {
+            merged.1 = request_contexts(1);
+            send(&merged.1, leader, tag, remote_coll_context(comm));
+        } else {
+            recv(&mut merged.1, leader, tag, remote_coll_context(comm));
+        }
+    }
+    bcast(&mut merged, 0, comm, tag);
+
+    let (local_first, context) = merged;
+    let mut group = if local_first { local.to_vec() } else { remote.to_vec() };
+    group.extend_from_slice(if local_first { &remote } else { &local });
+
+    let me = local[rank(comm)];
+    let rank = group.iter().position(|&r| r == me).unwrap();
+    let mut reg = REGISTRY.lock().unwrap();
+    let merged = reg.insert(group, rank, None, context);
+    reg.get(merged).info = info.clone();
+    merged
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn context_translates_ranks_both_ways() {
+        let context = Context {
+            id: 2,
+            group: Arc::new(vec![4, 1, 3]),
+            comm: MPIComm(2),
+        };
+        assert_eq!(context.world_proc(RequestProc::Process(2)), RequestProc::Process(3));
+        assert_eq!(context.group_proc(RequestProc::Process(1)), RequestProc::Process(1));
+        assert_eq!(context.group_proc(RequestProc::Process(4)), RequestProc::Process(0));
+        assert_eq!(context.group_proc(RequestProc::Any), RequestProc::Any);
+    }
+}
@@ -1,5 +1,8 @@
 // MPIRequest structure 
 
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
 #[derive(Debug, Clone, Default)]
 pub struct MPIRequest {
     src: Option<usize>,
@@ -16,3 +19,44 @@ impl MPIRequest {
     	}
     }
 }
+
+/// Handle to a non-blocking collective
+///
+/// The collective runs on its own thread and makes progress while the caller computes. Buffers
+/// are moved into the collective when it starts and handed back by `wait` once it completes.
+pub struct CollRequest<R> {
+    rx: Receiver<R>,
+    result: Option<R>,
+}
+
+impl<R: 'static + Send> CollRequest<R> {
+    pub(crate) fn spawn<F>(f: F) -> CollRequest<R>
+        where F: 'static + FnOnce() -> R + Send
+    {
+        let (tx, rx) = channel::<R>();
+        thread::spawn(move || {
+            let _ = tx.send(f());
+        });
+
+        CollRequest {
+            rx: rx,
+            result: None,
+        }
+    }
+
+    /// Check if the collective has completed without blocking
+    pub fn test(&mut self) -> bool {
+        if self.result.is_none() {
+            self.result = self.rx.try_recv().ok();
+        }
+        self.result.is_some()
+    }
+
+    /// Block until the collective completes and return its buffers
+    pub fn wait(mut self) -> R {
+        match self.result.take() {
+            Some(result) => result,
+            None => self.rx.recv().expect("Collective failed"),
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Implements the neighbourhood collectives
+//!
+//! On a communicator with a topology, every process exchanges blocks with its neighbours only:
+//! it receives one block from each process it receives from and sends one block to each process
+//! it sends to, in the order `topology` lists them. On a Cartesian grid those are the neighbours
+//! below and above in every dimension. Neighbours beyond the edge of a non-periodic dimension are
+//! `RequestProc::ProcNull`: nothing is sent to them and their blocks of the receive buffer are
+//! left as they are.
+
+use rustc_serialize::Decodable;
+use mpi_comm::{MPIComm, coll_context, next_coll_tag};
+use mpi_request::CollRequest;
+use comm_request::RequestProc;
+use std::fmt::Debug;
+use std::mem;
+use rustc_serialize::Encodable;
+use send::send;
+use receive::irecv;
+use receiver_traits::Message;
+use topology::{self, TopoType};
+use layout;
+
+/// Send `blocks[i]` to the `i`th neighbour the calling process sends to and return the block
+/// received from each neighbour it receives from, `None` for `RequestProc::ProcNull`
+fn exchange<T>(mut blocks: Vec<Vec<T>>, comm: MPIComm, tag: u64) -> Vec<Option<Vec<T>>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let (sources, destinations) = topology::neighbors(comm);
+    if blocks.len() != destinations.len() {
+        panic!("{} blocks for {} neighbours", blocks.len(), destinations.len());
+    }
+
+    // One sender per destination keeps its blocks in order without holding up the others
+    let cart = topology::mpi_topo_test(comm) == Some(TopoType::Cart);
+    let mut sends: Vec<CollRequest<()>> = Vec::new();
+    for (dest, order) in send_queues(&destinations, cart) {
+        let queue: Vec<Vec<T>> =
+            order.into_iter().map(|i| mem::replace(&mut blocks[i], Vec::new())).collect();
+        let context = coll_context(comm);
+        sends.push(CollRequest::spawn(move || {
+            for block in queue {
+                send(&block, dest, tag, context.clone());
+            }
+        }));
+    }
+
+    let received = sources.iter().map(|&src| irecv(src, tag, coll_context(comm)).wait()).collect();
+    for s in sends {
+        s.wait();
+    }
+    received
+}
+
+/// Indices of the blocks for each destination, in the order they are sent
+fn send_queues(destinations: &[RequestProc], cart: bool) -> Vec<(RequestProc, Vec<usize>)> {
+    // Blocks for the same process are received in the order they were sent. In a grid dimension
+    // of one or two processes the neighbours below and above are the same process, and the block
+    // sent up must land in its block from below, so it goes first.
+    let mut order: Vec<usize> = (0..destinations.len()).collect();
+    if cart {
+        for pair in order.chunks_mut(2) {
+            pair.reverse();
+        }
+    }
+
+    let mut queues: Vec<(RequestProc, Vec<usize>)> = Vec::new();
+    for i in order {
+        let dest = destinations[i];
+        match queues.iter_mut().find(|q| q.0 == dest) {
+            Some(q) => q.1.push(i),
+            None => queues.push((dest, vec![i])),
+        }
+    }
+    queues
+}
+
+/// Write the received blocks of equal size back to back into `recvbuf`
+fn place_equal<T>(recvbuf: &mut Vec<T>, received: Vec<Option<Vec<T>>>) {
+    let count = received.iter().filter_map(|b| b.as_ref()).map(|b| b.len()).next().unwrap_or(0);
+    if received.iter().all(|b| b.is_some()) {
+        *recvbuf = layout::concat(received.into_iter().map(Option::unwrap).collect(), count);
+    } else {
+        let n = received.len();
+        let displs: Vec<usize> = (0..n).map(|i| i * count).collect();
+        place(recvbuf, received, &vec![count; n], &displs);
+    }
+}
+
+/// Same as `layout::place`, leaving the blocks of missing neighbours untouched
+fn place<T>(recvbuf: &mut Vec<T>,
+            received: Vec<Option<Vec<T>>>,
+            recvcounts: &[usize],
+            displs: &[usize]) {
+    let counts: Vec<usize> = received.iter()
+                                     .zip(recvcounts)
+                                     .map(|(b, &c)| if b.is_some() { c } else { 0 })
+                                     .collect();
+    let blocks = received.into_iter().map(|b| b.unwrap_or_default()).collect();
+    layout::place(recvbuf, blocks, &counts, displs);
+}
+
+/// Send `sendbuf` to every neighbour and gather the buffers of the neighbours into `recvbuf`, in
+/// the order of the neighbours. With missing neighbours, `recvbuf` must already hold a block for
+/// every neighbour.
+pub fn mpi_neighbor_allgather<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    neighbor_allgather(sendbuf, recvbuf, comm, next_coll_tag(comm));
+}
+
+/// Start the exchange of `mpi_neighbor_allgather`. `wait` returns `recvbuf` with the received
+/// blocks in place.
+pub fn mpi_ineighbor_allgather<T>(sendbuf: Vec<T>,
+                                  recvbuf: Vec<T>,
+                                  comm: MPIComm)
+                                  -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_coll_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = recvbuf;
+        neighbor_allgather(&sendbuf, &mut recvbuf, comm, tag);
+        recvbuf
+    })
+}
+
+fn neighbor_allgather<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let (_, destinations) = topology::neighbors(comm);
+    let blocks = vec![sendbuf.to_vec(); destinations.len()];
+    place_equal(recvbuf, exchange(blocks, comm, tag));
+}
+
+/// Send `sendbuf` to every neighbour. The block of the `i`th neighbour must have `recvcounts[i]`
+/// elements and is placed at `displs[i]` of `recvbuf`, which must already be large enough to hold
+/// every block.
+pub fn mpi_neighbor_allgatherv<T>(sendbuf: &[T],
+                                  recvbuf: &mut Vec<T>,
+                                  recvcounts: &[usize],
+                                  displs: &[usize],
+                                  comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    neighbor_allgatherv(sendbuf, recvbuf, recvcounts, displs, comm, next_coll_tag(comm));
+}
+
+/// Start the exchange of `mpi_neighbor_allgatherv`. `wait` returns `recvbuf` with the received
+/// blocks in place.
+pub fn mpi_ineighbor_allgatherv<T>(sendbuf: Vec<T>,
+                                   recvbuf: Vec<T>,
+                                   recvcounts: Vec<usize>,
+                                   displs: Vec<usize>,
+                                   comm: MPIComm)
+                                   -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_coll_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = recvbuf;
+        neighbor_allgatherv(&sendbuf, &mut recvbuf, &recvcounts, &displs, comm, tag);
+        recvbuf
+    })
+}
+
+fn neighbor_allgatherv<T>(sendbuf: &[T],
+                          recvbuf: &mut Vec<T>,
+                          recvcounts: &[usize],
+                          displs: &[usize],
+                          comm: MPIComm,
+                          tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let (sources, destinations) = topology::neighbors(comm);
+    layout::check_layout(recvcounts, displs, sources.len());
+
+    let blocks = vec![sendbuf.to_vec(); destinations.len()];
+    place(recvbuf, exchange(blocks, comm, tag), recvcounts, displs);
+}
+
+/// Send the `i`th of equal blocks of `sendbuf` to the `i`th neighbour and gather the blocks
+/// received into `recvbuf`, in the order of the neighbours. With missing neighbours, `recvbuf`
+/// must already hold a block for every neighbour.
+pub fn mpi_neighbor_alltoall<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    neighbor_alltoall(sendbuf, recvbuf, comm, next_coll_tag(comm));
+}
+
+/// Start the exchange of `mpi_neighbor_alltoall`. `wait` returns `recvbuf` with the received
+/// blocks in place.
+pub fn mpi_ineighbor_alltoall<T>(sendbuf: Vec<T>,
+                                 recvbuf: Vec<T>,
+                                 comm: MPIComm)
+                                 -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_coll_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = recvbuf;
+        neighbor_alltoall(&sendbuf, &mut recvbuf, comm, tag);
+        recvbuf
+    })
+}
+
+fn neighbor_alltoall<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let (_, destinations) = topology::neighbors(comm);
+    let blocks = if destinations.is_empty() {
+        Vec::new()
+    } else {
+        layout::split_equal(sendbuf, destinations.len())
+    };
+    place_equal(recvbuf, exchange(blocks, comm, tag));
+}
+
+/// Send `sendcounts[i]` elements starting at `sdispls[i]` of `sendbuf` to the `i`th neighbour.
+/// The block of the `i`th neighbour must have `recvcounts[i]` elements and is placed at
+/// `rdispls[i]` of `recvbuf`, which must already be large enough to hold every block.
+pub fn mpi_neighbor_alltoallv<T>(sendbuf: &[T],
+                                 sendcounts: &[usize],
+                                 sdispls: &[usize],
+                                 recvbuf: &mut Vec<T>,
+                                 recvcounts: &[usize],
+                                 rdispls: &[usize],
+                                 comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    neighbor_alltoallv(sendbuf,
+                       sendcounts,
+                       sdispls,
+                       recvbuf,
+                       recvcounts,
+                       rdispls,
+                       comm,
+                       next_coll_tag(comm));
+}
+
+/// Start the exchange of `mpi_neighbor_alltoallv`. `wait` returns `recvbuf` with the received
+/// blocks in place.
+pub fn mpi_ineighbor_alltoallv<T>(sendbuf: Vec<T>,
+                                  sendcounts: Vec<usize>,
+                                  sdispls: Vec<usize>,
+                                  recvbuf: Vec<T>,
+                                  recvcounts: Vec<usize>,
+                                  rdispls: Vec<usize>,
+                                  comm: MPIComm)
+                                  -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_coll_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = recvbuf;
+        neighbor_alltoallv(&sendbuf,
+                           &sendcounts,
+                           &sdispls,
+                           &mut recvbuf,
+                           &recvcounts,
+                           &rdispls,
+                           comm,
+                           tag);
+        recvbuf
+    })
+}
+
+fn neighbor_alltoallv<T>(sendbuf: &[T],
+                         sendcounts: &[usize],
+                         sdispls: &[usize],
+                         recvbuf: &mut Vec<T>,
+                         recvcounts: &[usize],
+                         rdispls: &[usize],
+                         comm: MPIComm,
+                         tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let (sources, destinations) = topology::neighbors(comm);
+    layout::check_layout(sendcounts, sdispls, destinations.len());
+    layout::check_layout(recvcounts, rdispls, sources.len());
+
+    let blocks = layout::split(sendbuf, sendcounts, sdispls);
+    place(recvbuf, exchange(blocks, comm, tag), recvcounts, rdispls);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use comm_request::RequestProc::{Process, ProcNull};
+    use topology::{Topology, neighbors_of};
+
+    #[test]
+    fn neighbor_cart_order() {
+        // A 2 x 3 grid, periodic in the second dimension only. Rank 1 sits at (0, 1).
+        let grid = Topology::Cart {
+            dims: vec![2, 3],
+            periods: vec![false, true],
+        };
+        let (sources, destinations) = neighbors_of(&grid, 1);
+        assert_eq!(destinations, vec![ProcNull, Process(4), Process(0), Process(2)]);
+        assert_eq!(sources, destinations);
+        assert_eq!(send_queues(&destinations, true),
+                   vec![(Process(4), vec![1]),
+                        (ProcNull, vec![0]),
+                        (Process(2), vec![3]),
+                        (Process(0), vec![2])]);
+
+        // Rank 3 at (1, 0) wraps around to (1, 2)
+        assert_eq!(neighbors_of(&grid, 3).1,
+                   vec![Process(0), ProcNull, Process(5), Process(4)]);
+    }
+
+    #[test]
+    fn neighbor_cart_periodic_pairs_reversed() {
+        // On a ring of two, both neighbours are the other process. It receives from below first,
+        // so the block sent up goes first.
+        let ring = Topology::Cart {
+            dims: vec![2],
+            periods: vec![true],
+        };
+        let (sources, destinations) = neighbors_of(&ring, 0);
+        assert_eq!(sources, vec![Process(1), Process(1)]);
+        assert_eq!(send_queues(&destinations, true), vec![(Process(1), vec![1, 0])]);
+
+        // A single process is its own neighbour both ways
+        let alone = Topology::Cart {
+            dims: vec![1],
+            periods: vec![true],
+        };
+        let (_, destinations) = neighbors_of(&alone, 0);
+        assert_eq!(destinations, vec![Process(0), Process(0)]);
+        assert_eq!(send_queues(&destinations, true), vec![(Process(0), vec![1, 0])]);
+    }
+
+    #[test]
+    fn neighbor_graph_order() {
+        // A star around process 0
+        let star = Topology::Graph {
+            index: vec![3, 4, 5, 6],
+            edges: vec![1, 2, 3, 0, 0, 0],
+        };
+        let (sources, destinations) = neighbors_of(&star, 0);
+        assert_eq!(destinations, vec![Process(1), Process(2), Process(3)]);
+        assert_eq!(sources, destinations);
+        assert_eq!(send_queues(&destinations, false),
+                   vec![(Process(1), vec![0]), (Process(2), vec![1]), (Process(3), vec![2])]);
+
+        // Repeated edges keep their order outside of grids
+        let double = Topology::Graph {
+            index: vec![2, 4],
+            edges: vec![1, 1, 0, 0],
+        };
+        let (_, destinations) = neighbors_of(&double, 0);
+        assert_eq!(send_queues(&destinations, false), vec![(Process(1), vec![0, 1])]);
+    }
+
+    #[test]
+    fn neighbor_dist_graph_order() {
+        let mesh = Topology::DistGraph {
+            sources: vec![2, 0],
+            destinations: vec![1, 2, 1],
+        };
+        let (sources, destinations) = neighbors_of(&mesh, 0);
+        assert_eq!(sources, vec![Process(2), Process(0)]);
+        assert_eq!(destinations, vec![Process(1), Process(2), Process(1)]);
+        assert_eq!(send_queues(&destinations, false),
+                   vec![(Process(1), vec![0, 2]), (Process(2), vec![1])]);
+    }
+}
@@ -0,0 +1,183 @@
+//! Reduction operations
+//!
+//! An `Op` combines two values into one. Reductions always call it as `op(a, b)` where `a` holds
+//! the contribution of lower ranks than `b`, so operations that are not commutative still see
+//! their operands in rank order. Buffers are combined elementwise.
+
+use std::ops::{Add, Mul, BitAnd, BitOr};
+use std::sync::Arc;
+
+pub struct Op<T> {
+    func: Arc<Fn(&T, &T) -> T + Send + Sync>,
+    commute: bool,
+}
+
+impl<T> Clone for Op<T> {
+    fn clone(&self) -> Op<T> {
+        Op {
+            func: self.func.clone(),
+            commute: self.commute,
+        }
+    }
+}
+
+impl<T: 'static> Op<T> {
+    /// Create a user defined operation. `func` must be associative. Set `commute` only if it is
+    /// also commutative, which allows reductions to combine operands in any order.
+    pub fn create<F>(func: F, commute: bool) -> Op<T>
+        where F: 'static + Fn(&T, &T) -> T + Send + Sync
+    {
+        Op {
+            func: Arc::new(func),
+            commute: commute,
+        }
+    }
+
+    pub fn is_commutative(&self) -> bool {
+        self.commute
+    }
+
+    pub fn apply(&self, a: &T, b: &T) -> T {
+        (self.func)(a, b)
+    }
+
+    /// Combine two buffers elementwise
+    pub fn apply_vec(&self, a: &[T], b: &[T]) -> Vec<T> {
+        if a.len() != b.len() {
+            panic!("Reduction buffers differ in size: {} and {}", a.len(), b.len());
+        }
+        a.iter().zip(b.iter()).map(|(x, y)| self.apply(x, y)).collect()
+    }
+}
+
+/// Operations for accumulates
+impl<T: 'static + Clone> Op<T> {
+    /// Replace the target with the origin value
+    pub fn replace() -> Op<T> {
+        Op::create(|_: &T, b: &T| b.clone(), false)
+    }
+
+    /// Keep the target value, which makes `mpi_get_accumulate` an atomic read
+    pub fn no_op() -> Op<T> {
+        Op::create(|a: &T, _: &T| a.clone(), false)
+    }
+}
+
+impl<T: 'static + Clone + Add<Output = T>> Op<T> {
+    pub fn sum() -> Op<T> {
+        Op::create(|a: &T, b: &T| a.clone() + b.clone(), true)
+    }
+}
+
+impl<T: 'static + Clone + Mul<Output = T>> Op<T> {
+    pub fn prod() -> Op<T> {
+        Op::create(|a: &T, b: &T| a.clone() * b.clone(), true)
+    }
+}
+
+impl<T: 'static + Clone + PartialOrd> Op<T> {
+    pub fn min() -> Op<T> {
+        Op::create(|a: &T, b: &T| if b < a { b.clone() } else { a.clone() }, true)
+    }
+
+    pub fn max() -> Op<T> {
+        Op::create(|a: &T, b: &T| if b > a { b.clone() } else { a.clone() }, true)
+    }
+}
+
+impl<T: 'static + Clone + BitAnd<Output = T>> Op<T> {
+    pub fn band() -> Op<T> {
+        Op::create(|a: &T, b: &T| a.clone() & b.clone(), true)
+    }
+}
+
+impl<T: 'static + Clone + BitOr<Output = T>> Op<T> {
+    pub fn bor() -> Op<T> {
+        Op::create(|a: &T, b: &T| a.clone() | b.clone(), true)
+    }
+}
+
+impl Op<bool> {
+    pub fn land() -> Op<bool> {
+        Op::create(|a: &bool, b: &bool| *a && *b, true)
+    }
+
+    pub fn lor() -> Op<bool> {
+        Op::create(|a: &bool, b: &bool| *a || *b, true)
+    }
+}
+
+/// Operations on `(value, index)` pairs. Ties are resolved in favour of the lower index.
+impl<T: 'static + Clone + PartialOrd> Op<(T, usize)> {
+    pub fn maxloc() -> Op<(T, usize)> {
+        Op::create(|a: &(T, usize), b: &(T, usize)| {
+                       if b.0 > a.0 || (b.0 == a.0 && b.1 < a.1) {
+                           b.clone()
+                       } else {
+                           a.clone()
+                       }
+                   },
+                   true)
+    }
+
+    pub fn minloc() -> Op<(T, usize)> {
+        Op::create(|a: &(T, usize), b: &(T, usize)| {
+                       if b.0 < a.0 || (b.0 == a.0 && b.1 < a.1) {
+                           b.clone()
+                       } else {
+                           a.clone()
+                       }
+                   },
+                   true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn op_builtin_elementwise() {
+        let a = vec![1, 6, 3];
+        let b = vec![4, 2, 3];
+        assert_eq!(Op::sum().apply_vec(&a, &b), vec![5, 8, 6]);
+        assert_eq!(Op::prod().apply_vec(&a, &b), vec![4, 12, 9]);
+        assert_eq!(Op::min().apply_vec(&a, &b), vec![1, 2, 3]);
+        assert_eq!(Op::max().apply_vec(&a, &b), vec![4, 6, 3]);
+        assert_eq!(Op::band().apply_vec(&a, &b), vec![0, 2, 3]);
+        assert_eq!(Op::bor().apply_vec(&a, &b), vec![5, 6, 3]);
+    }
+
+    #[test]
+    fn op_replace_and_no_op() {
+        assert_eq!(Op::replace().apply_vec(&[1, 2], &[3, 4]), vec![3, 4]);
+        assert_eq!(Op::no_op().apply_vec(&[1, 2], &[3, 4]), vec![1, 2]);
+    }
+
+    #[test]
+    fn op_logical() {
+        assert_eq!(Op::land().apply_vec(&[true, true], &[true, false]), vec![true, false]);
+        assert_eq!(Op::lor().apply_vec(&[false, false], &[true, false]), vec![true, false]);
+    }
+
+    #[test]
+    fn op_loc_ties_pick_lower_index() {
+        assert_eq!(Op::maxloc().apply(&(3.0, 2), &(3.0, 1)), (3.0, 1));
+        assert_eq!(Op::maxloc().apply(&(1.0, 0), &(3.0, 1)), (3.0, 1));
+        assert_eq!(Op::minloc().apply(&(3, 4), &(3, 5)), (3, 4));
+        assert_eq!(Op::minloc().apply(&(3, 4), &(2, 5)), (2, 5));
+    }
+
+    #[test]
+    fn op_user_defined_keeps_order() {
+        let concat = Op::create(|a: &String, b: &String| format!("{}{}", a, b), false);
+        assert!(!concat.is_commutative());
+        assert_eq!(concat.apply(&"ab".to_owned(), &"cd".to_owned()), "abcd");
+    }
+
+    #[test]
+    #[should_panic]
+    fn op_size_mismatch() {
+        Op::sum().apply_vec(&[1, 2], &[1]);
+    }
+}
@@ -0,0 +1,100 @@
+//! Connecting independent jobs
+//!
+//! One job opens a port and accepts a connection on it, another job, started by a separate
+//! mpirun, connects to that port. Both get an intercommunicator to the other job. Port names are
+//! opaque strings. They can be handed over through the name service: `mpi_publish_name` files a
+//! port under a service name that `mpi_lookup_name` finds.
+//!
+//! The name service is run by the mpirun whose port is given in `MPIRS_NAME_SERVER`, usually one
+//! started with `mpirun --name-server`. Without it, the mpirun of each job serves the names
+//! published within the job.
+//!
+//! mpirs knows no hints for ports, connections or names. The `info` arguments are accepted for
+//! the sake of the MPI interface and ignored.
+
+use rustc_serialize::json;
+use comm_request::ControlTy;
+use mpi_comm::{self, MPIComm, control, control_at, members, next_coll_tag, rank};
+use bcast::bcast;
+use info::Info;
+use utils;
+
+/// Longest port name mpirun hands out
+pub const MPI_MAX_PORT_NAME: usize = 256;
+
+/// Open a port that other jobs can connect to
+pub fn mpi_open_port(_info: &Info) -> String {
+    let port_name: Option<String> = json::decode(&control(ControlTy::OpenPort, ()))
+                                        .expect("Invalid reply");
+    port_name.expect("Port opening failed")
+}
+
+/// Close `port_name`. Accepts still waiting on it fail.
+pub fn mpi_close_port(port_name: &str) {
+    if control(ControlTy::ClosePort, port_name.to_string()) != "true" {
+        panic!("Port {} is not open", port_name);
+    }
+}
+
+/// Wait for another job to connect to `port_name`, which must have been opened by this job, and
+/// return an intercommunicator to it. Only the port name of `root` is used. If the port is not
+/// open, every process of `comm` panics. Collective over `comm`.
+pub fn mpi_comm_accept(port_name: &str, _info: &Info, root: usize, comm: MPIComm) -> MPIComm {
+    establish(ControlTy::Accept, port_name, root, comm)
+}
+
+/// Connect to `port_name`, opened by another job, and return an intercommunicator to the job
+/// accepting on it. Only the port name of `root` is used. If the port is not open or its job
+/// cannot be reached, every process of `comm` panics. Collective over `comm`.
+pub fn mpi_comm_connect(port_name: &str, _info: &Info, root: usize, comm: MPIComm) -> MPIComm {
+    establish(ControlTy::Connect, port_name, root, comm)
+}
+
+fn establish(ty: ControlTy, port_name: &str, root: usize, comm: MPIComm) -> MPIComm {
+    let tag = next_coll_tag(comm);
+    let local = members(comm);
+
+    // World ranks of the other job and the context of the intercommunicator. Only the root
+    // knows whether the port was open, so it shares the outcome before anyone panics.
+    let mut established: Option<(Vec<usize>, u64)> = None;
+    if rank(comm) == root {
+        let reply = control(ty, (port_name.to_string(), local.to_vec()));
+        established = json::decode(&reply).unwrap_or(None);
+    }
+    bcast(&mut established, root, comm, tag);
+
+    let (remote, context) = match established {
+        Some(remote) => remote,
+        None => panic!("Port {} is not open or cannot be reached", port_name),
+    };
+    mpi_comm::insert_intercomm(local.to_vec(), rank(comm), remote, context)
+}
+
+fn name_server() -> String {
+    match utils::port_from_env("MPIRS_NAME_SERVER") {
+        Some(port) => format!("127.0.0.1:{}", port),
+        None => utils::mpirun_addr().to_string(),
+    }
+}
+
+/// Publish `port_name` under `service`, which must not be published yet
+pub fn mpi_publish_name(service: &str, _info: &Info, port_name: &str) {
+    let data = (service.to_string(), port_name.to_string());
+    if control_at(&name_server(), ControlTy::Publish, data) != "true" {
+        panic!("Service {} is already published", service);
+    }
+}
+
+/// Port published under `service`, `None` if there is none
+pub fn mpi_lookup_name(service: &str, _info: &Info) -> Option<String> {
+    let reply = control_at(&name_server(), ControlTy::Lookup, service.to_string());
+    json::decode(&reply).expect("Invalid reply")
+}
+
+/// Withdraw `port_name` published under `service`
+pub fn mpi_unpublish_name(service: &str, _info: &Info, port_name: &str) {
+    let data = (service.to_string(), port_name.to_string());
+    if control_at(&name_server(), ControlTy::Unpublish, data) != "true" {
+        panic!("Service {} is not published with port {}", service, port_name);
+    }
+}
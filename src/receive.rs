@@ -1,7 +1,7 @@
 use rustc_serialize::json;
 use libc;
-use mpi_comm::MPIComm;
-use comm_request::CommRequest;
+use mpi_comm::{MPIComm, Context, p2p_context};
+use comm_request::{self, CommRequest};
 use comm_request::CommRequestType;
 use comm_request::RequestProc;
 use comm_request::MType;
@@ -12,7 +12,6 @@ use receiver_traits::Message;
 use std::fmt::Debug;
 use rustc_serialize::Encodable;
 use rustc_serialize::Decodable;
-use std::io::prelude::*;
 use std::net::TcpStream;
 use utils;
 
@@ -24,38 +23,45 @@ pub fn mpi_irecv<T>(buf: &mut T,
                     -> Receiver<CommRequest<T>>
     where T: 'static + Debug + Clone + Encodable + Decodable + Send
 {
+    if src == RequestProc::ProcNull {
+        return comm_request::proc_null(MType::MRecv);
+    }
+    irecv(src, tag, p2p_context(comm))
+}
+
+pub(crate) fn irecv<T>(src: RequestProc, tag: u64, context: Context) -> Receiver<CommRequest<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if src == RequestProc::ProcNull {
+        return comm_request::proc_null(MType::MRecv);
+    }
 
     let pid = utils::pid();
-    let commreq = CommRequest::<u32>::new(Some(src),
-                                          None,
-                                          tag,
-                                          None,
-                                          CommRequestType::Message(MType::MRecv),
-                                          pid);
+
+    let mut commreq = CommRequest::<u32>::new(Some(context.world_proc(src)),
+                                              None,
+                                              tag,
+                                              None,
+                                              CommRequestType::Message(MType::MRecv),
+                                              pid);
+    commreq.set_context(context.id());
     let commreq_json = json::encode(&commreq).unwrap();
     // create channel
     let (tx, rx) = channel::<CommRequest<T>>();
     // spawn thread
     thread::spawn(move || {
         // in thread tcpstream connect, write and read
-        let mut stream = TcpStream::connect("127.0.0.1:31337").unwrap();
-        let _ = stream.write(&commreq_json.as_bytes());
-        let mut bytes_read = [0; 2048];
-        let mut str_in = String::new();
-
-        loop {
-            let n = stream.read(&mut bytes_read).expect("Read Error:");
-            str_in = format!("{}{}",
-                             str_in,
-                             String::from_utf8(bytes_read[0..n].to_vec()).unwrap());
-
-            if n < 2048 {
-                break;
-            }
-        }
+        let mut stream = TcpStream::connect(utils::mpirun_addr()).unwrap();
+        utils::write_stream(&mut stream, &commreq_json);
+        let str_in = utils::read_stream(&mut stream);
 
         if !str_in.is_empty() {
-            tx.send(json::decode(&str_in).expect("Invalid json"));
+            let mut reply: CommRequest<T> = json::decode(&str_in).expect("Invalid json");
+            // mpirun reports the sender by its world rank
+            if let Some(src) = reply.src() {
+                reply.set_src(context.group_proc(src));
+            }
+            tx.send(reply);
         }
 
     });
@@ -70,7 +76,37 @@ pub fn mpi_recv<T>(buf: &mut T,
                    comm: MPIComm)
     where T: 'static + Debug + Clone + Encodable + Decodable + Send
 {
+    let rx = mpi_irecv(buf, src, tag, comm);
+    complete(buf, src, rx);
+}
+
+pub(crate) fn recv<T>(buf: &mut T, src: RequestProc, tag: u64, context: Context)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    complete(buf, src, irecv(src, tag, context));
+}
+
+fn complete<T>(buf: &mut T, src: RequestProc, rx: Receiver<CommRequest<T>>)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    match rx.wait() {
+        Some(data) => *buf = data,
+        // Nothing arrives from ProcNull, `buf` is left as it is
+        None if src == RequestProc::ProcNull => {}
+        None => panic!("No data!"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mpi_comm::MPI_COMM_WORLD;
 
-    let rx: Receiver<CommRequest<T>> = mpi_irecv(buf, src, tag, comm);
-    *buf = rx.wait().expect("No data!");
+    #[test]
+    fn recv_proc_null_leaves_buffer() {
+        // No mpirun runs during unit tests, so the receive must not reach for it
+        let mut buf = vec![1, 2, 3];
+        mpi_recv(&mut buf, RequestProc::ProcNull, 3, MPI_COMM_WORLD);
+        assert_eq!(buf, vec![1, 2, 3]);
+    }
 }
@@ -0,0 +1,283 @@
+//! Implements mpi_reduce and mpi_allreduce
+//!
+//! Reductions combine partial results up a binomial tree, so the root finishes after log(n)
+//! rounds instead of receiving from every rank in turn.
+//!
+//! Allreduce can also skip the root altogether: with recursive doubling every process exchanges
+//! partial results with partners at doubling distances, and the ring algorithm reduces one chunk
+//! of the vector per process before gathering the chunks around the ring. `algorithm` decides
+//! which one to use.
+//!
+//! On an intercommunicator every group receives the reduction of the other group's buffers: each
+//! group reduces to its leader, the leaders swap their results and broadcast them. A reduction to
+//! a root reduces the other group to its leader, which sends the result to the root.
+
+use rustc_serialize::Decodable;
+use mpi_comm::{MPIComm, InterRole, coll_context, remote_coll_context, next_inter_tag,
+               inter_role, mpi_comm_test_inter};
+use mpi_request::CollRequest;
+use comm_request::RequestProc;
+use std::fmt::Debug;
+use rustc_serialize::Encodable;
+use send::send;
+use receive::recv;
+use sendrecv::sendrecv;
+use bcast::bcast;
+use allgather::ring_allgather;
+use comm_rank::mpi_comm_rank;
+use num_procs::mpi_get_num_procs;
+use op::Op;
+use algorithm::{self, AllreduceAlgorithm};
+
+/// Reduce `sendbuf` of every process elementwise with `op` into `recvbuf` of `root`. `recvbuf`
+/// is left untouched on all other processes.
+pub fn mpi_reduce<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, op: &Op<T>, root: usize, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    if mpi_comm_test_inter(comm) {
+        inter_reduce(sendbuf, recvbuf, op, root, comm, tag);
+    } else {
+        reduce(sendbuf, recvbuf, op, root, comm, tag);
+    }
+}
+
+/// Start reducing `sendbuf` of every process to `root`. `wait` returns the result on `root` and
+/// an empty vector on all other processes.
+pub fn mpi_ireduce<T>(sendbuf: Vec<T>,
+                      op: &Op<T>,
+                      root: usize,
+                      comm: MPIComm)
+                      -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    let op = op.clone();
+    CollRequest::spawn(move || {
+        let mut recvbuf = Vec::new();
+        if mpi_comm_test_inter(comm) {
+            inter_reduce(&sendbuf, &mut recvbuf, &op, root, comm, tag);
+        } else {
+            reduce(&sendbuf, &mut recvbuf, &op, root, comm, tag);
+        }
+        recvbuf
+    })
+}
+
+pub(crate) fn reduce<T>(sendbuf: &[T],
+                        recvbuf: &mut Vec<T>,
+                        op: &Op<T>,
+                        root: usize,
+                        comm: MPIComm,
+                        tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+
+    // A commutative operation can use a tree rooted at `root`. Otherwise reduce in rank order to
+    // rank 0 and forward the result to `root` from there.
+    let tree_root = if op.is_commutative() { root } else { 0 };
+    let vrank = (rank + n - tree_root) % n;
+
+    let mut acc = sendbuf.to_vec();
+    let mut mask = 1;
+    while mask < n {
+        if vrank & mask != 0 {
+            let vpeer = vrank & !mask;
+            send(&acc, RequestProc::Process((vpeer + tree_root) % n), tag, coll_context(comm));
+            break;
+        }
+        let vpeer = vrank | mask;
+        if vpeer < n {
+            let mut partial: Vec<T> = Vec::new();
+            recv(&mut partial,
+                 RequestProc::Process((vpeer + tree_root) % n),
+                 tag,
+                 coll_context(comm));
+            acc = op.apply_vec(&acc, &partial);
+        }
+        mask <<= 1;
+    }
+
+    if rank == tree_root {
+        if rank == root {
+            *recvbuf = acc;
+        } else {
+            send(&acc, RequestProc::Process(root), tag, coll_context(comm));
+        }
+    } else if rank == root {
+        recv(recvbuf, RequestProc::Process(tree_root), tag, coll_context(comm));
+    }
+}
+
+/// Reduce the buffers of the other group of intercommunicator `comm` to its leader, which sends
+/// the result to `root`
+fn inter_reduce<T>(sendbuf: &[T],
+                   recvbuf: &mut Vec<T>,
+                   op: &Op<T>,
+                   root: usize,
+                   comm: MPIComm,
+                   tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    match inter_role(root, comm) {
+        InterRole::Root => recv(recvbuf, RequestProc::Process(0), tag, remote_coll_context(comm)),
+        InterRole::Idle => {}
+        InterRole::Remote(root) => {
+            let mut local = Vec::new();
+            reduce(sendbuf, &mut local, op, 0, comm, tag);
+            if mpi_comm_rank(comm) == 0 {
+                send(&local, RequestProc::Process(root), tag, remote_coll_context(comm));
+            }
+        }
+    }
+}
+
+/// Reduce `sendbuf` of every process elementwise with `op` and leave the result in `recvbuf` of
+/// all processes. On an intercommunicator, `recvbuf` receives the reduction over the remote group.
+pub fn mpi_allreduce<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, op: &Op<T>, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    allreduce(sendbuf, recvbuf, op, comm, next_inter_tag(comm));
+}
+
+/// Start reducing `sendbuf` of every process. `wait` returns the result on every process.
+pub fn mpi_iallreduce<T>(sendbuf: Vec<T>, op: &Op<T>, comm: MPIComm) -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    let op = op.clone();
+    CollRequest::spawn(move || {
+        let mut recvbuf = Vec::new();
+        allreduce(&sendbuf, &mut recvbuf, &op, comm, tag);
+        recvbuf
+    })
+}
+
+fn allreduce<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, op: &Op<T>, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if mpi_comm_test_inter(comm) {
+        return inter_allreduce(sendbuf, recvbuf, op, comm, tag);
+    }
+
+    let n = mpi_get_num_procs(comm);
+    match algorithm::allreduce(n, sendbuf.len(), op.is_commutative()) {
+        AllreduceAlgorithm::ReduceBcast => {
+            // The reduction and the broadcast use the same tree in opposite directions, so they
+            // can share a tag.
+            reduce(sendbuf, recvbuf, op, 0, comm, tag);
+            bcast(recvbuf, 0, comm, tag);
+        }
+        AllreduceAlgorithm::RecursiveDoubling => {
+            allreduce_recursive_doubling(sendbuf, recvbuf, op, comm, tag)
+        }
+        AllreduceAlgorithm::Ring => allreduce_ring(sendbuf, recvbuf, op, comm, tag),
+    }
+}
+
+fn inter_allreduce<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, op: &Op<T>, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let mut local = Vec::new();
+    reduce(sendbuf, &mut local, op, 0, comm, tag);
+    if mpi_comm_rank(comm) == 0 {
+        let leader = RequestProc::Process(0);
+        sendrecv(&local, leader, tag, recvbuf, leader, tag, remote_coll_context(comm));
+    }
+    bcast(recvbuf, 0, comm, tag);
+}
+
+fn allreduce_recursive_doubling<T>(sendbuf: &[T],
+                                   recvbuf: &mut Vec<T>,
+                                   op: &Op<T>,
+                                   comm: MPIComm,
+                                   tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+    let mut pof2 = 1;
+    while pof2 * 2 <= n {
+        pof2 *= 2;
+    }
+    let rem = n - pof2;
+
+    // The first 2 * rem ranks pair up so that a power of two processes take part in the
+    // exchange. Even ranks hand their data to the odd rank above them and wait for the result.
+    let mut acc = sendbuf.to_vec();
+    let vrank = if rank < 2 * rem {
+        if rank % 2 == 0 {
+            send(&acc, RequestProc::Process(rank + 1), tag, coll_context(comm));
+            recv(recvbuf, RequestProc::Process(rank + 1), tag, coll_context(comm));
+            return;
+        }
+        let mut lower: Vec<T> = Vec::new();
+        recv(&mut lower, RequestProc::Process(rank - 1), tag, coll_context(comm));
+        acc = op.apply_vec(&lower, &acc);
+        rank / 2
+    } else {
+        rank - rem
+    };
+
+    // Every process holds the reduction of a contiguous range of ranks, and ranges keep their
+    // order under the mapping to virtual ranks, so operands stay in rank order.
+    let mut mask = 1;
+    while mask < pof2 {
+        let vpeer = vrank ^ mask;
+        let peer = if vpeer < rem { vpeer * 2 + 1 } else { vpeer + rem };
+        let mut theirs: Vec<T> = Vec::new();
+        sendrecv(&acc,
+                 RequestProc::Process(peer),
+                 tag,
+                 &mut theirs,
+                 RequestProc::Process(peer),
+                 tag,
+                 coll_context(comm));
+        acc = if peer < rank {
+            op.apply_vec(&theirs, &acc)
+        } else {
+            op.apply_vec(&acc, &theirs)
+        };
+        mask <<= 1;
+    }
+
+    if rank < 2 * rem {
+        send(&acc, RequestProc::Process(rank - 1), tag, coll_context(comm));
+    }
+    *recvbuf = acc;
+}
+
+/// Only for commutative operations: chunks are combined in ring order, not rank order
+fn allreduce_ring<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, op: &Op<T>, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+    let count = sendbuf.len();
+    let left = RequestProc::Process((rank + n - 1) % n);
+    let right = RequestProc::Process((rank + 1) % n);
+
+    let mut chunks: Vec<Vec<T>> =
+        (0..n).map(|i| sendbuf[i * count / n..(i + 1) * count / n].to_vec()).collect();
+
+    // Pass partial chunks to the right. After n - 1 steps chunk `rank + 1` holds the
+    // contributions of every process.
+    for step in 0..n - 1 {
+        let send_idx = (rank + n - step) % n;
+        let recv_idx = (rank + n - step - 1) % n;
+        let mut incoming: Vec<T> = Vec::new();
+        sendrecv(&chunks[send_idx], right, tag, &mut incoming, left, tag, coll_context(comm));
+        chunks[recv_idx] = op.apply_vec(&incoming, &chunks[recv_idx]);
+    }
+
+    let mine = chunks.swap_remove((rank + 1) % n);
+    let reduced = ring_allgather(mine, comm, tag);
+
+    // Chunk `i` was reduced by process `i - 1`
+    recvbuf.clear();
+    for i in 0..n {
+        recvbuf.extend_from_slice(&reduced[(i + n - 1) % n]);
+    }
+}
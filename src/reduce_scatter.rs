@@ -0,0 +1,129 @@
+//! Implements mpi_reduce_scatter and mpi_reduce_scatter_block
+//!
+//! Each process only needs its own block of the reduced vector, so blocks are exchanged pairwise
+//! and reduced by their owner. Every element crosses the network once instead of being reduced
+//! on one process and sent back out to all of them.
+//!
+//! On an intercommunicator every group receives the reduction of the other group's buffers,
+//! scattered by its own receive counts: each group reduces to its leader, the leaders swap their
+//! results and scatter them within their group.
+
+use rustc_serialize::Decodable;
+use mpi_comm::{MPIComm, remote_coll_context, next_inter_tag, mpi_comm_test_inter};
+use mpi_request::CollRequest;
+use std::fmt::Debug;
+use rustc_serialize::Encodable;
+use comm_request::RequestProc;
+use sendrecv::sendrecv;
+use alltoall::pairwise_exchange;
+use reduce::reduce;
+use scatter::scatter_blocks;
+use comm_rank::mpi_comm_rank;
+use num_procs::mpi_get_num_procs;
+use op::Op;
+use layout;
+
+/// Reduce `sendbuf` of every process elementwise with `op` and scatter the result. Process `i`
+/// receives `recvcounts[i]` elements in `recvbuf`, taken in rank order from the reduced vector.
+pub fn mpi_reduce_scatter<T>(sendbuf: &[T],
+                             recvbuf: &mut Vec<T>,
+                             recvcounts: &[usize],
+                             op: &Op<T>,
+                             comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    reduce_scatter(sendbuf, recvbuf, recvcounts, op, comm, next_inter_tag(comm));
+}
+
+/// Start the reduction of `mpi_reduce_scatter`. `wait` returns the block of this process.
+pub fn mpi_ireduce_scatter<T>(sendbuf: Vec<T>,
+                              recvcounts: Vec<usize>,
+                              op: &Op<T>,
+                              comm: MPIComm)
+                              -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    let op = op.clone();
+    CollRequest::spawn(move || {
+        let mut recvbuf = Vec::new();
+        reduce_scatter(&sendbuf, &mut recvbuf, &recvcounts, &op, comm, tag);
+        recvbuf
+    })
+}
+
+fn reduce_scatter<T>(sendbuf: &[T],
+                     recvbuf: &mut Vec<T>,
+                     recvcounts: &[usize],
+                     op: &Op<T>,
+                     comm: MPIComm,
+                     tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    if recvcounts.len() != n {
+        panic!("Expected {} receive counts", n);
+    }
+
+    if mpi_comm_test_inter(comm) {
+        return inter_reduce_scatter(sendbuf, recvbuf, recvcounts, op, comm, tag);
+    }
+
+    let count = recvcounts.iter().fold(0, |sum, &x| sum + x);
+    if count != sendbuf.len() {
+        panic!("Receive counts do not add up to send buffer size");
+    }
+
+    let blocks = layout::split(sendbuf, recvcounts, &layout::packed_displs(recvcounts));
+
+    // Contributions arrive indexed by rank, so folding them in order keeps the operands of a
+    // non-commutative operation in rank order.
+    let mut contributions = pairwise_exchange(blocks, comm, tag).into_iter();
+    let first = contributions.next().expect("No contributions to reduce");
+    *recvbuf = contributions.fold(first, |acc, c| op.apply_vec(&acc, &c));
+}
+
+fn inter_reduce_scatter<T>(sendbuf: &[T],
+                           recvbuf: &mut Vec<T>,
+                           recvcounts: &[usize],
+                           op: &Op<T>,
+                           comm: MPIComm,
+                           tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let mut local = Vec::new();
+    reduce(sendbuf, &mut local, op, 0, comm, tag);
+
+    let mut blocks = None;
+    if mpi_comm_rank(comm) == 0 {
+        let leader = RequestProc::Process(0);
+        let mut remote: Vec<T> = Vec::new();
+        sendrecv(&local, leader, tag, &mut remote, leader, tag, remote_coll_context(comm));
+        blocks = Some(layout::split(&remote, recvcounts, &layout::packed_displs(recvcounts)));
+    }
+    scatter_blocks(blocks, recvbuf, 0, comm, tag);
+}
+
+/// Same as `mpi_reduce_scatter` where every process receives `recvcount` elements
+pub fn mpi_reduce_scatter_block<T>(sendbuf: &[T],
+                                   recvbuf: &mut Vec<T>,
+                                   recvcount: usize,
+                                   op: &Op<T>,
+                                   comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    mpi_reduce_scatter(sendbuf, recvbuf, &vec![recvcount; n], op, comm);
+}
+
+/// Start the reduction of `mpi_reduce_scatter_block`. `wait` returns the block of this process.
+pub fn mpi_ireduce_scatter_block<T>(sendbuf: Vec<T>,
+                                    recvcount: usize,
+                                    op: &Op<T>,
+                                    comm: MPIComm)
+                                    -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    mpi_ireduce_scatter(sendbuf, vec![recvcount; n], op, comm)
+}
@@ -0,0 +1,99 @@
+//! Implements mpi_scan and mpi_exscan
+//!
+//! Prefix reductions use recursive doubling. After the step with distance `d` every process
+//! holds the reduction of the `2d` ranks ending at itself, so the scan completes in log(n)
+//! steps. Partial results are always combined as `op(lower ranks, higher ranks)`, which keeps
+//! non-commutative operations correct.
+//!
+//! As in MPI, scans are only defined on intra-communicators and panic on an intercommunicator.
+
+use rustc_serialize::Decodable;
+use mpi_comm::{MPIComm, coll_context, next_coll_tag};
+use mpi_request::CollRequest;
+use comm_request::RequestProc;
+use std::fmt::Debug;
+use rustc_serialize::Encodable;
+use send::send;
+use receive::recv;
+use sendrecv::sendrecv;
+use comm_rank::mpi_comm_rank;
+use num_procs::mpi_get_num_procs;
+use op::Op;
+
+/// Compute the inclusive and exclusive prefix reductions ending at this process. The exclusive
+/// prefix is `None` on rank 0.
+fn prefix<T>(sendbuf: &[T], op: &Op<T>, comm: MPIComm, tag: u64) -> (Vec<T>, Option<Vec<T>>)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let n = mpi_get_num_procs(comm);
+    let rank = mpi_comm_rank(comm);
+
+    let mut partial = sendbuf.to_vec();
+    let mut exclusive: Option<Vec<T>> = None;
+    let mut d = 1;
+    while d < n {
+        let has_dest = rank + d < n;
+        let has_src = rank >= d;
+        let mut incoming: Vec<T> = Vec::new();
+
+        if has_dest && has_src {
+            sendrecv(&partial,
+                     RequestProc::Process(rank + d),
+                     tag,
+                     &mut incoming,
+                     RequestProc::Process(rank - d),
+                     tag,
+                     coll_context(comm));
+        } else if has_dest {
+            send(&partial, RequestProc::Process(rank + d), tag, coll_context(comm));
+        } else if has_src {
+            recv(&mut incoming, RequestProc::Process(rank - d), tag, coll_context(comm));
+        }
+
+        if has_src {
+            exclusive = Some(match exclusive {
+                Some(ref e) => op.apply_vec(&incoming, e),
+                None => incoming.clone(),
+            });
+            partial = op.apply_vec(&incoming, &partial);
+        }
+        d <<= 1;
+    }
+    (partial, exclusive)
+}
+
+/// Store in `recvbuf` the elementwise reduction of `sendbuf` over ranks `0..=rank`
+pub fn mpi_scan<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, op: &Op<T>, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    *recvbuf = prefix(sendbuf, op, comm, next_coll_tag(comm)).0;
+}
+
+/// Start an inclusive prefix reduction. `wait` returns the reduction over ranks `0..=rank`.
+pub fn mpi_iscan<T>(sendbuf: Vec<T>, op: &Op<T>, comm: MPIComm) -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_coll_tag(comm);
+    let op = op.clone();
+    CollRequest::spawn(move || prefix(&sendbuf, &op, comm, tag).0)
+}
+
+/// Store in `recvbuf` the elementwise reduction of `sendbuf` over ranks `0..rank`. There is
+/// nothing to reduce on rank 0, so its `recvbuf` is left untouched.
+pub fn mpi_exscan<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, op: &Op<T>, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if let Some(exclusive) = prefix(sendbuf, op, comm, next_coll_tag(comm)).1 {
+        *recvbuf = exclusive;
+    }
+}
+
+/// Start an exclusive prefix reduction. `wait` returns the reduction over ranks `0..rank`, which
+/// is empty on rank 0.
+pub fn mpi_iexscan<T>(sendbuf: Vec<T>, op: &Op<T>, comm: MPIComm) -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_coll_tag(comm);
+    let op = op.clone();
+    CollRequest::spawn(move || prefix(&sendbuf, &op, comm, tag).1.unwrap_or(Vec::new()))
+}
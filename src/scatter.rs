@@ -1,37 +1,212 @@
-use rustc_serialize::json;
+//! Implements mpi_scatter and mpi_scatterv
+//!
+//! The root sends every other process its block directly and keeps its own block locally.
+//!
+//! On an intercommunicator the root hands out blocks to the other group: it sends them all to the
+//! leader of that group, which scatters them within its group.
+
 use rustc_serialize::Decodable;
-use mpi_comm::MPIComm;
+use mpi_comm::{MPIComm, InterRole, MPI_ROOT, coll_context, remote_coll_context, next_inter_tag,
+               inter_role, mpi_comm_test_inter};
+use mpi_request::CollRequest;
 use comm_request::RequestProc;
 use std::fmt::Debug;
 use rustc_serialize::Encodable;
-use send::mpi_send;
-use receive::mpi_recv;
+use send::send;
+use receive::recv;
 use comm_rank::mpi_comm_rank;
-use num_procs::mpi_get_num_procs;
-use utils;
-
-// Functions in the Scatter module
-pub fn mpi_scatterv<T>(sendbuf: Vec<T>, sendcount: Vec<usize>,
-			 displs: Vec<usize>, recvbuf: &mut T, root: usize, comm: MPIComm) 
-			 where T: 'static + Debug + Clone + Encodable + Decodable + Send {
-
-		let n = mpi_get_num_procs();
-		let tag = u64::max_value();
-		if mpi_comm_rank() == root {
-			// verify if sum of sendcount equals size of sendbuf
-			let count = sendcount.iter().fold(0, |mut sum, &x| {sum += x; sum});
-
-			if count != sendbuf.len() {
-				panic!("Sendcount does not add upto send buffer size");
-			}
-
-			for i in 0..n {
-					let buf = sendbuf[displs[i]..sendcount[i]].to_vec();
-			    mpi_send(&json::encode(&buf).unwrap(), RequestProc::Process(i), tag, comm);
-			}
-		}
-		
-		mpi_recv(recvbuf, RequestProc::Process(root), tag, comm);
-		
+use num_procs::{mpi_get_num_procs, mpi_comm_remote_size};
+use layout;
+
+/// Split `sendbuf` of `root` in n equal blocks and send block `i` to process `i`, which stores
+/// it in `recvbuf`. `sendbuf` is only read on `root`.
+pub fn mpi_scatter<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, root: usize, comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    scatter(sendbuf, recvbuf, root, comm, next_inter_tag(comm));
+}
+
+/// Start scattering `sendbuf` of `root`. `wait` returns the block of this process.
+pub fn mpi_iscatter<T>(sendbuf: Vec<T>, root: usize, comm: MPIComm) -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = Vec::new();
+        scatter(&sendbuf, &mut recvbuf, root, comm, tag);
+        recvbuf
+    })
 }
 
+/// Send `sendcounts[i]` elements starting at `displs[i]` of `sendbuf` of `root` to process `i`,
+/// which stores them in `recvbuf`. `sendbuf`, `sendcounts` and `displs` are only read on `root`.
+pub fn mpi_scatterv<T>(sendbuf: &[T],
+                       sendcounts: &[usize],
+                       displs: &[usize],
+                       recvbuf: &mut Vec<T>,
+                       root: usize,
+                       comm: MPIComm)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    scatterv(sendbuf, sendcounts, displs, recvbuf, root, comm, next_inter_tag(comm));
+}
+
+/// Start scattering the blocks of `sendbuf` of `root`. `wait` returns the block of this process.
+pub fn mpi_iscatterv<T>(sendbuf: Vec<T>,
+                        sendcounts: Vec<usize>,
+                        displs: Vec<usize>,
+                        root: usize,
+                        comm: MPIComm)
+                        -> CollRequest<Vec<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let tag = next_inter_tag(comm);
+    CollRequest::spawn(move || {
+        let mut recvbuf = Vec::new();
+        scatterv(&sendbuf, &sendcounts, &displs, &mut recvbuf, root, comm, tag);
+        recvbuf
+    })
+}
+
+fn scatter<T>(sendbuf: &[T], recvbuf: &mut Vec<T>, root: usize, comm: MPIComm, tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let blocks = if is_root(root, comm) {
+        Some(layout::split_equal(sendbuf, receivers(comm)))
+    } else {
+        None
+    };
+    if mpi_comm_test_inter(comm) {
+        inter_scatter_blocks(blocks, recvbuf, root, comm, tag);
+    } else {
+        scatter_blocks(blocks, recvbuf, root, comm, tag);
+    }
+}
+
+fn scatterv<T>(sendbuf: &[T],
+               sendcounts: &[usize],
+               displs: &[usize],
+               recvbuf: &mut Vec<T>,
+               root: usize,
+               comm: MPIComm,
+               tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let blocks = if is_root(root, comm) {
+        layout::check_layout(sendcounts, displs, receivers(comm));
+        Some(layout::split(sendbuf, sendcounts, displs))
+    } else {
+        None
+    };
+    if mpi_comm_test_inter(comm) {
+        inter_scatter_blocks(blocks, recvbuf, root, comm, tag);
+    } else {
+        scatter_blocks(blocks, recvbuf, root, comm, tag);
+    }
+}
+
+/// Whether this process hands out the blocks
+fn is_root(root: usize, comm: MPIComm) -> bool {
+    if mpi_comm_test_inter(comm) {
+        root == MPI_ROOT
+    } else {
+        mpi_comm_rank(comm) == root
+    }
+}
+
+/// Number of processes the root hands out blocks to: the other group on an intercommunicator
+fn receivers(comm: MPIComm) -> usize {
+    if mpi_comm_test_inter(comm) {
+        mpi_comm_remote_size(comm)
+    } else {
+        mpi_get_num_procs(comm)
+    }
+}
+
+/// `blocks` is only given on `root`
+pub(crate) fn scatter_blocks<T>(blocks: Option<Vec<Vec<T>>>,
+                                recvbuf: &mut Vec<T>,
+                                root: usize,
+                                comm: MPIComm,
+                                tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    match blocks {
+        Some(blocks) => {
+            *recvbuf = deal(blocks, root, |i, block| {
+                send(&block, RequestProc::Process(i), tag, coll_context(comm));
+            });
+        }
+        None => recv(recvbuf, RequestProc::Process(root), tag, coll_context(comm)),
+    }
+}
+
+/// `blocks`, one for every process of the other group of intercommunicator `comm`, are only given
+/// on `root`
+fn inter_scatter_blocks<T>(blocks: Option<Vec<Vec<T>>>,
+                           recvbuf: &mut Vec<T>,
+                           root: usize,
+                           comm: MPIComm,
+                           tag: u64)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    match inter_role(root, comm) {
+        InterRole::Root => {
+            let blocks = blocks.expect("No blocks to scatter");
+            send(&blocks, RequestProc::Process(0), tag, remote_coll_context(comm));
+        }
+        InterRole::Idle => {}
+        InterRole::Remote(root) => {
+            let mut blocks = None;
+            if mpi_comm_rank(comm) == 0 {
+                let mut received: Vec<Vec<T>> = Vec::new();
+                recv(&mut received, RequestProc::Process(root), tag, remote_coll_context(comm));
+                blocks = Some(received);
+            }
+            scatter_blocks(blocks, recvbuf, 0, comm, tag);
+        }
+    }
+}
+
+/// Hand block `i` to `send_to` for every process `i` but `root`, in rank order, and return the
+/// block of `root`
+fn deal<T, F>(blocks: Vec<Vec<T>>, root: usize, mut send_to: F) -> Vec<T>
+    where F: FnMut(usize, Vec<T>)
+{
+    let mut own = Vec::new();
+    for (i, block) in blocks.into_iter().enumerate() {
+        if i == root {
+            own = block;
+        } else {
+            send_to(i, block);
+        }
+    }
+    own
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scatter_keeps_root_block() {
+        let blocks = layout::split_equal(&[0, 1, 2, 3, 4, 5], 3);
+        let mut sent = Vec::new();
+        let own = deal(blocks, 2, |i, block| sent.push((i, block)));
+        assert_eq!(own, vec![4, 5]);
+        assert_eq!(sent, vec![(0, vec![0, 1]), (1, vec![2, 3])]);
+    }
+
+    #[test]
+    fn scatterv_uneven_counts() {
+        let counts = [2, 0, 3, 1];
+        let displs = [4, 0, 0, 3];
+        layout::check_layout(&counts, &displs, 4);
+        let blocks = layout::split(&['a', 'b', 'c', 'd', 'e', 'f'], &counts, &displs);
+        let mut sent = Vec::new();
+        let own = deal(blocks, 1, |i, block| sent.push((i, block)));
+        assert_eq!(own, Vec::<char>::new());
+        assert_eq!(sent,
+                   vec![(0, vec!['e', 'f']), (2, vec!['a', 'b', 'c']), (3, vec!['d'])]);
+    }
+}
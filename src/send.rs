@@ -1,7 +1,7 @@
 use rustc_serialize::json;
 use libc;
-use mpi_comm::MPIComm;
-use comm_request::CommRequest;
+use mpi_comm::{MPIComm, Context, p2p_context};
+use comm_request::{self, CommRequest};
 use comm_request::CommRequestType;
 use comm_request::RequestProc;
 use comm_request::MType;
@@ -12,7 +12,6 @@ use receiver_traits::Message;
 use std::fmt::Debug;
 use rustc_serialize::Encodable;
 use rustc_serialize::Decodable;
-use std::io::prelude::*;
 use std::net::TcpStream;
 use utils;
 
@@ -25,37 +24,49 @@ pub fn mpi_isend<T>(buf: &T,
                     -> Receiver<CommRequest<T>>
     where T: 'static + Debug + Clone + Encodable + Decodable + Send
 {
+    if dest == RequestProc::ProcNull {
+        return comm_request::proc_null(MType::MSend);
+    }
+    isend(buf, dest, tag, p2p_context(comm))
+}
+
+pub(crate) fn isend<T>(buf: &T,
+                       dest: RequestProc,
+                       tag: u64,
+                       context: Context)
+                       -> Receiver<CommRequest<T>>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if dest == RequestProc::ProcNull {
+        return comm_request::proc_null(MType::MSend);
+    }
+
     let pid = utils::pid();
-    let commreq = CommRequest::<T>::new(None,
-                                       Some(dest),
-                                       tag,
-                                       Some(buf.clone()),
-                                       CommRequestType::Message(MType::MSend),
-                                       pid);
+
+    let mut commreq = CommRequest::<T>::new(None,
+                                           Some(context.world_proc(dest)),
+                                           tag,
+                                           Some(buf.clone()),
+                                           CommRequestType::Message(MType::MSend),
+                                           pid);
+    commreq.set_context(context.id());
     let commreq_json = json::encode(&commreq).unwrap();
     // create channel
     let (tx, rx) = channel::<CommRequest<T>>();
     // spawn thread
     thread::spawn(move || {
         // in thread tcpstream connect, write and read
-        let mut stream = TcpStream::connect("127.0.0.1:31337").unwrap();
-        let _ = stream.write(&commreq_json.as_bytes());
-        let mut bytes_read = [0; 2048];
-        let mut str_in = String::new();
-
-        loop {
-            let n = stream.read(&mut bytes_read).expect("Read Error:");
-            str_in = format!("{}{}",
-                             str_in,
-                             String::from_utf8(bytes_read[0..n].to_vec()).unwrap());
-
-            if n < 2048 {
-                break;
-            }
-        }
+        let mut stream = TcpStream::connect(utils::mpirun_addr()).unwrap();
+        utils::write_stream(&mut stream, &commreq_json);
+        let str_in = utils::read_stream(&mut stream);
 
         if !str_in.is_empty() {
-            tx.send(json::decode(&str_in).expect("Invalid json"));
+            // mpirun answers null when it cannot pass the message on to the job of `dest`
+            let ack: Option<CommRequest<T>> = json::decode(&str_in).expect("Invalid json");
+            let ack = ack.unwrap_or_else(|| {
+                panic!("Message for {:?} could not be delivered to its job", dest)
+            });
+            tx.send(ack);
         }
 
     });
@@ -70,6 +81,12 @@ pub fn mpi_send<T>(buf: &T,
                    comm: MPIComm)
     where T: 'static + Debug + Clone + Encodable + Decodable + Send
 {
-    let rx: Receiver<CommRequest<T>> = mpi_isend(buf, dest, tag, comm);
+    mpi_isend(buf, dest, tag, comm).wait();
+}
+
+pub(crate) fn send<T>(buf: &T, dest: RequestProc, tag: u64, context: Context)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let rx: Receiver<CommRequest<T>> = isend(buf, dest, tag, context);
     rx.wait();
 }
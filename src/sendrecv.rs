@@ -0,0 +1,42 @@
+//! Implements mpi_sendrecv
+//!
+//! Sends are only acknowledged once a matching receive is posted, so two processes that both
+//! send to each other before receiving would deadlock. `mpi_sendrecv` posts the send in the
+//! background before blocking on the receive.
+
+use rustc_serialize::Decodable;
+use mpi_comm::{MPIComm, Context, p2p_context};
+use comm_request::RequestProc;
+use std::fmt::Debug;
+use rustc_serialize::Encodable;
+use send::isend;
+use receive::recv;
+use receiver_traits::Message;
+
+pub fn mpi_sendrecv<S, R>(sendbuf: &S,
+                          dest: RequestProc,
+                          sendtag: u64,
+                          recvbuf: &mut R,
+                          src: RequestProc,
+                          recvtag: u64,
+                          comm: MPIComm)
+    where S: 'static + Debug + Clone + Encodable + Decodable + Send,
+          R: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    sendrecv(sendbuf, dest, sendtag, recvbuf, src, recvtag, p2p_context(comm));
+}
+
+pub(crate) fn sendrecv<S, R>(sendbuf: &S,
+                             dest: RequestProc,
+                             sendtag: u64,
+                             recvbuf: &mut R,
+                             src: RequestProc,
+                             recvtag: u64,
+                             context: Context)
+    where S: 'static + Debug + Clone + Encodable + Decodable + Send,
+          R: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let rx = isend(sendbuf, dest, sendtag, context.clone());
+    recv(recvbuf, src, recvtag, context);
+    rx.wait();
+}
@@ -0,0 +1,96 @@
+//! Dynamic process creation
+//!
+//! mpirun starts the new processes on behalf of the root of the spawning communicator. They get
+//! a `MPI_COMM_WORLD` of their own and reach their parents through `mpi_comm_get_parent`, the
+//! parents through the intercommunicator `mpi_comm_spawn` returns.
+//!
+//! Of the info keys reserved by the MPI standard, `wdir` is supported: it sets the working
+//! directory of the new processes. Other keys are ignored.
+
+use rustc_serialize::json;
+use comm_request::{ControlTy, SpawnCommand};
+use mpi_comm::{self, MPIComm, control, members, next_coll_tag, rank};
+use bcast::bcast;
+use info::{self, Info};
+
+/// Start `maxprocs` processes running `command` with arguments `argv` and return an
+/// intercommunicator to them. Only the arguments of `root` are used. Collective over `comm`.
+pub fn mpi_comm_spawn(command: &str,
+                      argv: &[&str],
+                      maxprocs: usize,
+                      info: &Info,
+                      root: usize,
+                      comm: MPIComm)
+                      -> MPIComm {
+    mpi_comm_spawn_multiple(&[command], &[argv], &[maxprocs], &[info.clone()], root, comm)
+}
+
+/// Start `maxprocs[i]` processes running `commands[i]` with arguments `argv[i]` for every `i`
+/// and return an intercommunicator to them. The new processes share one `MPI_COMM_WORLD`, ranked
+/// in the order of the commands. Only the arguments of `root` are used. If the spawn fails, every
+/// process of `comm` panics. Collective over `comm`.
+pub fn mpi_comm_spawn_multiple(commands: &[&str],
+                               argv: &[&[&str]],
+                               maxprocs: &[usize],
+                               info: &[Info],
+                               root: usize,
+                               comm: MPIComm)
+                               -> MPIComm {
+    let tag = next_coll_tag(comm);
+    let parent = members(comm);
+
+    // World ranks of the children and the context of the intercommunicator, or why the spawn
+    // failed. Only the root knows, so it shares either before anyone panics.
+    let mut spawned: (Option<(Vec<usize>, u64)>, String) = (None, String::new());
+    if rank(comm) == root {
+        spawned = match spawn(commands, argv, maxprocs, info, &parent) {
+            Ok(children) => (Some(children), String::new()),
+            Err(e) => (None, e),
+        };
+    }
+    bcast(&mut spawned, root, comm, tag);
+
+    let (children, context) = match spawned {
+        (Some(children), _) => children,
+        (None, e) => panic!("{}", e),
+    };
+    mpi_comm::insert_intercomm(parent.to_vec(), rank(comm), children, context)
+}
+
+/// Ask mpirun to start the processes. Returns their world ranks and the context of the
+/// intercommunicator.
+fn spawn(commands: &[&str],
+         argv: &[&[&str]],
+         maxprocs: &[usize],
+         info: &[Info],
+         parent: &[usize])
+         -> Result<(Vec<usize>, u64), String> {
+    if argv.len() != commands.len() || maxprocs.len() != commands.len() ||
+       info.len() != commands.len() {
+        return Err(format!("Spawning {} commands needs as many argument lists, counts and infos",
+                           commands.len()));
+    }
+
+    let spawn: Vec<SpawnCommand> = (0..commands.len())
+                                       .map(|i| {
+                                           SpawnCommand {
+                                               command: commands[i].to_string(),
+                                               args: argv[i]
+                                                         .iter()
+                                                         .map(|a| a.to_string())
+                                                         .collect(),
+                                               maxprocs: maxprocs[i],
+                                               wdir: info::mpi_info_get(&info[i], "wdir"),
+                                           }
+                                       })
+                                       .collect();
+    let reply = control(ControlTy::Spawn, (spawn, parent.to_vec()));
+    let spawned: Option<(Vec<usize>, u64)> = json::decode(&reply).unwrap_or(None);
+    spawned.ok_or_else(|| format!("mpirun failed to spawn {:?}", commands))
+}
+
+/// Intercommunicator to the processes that spawned the calling one. `None` for the processes
+/// mpirun started itself.
+pub fn mpi_comm_get_parent() -> Option<MPIComm> {
+    mpi_comm::parent()
+}
@@ -0,0 +1,521 @@
+//! Process topologies
+//!
+//! A topology describes how the processes of a communicator are connected, so applications can
+//! ask for their neighbours instead of computing them from ranks. A Cartesian topology lays the
+//! processes out on a grid in row-major order: the last dimension varies fastest.
+//!
+//! A graph topology lists the neighbours of every process, and every process knows the whole
+//! graph. A distributed graph only tells each process about its own edges, which scales to
+//! large meshes: the processes it receives from and those it sends to.
+
+use mpi_comm::{self, MPIComm};
+use comm_request::RequestProc;
+use comm_rank::mpi_comm_rank;
+use num_procs::mpi_get_num_procs;
+use allgather::ring_allgather;
+use info::Info;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Topology {
+    Cart { dims: Vec<usize>, periods: Vec<bool> },
+    Graph { index: Vec<usize>, edges: Vec<usize> },
+    DistGraph {
+        sources: Vec<usize>,
+        destinations: Vec<usize>,
+    },
+}
+
+/// Result of `mpi_topo_test`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopoType {
+    Cart,
+    Graph,
+    DistGraph,
+}
+
+/// Kind of topology attached to `comm`, `None` if it has none
+pub fn mpi_topo_test(comm: MPIComm) -> Option<TopoType> {
+    mpi_comm::topology(comm).map(|t| {
+        match t {
+            Topology::Cart { .. } => TopoType::Cart,
+            Topology::Graph { .. } => TopoType::Graph,
+            Topology::DistGraph { .. } => TopoType::DistGraph,
+        }
+    })
+}
+
+/// Fill the zero entries of `dims` so that the product of all entries is `nnodes` and the filled
+/// entries are as close to each other as possible, in non-increasing order. Non-zero entries are
+/// kept as they are.
+pub fn mpi_dims_create(nnodes: usize, dims: &mut [usize]) {
+    let fixed: usize = dims.iter().filter(|&&d| d != 0).product();
+    if fixed == 0 || nnodes % fixed != 0 {
+        panic!("Cannot split {} processes with the fixed dimensions {:?}", nnodes, dims);
+    }
+
+    let free: Vec<usize> = (0..dims.len()).filter(|&i| dims[i] == 0).collect();
+    let mut rest = nnodes / fixed;
+    if free.is_empty() {
+        if rest != 1 {
+            panic!("Dimensions {:?} do not hold {} processes", dims, nnodes);
+        }
+        return;
+    }
+
+    let mut factors = Vec::new();
+    let mut p = 2;
+    while p * p <= rest {
+        while rest % p == 0 {
+            factors.push(p);
+            rest /= p;
+        }
+        p += 1;
+    }
+    if rest > 1 {
+        factors.push(rest);
+    }
+
+    // Hand out the largest factors first, each to the smallest dimension so far
+    let mut sizes = vec![1; free.len()];
+    for f in factors.into_iter().rev() {
+        let smallest = (0..sizes.len()).min_by_key(|&i| sizes[i]).unwrap();
+        sizes[smallest] *= f;
+    }
+    sizes.sort_by(|a, b| b.cmp(a));
+    for (&i, size) in free.iter().zip(sizes) {
+        dims[i] = size;
+    }
+}
+
+/// Lay the processes of `comm` out on a grid with the given dimensions. `periods` tells for each
+/// dimension whether it wraps around. The processes keep their order; those with a rank beyond
+/// the size of the grid get no communicator. Collective over `comm`.
+pub fn mpi_cart_create(comm: MPIComm, dims: &[usize], periods: &[bool]) -> Option<MPIComm> {
+    mpi_cart_create_with_info(comm, dims, periods, &Info::default())
+}
+
+/// Same as `mpi_cart_create`, the grid getting the hints of `info`
+pub fn mpi_cart_create_with_info(comm: MPIComm,
+                                 dims: &[usize],
+                                 periods: &[bool],
+                                 info: &Info)
+                                 -> Option<MPIComm> {
+    if dims.len() != periods.len() {
+        panic!("{} dimensions but {} periods", dims.len(), periods.len());
+    }
+    let n: usize = dims.iter().product();
+    if n > mpi_get_num_procs(comm) {
+        panic!("Grid {:?} is larger than the communicator", dims);
+    }
+
+    let rank = mpi_comm_rank(comm);
+    let color = if rank < n { Some(0) } else { None };
+    let cart = mpi_comm::mpi_comm_split_with_info(comm, color, rank, info);
+    if let Some(cart) = cart {
+        mpi_comm::set_topology(cart,
+                               Topology::Cart {
+                                   dims: dims.to_vec(),
+                                   periods: periods.to_vec(),
+                               });
+    }
+    cart
+}
+
+fn cart(comm: MPIComm) -> (Vec<usize>, Vec<bool>) {
+    match mpi_comm::topology(comm) {
+        Some(Topology::Cart { dims, periods }) => (dims, periods),
+        _ => panic!("Communicator {} has no Cartesian topology", comm),
+    }
+}
+
+/// Number of dimensions of Cartesian communicator `comm`
+pub fn mpi_cartdim_get(comm: MPIComm) -> usize {
+    cart(comm).0.len()
+}
+
+/// Dimensions and periods of Cartesian communicator `comm`, and the coordinates of the calling
+/// process
+pub fn mpi_cart_get(comm: MPIComm) -> (Vec<usize>, Vec<bool>, Vec<usize>) {
+    let (dims, periods) = cart(comm);
+    let coords = coords_of(&dims, mpi_comm_rank(comm));
+    (dims, periods, coords)
+}
+
+/// Rank of the process at `coords`. Coordinates outside the grid wrap around in periodic
+/// dimensions.
+pub fn mpi_cart_rank(comm: MPIComm, coords: &[isize]) -> usize {
+    let (dims, periods) = cart(comm);
+    match rank_of(&dims, &periods, coords) {
+        Some(rank) => rank,
+        None => panic!("Coordinates {:?} outside the grid {:?}", coords, dims),
+    }
+}
+
+/// Coordinates of the process with `rank`
+pub fn mpi_cart_coords(comm: MPIComm, rank: usize) -> Vec<usize> {
+    let (dims, _) = cart(comm);
+    if rank >= dims.iter().product() {
+        panic!("Rank {} outside the grid {:?}", rank, dims);
+    }
+    coords_of(&dims, rank)
+}
+
+/// Neighbours of the calling process along `direction`: the process `disp` steps back, which
+/// sends to it, and the process `disp` steps ahead, which it sends to. A neighbour beyond the edge
+/// of a non-periodic dimension is `RequestProc::ProcNull`.
+pub fn mpi_cart_shift(comm: MPIComm,
+                      direction: usize,
+                      disp: isize)
+                      -> (RequestProc, RequestProc) {
+    let (dims, periods) = cart(comm);
+    shift(&dims, &periods, mpi_comm_rank(comm), direction, disp)
+}
+
+fn shift(dims: &[usize],
+         periods: &[bool],
+         rank: usize,
+         direction: usize,
+         disp: isize)
+         -> (RequestProc, RequestProc) {
+    if direction >= dims.len() {
+        panic!("Direction {} of a grid with {} dimensions", direction, dims.len());
+    }
+
+    let coords: Vec<isize> = coords_of(dims, rank).into_iter().map(|c| c as isize).collect();
+    let shifted = |d: isize| {
+        let mut c = coords.clone();
+        c[direction] += d;
+        rank_of(dims, periods, &c).map_or(RequestProc::ProcNull, RequestProc::Process)
+    };
+    (shifted(-disp), shifted(disp))
+}
+
+/// Slice Cartesian communicator `comm` into lower-dimensional grids that keep the dimensions
+/// with `remain_dims` set. Each process gets the grid through its own coordinates. Collective
+/// over `comm`.
+pub fn mpi_cart_sub(comm: MPIComm, remain_dims: &[bool]) -> MPIComm {
+    let (dims, periods) = cart(comm);
+    if remain_dims.len() != dims.len() {
+        panic!("{} dimensions to keep of a grid with {}", remain_dims.len(), dims.len());
+    }
+
+    // Processes sharing the coordinates of the dropped dimensions form a grid. Their ranks in
+    // `comm` are already in row-major order of the kept dimensions.
+    let rank = mpi_comm_rank(comm);
+    let coords = coords_of(&dims, rank);
+    let color = (0..dims.len())
+                    .filter(|&i| !remain_dims[i])
+                    .fold(0, |color, i| color * dims[i] + coords[i]);
+    let sub = mpi_comm::mpi_comm_split(comm, Some(color), rank).unwrap();
+
+    let kept: Vec<usize> = (0..dims.len()).filter(|&i| remain_dims[i]).collect();
+    mpi_comm::set_topology(sub,
+                           Topology::Cart {
+                               dims: kept.iter().map(|&i| dims[i]).collect(),
+                               periods: kept.iter().map(|&i| periods[i]).collect(),
+                           });
+    sub
+}
+
+/// Attach a graph to the processes of `comm`. The neighbours of the process with rank `i` are
+/// `edges[index[i - 1]..index[i]]`, those of rank 0 start at the front of `edges`. All processes
+/// pass the same graph. The processes keep their order; those with a rank beyond the number of
+/// nodes get no communicator. Collective over `comm`.
+pub fn mpi_graph_create(comm: MPIComm, index: &[usize], edges: &[usize]) -> Option<MPIComm> {
+    mpi_graph_create_with_info(comm, index, edges, &Info::default())
+}
+
+/// Same as `mpi_graph_create`, the graph getting the hints of `info`
+pub fn mpi_graph_create_with_info(comm: MPIComm,
+                                  index: &[usize],
+                                  edges: &[usize],
+                                  info: &Info)
+                                  -> Option<MPIComm> {
+    let n = index.len();
+    if n > mpi_get_num_procs(comm) {
+        panic!("Graph with {} nodes is larger than the communicator", n);
+    }
+    check_graph(index, edges);
+
+    let rank = mpi_comm_rank(comm);
+    let color = if rank < n { Some(0) } else { None };
+    let graph = mpi_comm::mpi_comm_split_with_info(comm, color, rank, info);
+    if let Some(graph) = graph {
+        mpi_comm::set_topology(graph,
+                               Topology::Graph {
+                                   index: index.to_vec(),
+                                   edges: edges.to_vec(),
+                               });
+    }
+    graph
+}
+
+fn graph(comm: MPIComm) -> (Vec<usize>, Vec<usize>) {
+    match mpi_comm::topology(comm) {
+        Some(Topology::Graph { index, edges }) => (index, edges),
+        _ => panic!("Communicator {} has no graph topology", comm),
+    }
+}
+
+/// Number of nodes and edges of graph communicator `comm`
+pub fn mpi_graphdims_get(comm: MPIComm) -> (usize, usize) {
+    let (index, edges) = graph(comm);
+    (index.len(), edges.len())
+}
+
+/// `index` and `edges` of graph communicator `comm`, as passed to `mpi_graph_create`
+pub fn mpi_graph_get(comm: MPIComm) -> (Vec<usize>, Vec<usize>) {
+    graph(comm)
+}
+
+/// Number of neighbours of the process with `rank` in graph communicator `comm`
+pub fn mpi_graph_neighbors_count(comm: MPIComm, rank: usize) -> usize {
+    let (index, edges) = graph(comm);
+    graph_neighbors(&index, &edges, rank).len()
+}
+
+/// Neighbours of the process with `rank` in graph communicator `comm`
+pub fn mpi_graph_neighbors(comm: MPIComm, rank: usize) -> Vec<usize> {
+    let (index, edges) = graph(comm);
+    graph_neighbors(&index, &edges, rank).to_vec()
+}
+
+/// Attach a distributed graph to the processes of `comm`. Each process lists the processes it
+/// receives from and those it sends to; an edge must be listed by both of its ends, which is
+/// checked by gathering the lists of all processes once. The graph keeps the info hints of `comm`.
+/// Collective over `comm`.
+pub fn mpi_dist_graph_create_adjacent(comm: MPIComm,
+                                      sources: &[usize],
+                                      destinations: &[usize])
+                                      -> MPIComm {
+    let info = mpi_comm::mpi_comm_get_info(comm);
+    mpi_dist_graph_create_adjacent_with_info(comm, sources, destinations, &info)
+}
+
+/// Same as `mpi_dist_graph_create_adjacent`, the graph getting the hints of `info` instead
+pub fn mpi_dist_graph_create_adjacent_with_info(comm: MPIComm,
+                                                sources: &[usize],
+                                                destinations: &[usize],
+                                                info: &Info)
+                                                -> MPIComm {
+    // Every process checks the edges of all, so a mismatch fails everywhere at once instead of
+    // hanging the neighbourhood collectives later
+    let lists = ring_allgather(vec![(sources.to_vec(), destinations.to_vec())],
+                               comm,
+                               mpi_comm::next_coll_tag(comm));
+    check_dist_graph(&lists.into_iter().map(|mut l| l.remove(0)).collect::<Vec<_>>());
+
+    let graph = mpi_comm::mpi_comm_dup_with_info(comm, info);
+    mpi_comm::set_topology(graph,
+                           Topology::DistGraph {
+                               sources: sources.to_vec(),
+                               destinations: destinations.to_vec(),
+                           });
+    graph
+}
+
+fn dist_graph(comm: MPIComm) -> (Vec<usize>, Vec<usize>) {
+    match mpi_comm::topology(comm) {
+        Some(Topology::DistGraph { sources, destinations }) => (sources, destinations),
+        _ => panic!("Communicator {} has no distributed graph topology", comm),
+    }
+}
+
+/// Number of processes the calling process receives from and sends to in distributed graph
+/// communicator `comm`
+pub fn mpi_dist_graph_neighbors_count(comm: MPIComm) -> (usize, usize) {
+    let (sources, destinations) = dist_graph(comm);
+    (sources.len(), destinations.len())
+}
+
+/// Processes the calling process receives from and sends to in distributed graph communicator
+/// `comm`, in the order they were given
+pub fn mpi_dist_graph_neighbors(comm: MPIComm) -> (Vec<usize>, Vec<usize>) {
+    dist_graph(comm)
+}
+
+/// Processes the calling process receives from and sends to in the neighbourhood collectives. On
+/// a Cartesian grid both list the neighbour below and the one above in every dimension, and
+/// neighbours beyond the edge of a non-periodic dimension are `RequestProc::ProcNull`.
+pub(crate) fn neighbors(comm: MPIComm) -> (Vec<RequestProc>, Vec<RequestProc>) {
+    match mpi_comm::topology(comm) {
+        Some(topology) => neighbors_of(&topology, mpi_comm_rank(comm)),
+        None => panic!("Communicator {} has no topology", comm),
+    }
+}
+
+/// Same as `neighbors`, for the process with `rank` under `topology`
+pub(crate) fn neighbors_of(topology: &Topology,
+                           rank: usize)
+                           -> (Vec<RequestProc>, Vec<RequestProc>) {
+    let procs = |ranks: &[usize]| -> Vec<RequestProc> {
+        ranks.iter().map(|&r| RequestProc::Process(r)).collect()
+    };
+    match *topology {
+        Topology::Cart { ref dims, ref periods } => {
+            let mut list = Vec::with_capacity(2 * dims.len());
+            for direction in 0..dims.len() {
+                let (below, above) = shift(dims, periods, rank, direction, 1);
+                list.push(below);
+                list.push(above);
+            }
+            (list.clone(), list)
+        }
+        Topology::Graph { ref index, ref edges } => {
+            let list = procs(graph_neighbors(index, edges, rank));
+            (list.clone(), list)
+        }
+        Topology::DistGraph { ref sources, ref destinations } => {
+            (procs(sources), procs(destinations))
+        }
+    }
+}
+
+/// Check that every edge of a distributed graph, given as the sources and destinations of each
+/// process, is listed by both of its ends as often
+fn check_dist_graph(lists: &[(Vec<usize>, Vec<usize>)]) {
+    let n = lists.len();
+    for (p, &(ref sources, ref destinations)) in lists.iter().enumerate() {
+        if let Some(r) = sources.iter().chain(destinations).find(|&&r| r >= n) {
+            panic!("Process {} lists rank {}, out of range for a communicator of {}", p, r, n);
+        }
+    }
+
+    let count = |list: &[usize], r: usize| list.iter().filter(|&&x| x == r).count();
+    for (p, &(ref sources, ref destinations)) in lists.iter().enumerate() {
+        if let Some(&s) = sources.iter().find(|&&s| count(&lists[s].1, p) != count(sources, s)) {
+            panic!("Process {} receives from {}, which does not send to it as often", p, s);
+        }
+        if let Some(&d) = destinations.iter()
+                                      .find(|&&d| count(&lists[d].0, p) != count(destinations, d)) {
+            panic!("Process {} sends to {}, which does not receive from it as often", p, d);
+        }
+    }
+}
+
+fn check_graph(index: &[usize], edges: &[usize]) {
+    if index.windows(2).any(|w| w[0] > w[1]) || index.last().cloned().unwrap_or(0) != edges.len() {
+        panic!("Invalid graph index {:?} for {} edges", index, edges.len());
+    }
+    if let Some(e) = edges.iter().find(|&&e| e >= index.len()) {
+        panic!("Edge to node {} of a graph with {} nodes", e, index.len());
+    }
+}
+
+fn graph_neighbors<'a>(index: &[usize], edges: &'a [usize], rank: usize) -> &'a [usize] {
+    if rank >= index.len() {
+        panic!("Rank {} outside a graph with {} nodes", rank, index.len());
+    }
+    let start = if rank == 0 { 0 } else { index[rank - 1] };
+    &edges[start..index[rank]]
+}
+
+fn coords_of(dims: &[usize], mut rank: usize) -> Vec<usize> {
+    let mut coords = vec![0; dims.len()];
+    for i in (0..dims.len()).rev() {
+        coords[i] = rank % dims[i];
+        rank /= dims[i];
+    }
+    coords
+}
+
+/// Rank of the process at `coords`, `None` if they are outside a non-periodic dimension
+fn rank_of(dims: &[usize], periods: &[bool], coords: &[isize]) -> Option<usize> {
+    if coords.len() != dims.len() {
+        panic!("{} coordinates for a grid with {} dimensions", coords.len(), dims.len());
+    }
+
+    let mut rank = 0;
+    for i in 0..dims.len() {
+        let d = dims[i] as isize;
+        let mut c = coords[i];
+        if periods[i] {
+            c = ((c % d) + d) % d;
+        } else if c < 0 || c >= d {
+            return None;
+        }
+        rank = rank * dims[i] + c as usize;
+    }
+    Some(rank)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dims_create(nnodes: usize, dims: &[usize]) -> Vec<usize> {
+        let mut dims = dims.to_vec();
+        mpi_dims_create(nnodes, &mut dims);
+        dims
+    }
+
+    #[test]
+    fn dims_create_balanced() {
+        assert_eq!(dims_create(12, &[0, 0]), vec![4, 3]);
+        assert_eq!(dims_create(16, &[0, 0, 0]), vec![4, 2, 2]);
+        assert_eq!(dims_create(7, &[0, 0]), vec![7, 1]);
+        assert_eq!(dims_create(24, &[0, 3, 0]), vec![4, 3, 2]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn dims_create_indivisible() {
+        dims_create(10, &[3, 0]);
+    }
+
+    #[test]
+    fn cart_coords_round_trip() {
+        let dims = [2, 3, 4];
+        let periods = [false, false, false];
+        for rank in 0..24 {
+            let coords: Vec<isize> =
+                coords_of(&dims, rank).into_iter().map(|c| c as isize).collect();
+            assert_eq!(rank_of(&dims, &periods, &coords), Some(rank));
+        }
+        assert_eq!(coords_of(&dims, 7), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn graph_neighbors_index() {
+        // 0 - 1, 1 - 2, 0 - 2, 3 alone
+        let index = [2, 4, 6, 6];
+        let edges = [1, 2, 0, 2, 1, 0];
+        check_graph(&index, &edges);
+        assert_eq!(graph_neighbors(&index, &edges, 0), &[1, 2]);
+        assert_eq!(graph_neighbors(&index, &edges, 2), &[1, 0]);
+        assert!(graph_neighbors(&index, &edges, 3).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn graph_edge_out_of_range() {
+        check_graph(&[1, 2], &[1, 2]);
+    }
+
+    #[test]
+    fn dist_graph_edges_match() {
+        // A ring of three, each process sending to the next one
+        let ring = vec![(vec![2], vec![1]), (vec![0], vec![2]), (vec![1], vec![0])];
+        check_dist_graph(&ring);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not send to it")]
+    fn dist_graph_edge_listed_once() {
+        check_dist_graph(&[(vec![1], vec![]), (vec![], vec![])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn dist_graph_rank_out_of_range() {
+        check_dist_graph(&[(vec![], vec![1])]);
+    }
+
+    #[test]
+    fn cart_rank_periods() {
+        let dims = [3, 4];
+        assert_eq!(rank_of(&dims, &[true, false], &[-1, 2]), Some(10));
+        assert_eq!(rank_of(&dims, &[true, false], &[1, 4]), None);
+        assert_eq!(rank_of(&dims, &[false, true], &[1, 9]), Some(5));
+    }
+}
@@ -0,0 +1,398 @@
+//! One-sided communication through RMA windows
+//!
+//! Every process of a window exposes a vector of elements that the others read and write with
+//! `mpi_put`, `mpi_get` and the accumulates, without the target taking part. mpirun keeps the
+//! exposed memory and serves these requests on behalf of the target, so a process reads and
+//! writes its own part like any other, with its own rank as target. Each operation is complete at
+//! the target when it returns.
+//!
+//! Accesses are separated into epochs, either by `mpi_win_fence` over the whole window or by
+//! post/start/complete/wait between groups of processes. In passive-target epochs only the origin
+//! takes part: it locks the memory of a target with `mpi_win_lock`, exclusively or shared with
+//! other origins, accesses it and unlocks it again. Accumulates, `mpi_fetch_and_op` and
+//! `mpi_compare_and_swap` on the same elements are atomic with respect to each other and to puts
+//! and gets, whatever the epoch. Windows cannot span jobs connected through `port`.
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::sync::mpsc::Receiver;
+use rustc_serialize::{json, Decodable, Encodable};
+use comm_request::{CommRequest, ControlTy, RequestProc, RmaReply};
+use mpi_comm::{self, MPIComm, control, members, p2p_context, spans_jobs};
+use barrier::mpi_barrier;
+use group::{self, Group};
+use info::Info;
+use op::Op;
+use send::isend;
+use receive::recv;
+
+/// Handle to a window of elements of type `T`
+pub struct Window<T> {
+    /// Private duplicate of the communicator the window was created on
+    comm: MPIComm,
+    id: u64,
+    ty: PhantomData<T>,
+}
+
+impl<T> Clone for Window<T> {
+    fn clone(&self) -> Window<T> {
+        Window {
+            comm: self.comm,
+            id: self.id,
+            ty: PhantomData,
+        }
+    }
+}
+
+impl<T> Copy for Window<T> {}
+
+impl<T> fmt::Debug for Window<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Window({})", self.id)
+    }
+}
+
+/// Kind of lock a passive-target epoch holds on a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockType {
+    /// No other origin accesses the target until the lock is released
+    Exclusive,
+    /// Other origins holding shared locks access the target concurrently
+    Shared,
+}
+
+/// Tags of the notices post/start/complete/wait exchange on the communicator of a window
+const POST_TAG: u64 = 0;
+const COMPLETE_TAG: u64 = 1;
+
+struct WinState {
+    info: Info,
+    /// Targets of the access epoch opened by `mpi_win_start`
+    access: Option<Vec<usize>>,
+    /// Origins of the exposure epoch opened by `mpi_win_post`
+    exposure: Option<Vec<usize>>,
+    /// Targets locked by passive-target epochs
+    locks: HashMap<usize, LockType>,
+    /// Notices still on their way, which must arrive before the window is freed
+    notices: Vec<Receiver<CommRequest<bool>>>,
+}
+
+lazy_static! {
+    static ref WINDOWS: Mutex<HashMap<u64, WinState>> = Mutex::new(HashMap::new());
+}
+
+fn state<F, R>(win: u64, f: F) -> R
+    where F: FnOnce(&mut WinState) -> R
+{
+    let mut windows = WINDOWS.lock().unwrap();
+    f(windows.get_mut(&win).unwrap_or_else(|| panic!("Invalid window {}", win)))
+}
+
+fn rma<D>(ty: ControlTy, data: D) -> Vec<String>
+    where D: Debug + Clone + Encodable + Decodable
+{
+    match json::decode(&control(ty, data)).expect("Invalid reply") {
+        RmaReply::Done(elems) => elems,
+        RmaReply::Failed(e) => panic!("{}", e),
+    }
+}
+
+fn encode<T: Encodable>(values: &[T]) -> Vec<String> {
+    values.iter().map(|v| json::encode(v).unwrap()).collect()
+}
+
+fn decode<T: Decodable>(elems: &[String]) -> Vec<T> {
+    elems.iter().map(|e| json::decode(e).expect("Invalid window element")).collect()
+}
+
+/// World rank of the process with rank `target` in the window
+fn target_proc<T>(win: Window<T>, target: usize) -> usize {
+    let members = members(win.comm);
+    match members.get(target) {
+        Some(&w) => w,
+        None => panic!("Rank {} out of range for a window of {}", target, members.len()),
+    }
+}
+
+/// Ranks in the window of the processes of `group`
+fn window_ranks<T>(group: &Group, win: Window<T>) -> Vec<usize> {
+    let members = members(win.comm);
+    group.world_ranks()
+         .iter()
+         .map(|w| {
+             members.iter().position(|m| m == w).unwrap_or_else(|| {
+                 panic!("Process {} of the group is not part of {:?}", w, win)
+             })
+         })
+         .collect()
+}
+
+/// Create a window exposing `base`. The processes of `comm` may expose different numbers of
+/// elements. Collective over `comm`.
+pub fn mpi_win_create<T>(base: Vec<T>, info: &Info, comm: MPIComm) -> Window<T>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if spans_jobs(comm) {
+        panic!("Windows cannot span jobs");
+    }
+
+    let comm = mpi_comm::mpi_comm_dup(comm);
+    let id = mpi_comm::context_id(comm);
+    rma(ControlTy::WinCreate, (id, encode(&base)));
+    WINDOWS.lock().unwrap().insert(id,
+                                   WinState {
+                                       info: info.clone(),
+                                       access: None,
+                                       exposure: None,
+                                       locks: HashMap::new(),
+                                       notices: Vec::new(),
+                                   });
+
+    // No process may access the window before all have exposed their memory
+    mpi_barrier(comm);
+    Window {
+        comm: comm,
+        id: id,
+        ty: PhantomData,
+    }
+}
+
+/// Create a window exposing `size` default elements. Collective over `comm`.
+pub fn mpi_win_allocate<T>(size: usize, info: &Info, comm: MPIComm) -> Window<T>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send + Default
+{
+    mpi_win_create(vec![T::default(); size], info, comm)
+}
+
+/// Release `win` once all processes are done with it and return the elements the calling
+/// process exposed. Collective over the window.
+pub fn mpi_win_free<T>(win: Window<T>) -> Vec<T>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let notices = state(win.id, |s| s.notices.drain(..).collect::<Vec<_>>());
+    for notice in notices {
+        let _ = notice.recv();
+    }
+    mpi_barrier(win.comm);
+
+    let elems = rma(ControlTy::WinFree, win.id);
+    WINDOWS.lock().unwrap().remove(&win.id);
+    mpi_comm::mpi_comm_free(win.comm);
+    decode(&elems)
+}
+
+/// Group of the processes of `win`
+pub fn mpi_win_get_group<T>(win: Window<T>) -> Group {
+    group::mpi_comm_group(win.comm)
+}
+
+/// Replace the info hints of `win`
+pub fn mpi_win_set_info<T>(win: Window<T>, info: &Info) {
+    state(win.id, |s| s.info = info.clone());
+}
+
+pub fn mpi_win_get_info<T>(win: Window<T>) -> Info {
+    state(win.id, |s| s.info.clone())
+}
+
+/// Write `origin` to the elements of `target` starting at `disp`
+pub fn mpi_put<T>(origin: &[T], target: usize, disp: usize, win: Window<T>)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    rma(ControlTy::Put,
+        (win.id, target_proc(win, target), disp, encode(origin)));
+}
+
+/// Read `origin.len()` elements of `target` starting at `disp` into `origin`
+pub fn mpi_get<T>(origin: &mut [T], target: usize, disp: usize, win: Window<T>)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let elems = rma(ControlTy::Get,
+                    (win.id, target_proc(win, target), disp, origin.len()));
+    origin.clone_from_slice(&decode(&elems));
+}
+
+/// Combine the elements of `target` starting at `disp` with `origin`, setting each to
+/// `op(target, origin)`
+pub fn mpi_accumulate<T>(origin: &[T], target: usize, disp: usize, op: &Op<T>, win: Window<T>)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    update(origin, target, disp, op, win);
+}
+
+/// Same as `mpi_accumulate`, also reading the elements of `target` into `result` before they
+/// are combined
+pub fn mpi_get_accumulate<T>(origin: &[T],
+                             result: &mut [T],
+                             target: usize,
+                             disp: usize,
+                             op: &Op<T>,
+                             win: Window<T>)
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    if result.len() != origin.len() {
+        panic!("Result buffer of {} elements for {} elements", result.len(), origin.len());
+    }
+    result.clone_from_slice(&update(origin, target, disp, op, win));
+}
+
+/// Same as `mpi_get_accumulate` for a single element, returning the old value of the element
+pub fn mpi_fetch_and_op<T>(origin: &T,
+                           target: usize,
+                           disp: usize,
+                           op: &Op<T>,
+                           win: Window<T>)
+                           -> T
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    update(&[origin.clone()], target, disp, op, win).remove(0)
+}
+
+/// Replace the element of `target` at `disp` by `origin` if it equals `compare`. Returns the old
+/// value of the element either way.
+pub fn mpi_compare_and_swap<T>(origin: &T,
+                               compare: &T,
+                               target: usize,
+                               disp: usize,
+                               win: Window<T>)
+                               -> T
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send + PartialEq
+{
+    let proc = target_proc(win, target);
+    let old: T = decode(&rma(ControlTy::Fetch, (win.id, proc, disp, 1))).remove(0);
+    let new = if old == *compare { origin } else { &old };
+    rma(ControlTy::Store, (win.id, proc, disp, encode(&[new.clone()])));
+    old
+}
+
+/// Atomically combine the elements of `target` with `origin` and return their old values.
+/// mpirun holds the elements of `target` from the fetch to the store.
+pub(crate) fn update<T>(origin: &[T],
+                        target: usize,
+                        disp: usize,
+                        op: &Op<T>,
+                        win: Window<T>)
+                        -> Vec<T>
+    where T: 'static + Debug + Clone + Encodable + Decodable + Send
+{
+    let proc = target_proc(win, target);
+    let old: Vec<T> = decode(&rma(ControlTy::Fetch, (win.id, proc, disp, origin.len())));
+    let new = op.apply_vec(&old, origin);
+    rma(ControlTy::Store, (win.id, proc, disp, encode(&new)));
+    old
+}
+
+/// Separate the accesses to `win` before the call from those after it. Collective over the
+/// window.
+pub fn mpi_win_fence<T>(win: Window<T>) {
+    mpi_barrier(win.comm);
+}
+
+/// Expose the memory of the calling process to the processes of `group` until `mpi_win_wait`
+pub fn mpi_win_post<T>(group: &Group, win: Window<T>) {
+    let origins = window_ranks(group, win);
+    let mut notices = Vec::with_capacity(origins.len());
+    for &origin in &origins {
+        notices.push(isend(&true, RequestProc::Process(origin), POST_TAG, p2p_context(win.comm)));
+    }
+
+    state(win.id, |s| {
+        if s.exposure.is_some() {
+            panic!("{:?} is already posted", win);
+        }
+        s.exposure = Some(origins);
+        s.notices.extend(notices);
+    });
+}
+
+/// Start accessing the memory of the processes of `group`. Waits until all of them posted.
+pub fn mpi_win_start<T>(group: &Group, win: Window<T>) {
+    let targets = window_ranks(group, win);
+    if state(win.id, |s| s.access.is_some()) {
+        panic!("{:?} is already started", win);
+    }
+
+    for &target in &targets {
+        let mut posted = false;
+        recv(&mut posted,
+             RequestProc::Process(target),
+             POST_TAG,
+             p2p_context(win.comm));
+    }
+    state(win.id, |s| s.access = Some(targets));
+}
+
+/// End the access epoch opened by `mpi_win_start`
+pub fn mpi_win_complete<T>(win: Window<T>) {
+    let targets = state(win.id, |s| s.access.take())
+                      .unwrap_or_else(|| panic!("{:?} was not started", win));
+    let notices: Vec<_> = targets.iter()
+                                 .map(|&t| {
+                                     isend(&true,
+                                           RequestProc::Process(t),
+                                           COMPLETE_TAG,
+                                           p2p_context(win.comm))
+                                 })
+                                 .collect();
+    state(win.id, |s| s.notices.extend(notices));
+}
+
+/// End the exposure epoch opened by `mpi_win_post`. Waits until all origins completed their
+/// accesses.
+pub fn mpi_win_wait<T>(win: Window<T>) {
+    let origins = state(win.id, |s| s.exposure.take())
+                      .unwrap_or_else(|| panic!("{:?} was not posted", win));
+    for &origin in &origins {
+        let mut completed = false;
+        recv(&mut completed,
+             RequestProc::Process(origin),
+             COMPLETE_TAG,
+             p2p_context(win.comm));
+    }
+}
+
+/// Start a passive-target epoch on `target`. Waits until the lock is granted.
+pub fn mpi_win_lock<T>(lock_type: LockType, target: usize, win: Window<T>) {
+    if state(win.id, |s| s.locks.contains_key(&target)) {
+        panic!("Rank {} of {:?} is already locked", target, win);
+    }
+    let exclusive = lock_type == LockType::Exclusive;
+    rma(ControlTy::Lock, (win.id, target_proc(win, target), exclusive));
+    state(win.id, |s| s.locks.insert(target, lock_type));
+}
+
+/// End the passive-target epoch on `target`. The accesses of the epoch are complete.
+pub fn mpi_win_unlock<T>(target: usize, win: Window<T>) {
+    if state(win.id, |s| s.locks.remove(&target)).is_none() {
+        panic!("Rank {} of {:?} is not locked", target, win);
+    }
+    rma(ControlTy::Unlock, (win.id, target_proc(win, target)));
+}
+
+/// Lock every process of `win` shared, in rank order
+pub fn mpi_win_lock_all<T>(win: Window<T>) {
+    for target in 0..members(win.comm).len() {
+        mpi_win_lock(LockType::Shared, target, win);
+    }
+}
+
+pub fn mpi_win_unlock_all<T>(win: Window<T>) {
+    for target in 0..members(win.comm).len() {
+        mpi_win_unlock(target, win);
+    }
+}
+
+/// Complete the accesses to `target` within the passive-target epoch. Operations are complete at
+/// the target when they return, so this only checks that `target` is locked.
+pub fn mpi_win_flush<T>(target: usize, win: Window<T>) {
+    if !state(win.id, |s| s.locks.contains_key(&target)) {
+        panic!("Rank {} of {:?} is not locked", target, win);
+    }
+}
+
+/// Complete the accesses to `target` at the origin, so their buffers may be reused
+pub fn mpi_win_flush_local<T>(target: usize, win: Window<T>) {
+    mpi_win_flush(target, win);
+}